@@ -0,0 +1,51 @@
+use std::sync::Mutex;
+use std::thread;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use indicatif::ProgressBar;
+
+const INCREMENTS: u64 = 1 << 16;
+
+/// Calls `inc` `INCREMENTS` times in total, spread across `threads` threads
+fn spread(threads: u64, inc: impl Fn() + Sync) {
+    thread::scope(|s| {
+        for _ in 0..threads {
+            s.spawn(|| {
+                for _ in 0..INCREMENTS / threads {
+                    inc();
+                }
+            });
+        }
+    });
+}
+
+/// Increments a single progress bar from many threads at once, like a parallel file walker would
+fn contended_inc(c: &mut Criterion) {
+    let mut group = c.benchmark_group("contended inc");
+    group.throughput(Throughput::Elements(INCREMENTS));
+    for threads in [1, 2, 4, 8] {
+        group.bench_with_input(BenchmarkId::new("lock-free", threads), &threads, |b, &n| {
+            b.iter(|| {
+                let pb = ProgressBar::hidden();
+                spread(n, || pb.inc(1));
+                assert_eq!(pb.position(), INCREMENTS);
+            })
+        });
+
+        // What each increment cost when it locked the progress bar
+        group.bench_with_input(BenchmarkId::new("mutex", threads), &threads, |b, &n| {
+            b.iter(|| {
+                let (pb, lock) = (ProgressBar::hidden(), Mutex::new(()));
+                spread(n, || {
+                    let _guard = lock.lock().unwrap();
+                    pb.inc(1);
+                });
+                assert_eq!(pb.position(), INCREMENTS);
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, contended_inc);
+criterion_main!(benches);
@@ -0,0 +1,32 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use indicatif::{ProgressBar, ProgressStyle};
+
+const TEMPLATE: &str =
+    "{spinner:.green} [{elapsed_precise}] {prefix:>8!} {wide_bar:.cyan/blue} {pos:>7}/{len:7} {percent}% {msg:<12}";
+
+fn bar() -> ProgressBar {
+    let pb = ProgressBar::hidden().with_prefix("extracting");
+    pb.set_length(1000);
+    pb.set_position(250);
+    pb.set_message("item 250");
+    pb
+}
+
+fn render(c: &mut Criterion) {
+    let pb = bar().with_style(ProgressStyle::with_template(TEMPLATE).unwrap());
+    c.bench_function("render compiled template", |b| {
+        b.iter(|| black_box(pb.render_line_with_width(black_box(120))))
+    });
+
+    // What each draw cost when the template was parsed for every render
+    let pb = bar();
+    c.bench_function("parse and render template", |b| {
+        b.iter(|| {
+            pb.set_style(ProgressStyle::with_template(black_box(TEMPLATE)).unwrap());
+            black_box(pb.render_line_with_width(black_box(120)))
+        })
+    });
+}
+
+criterion_group!(benches, render);
+criterion_main!(benches);
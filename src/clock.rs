@@ -0,0 +1,36 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+
+#[cfg(target_arch = "wasm32")]
+use instant::Instant;
+
+/// A source of the current time for a [`ProgressBar`](crate::ProgressBar)
+///
+/// See [`ProgressBar::with_clock()`](crate::ProgressBar::with_clock). This is implemented for
+/// closures returning an [`Instant`], so a simulated clock can be as simple as a closure reading
+/// from a shared `Mutex<Instant>`.
+///
+/// A clock that goes backwards is tolerated, but time doesn't move backwards with it: durations
+/// such as [`ProgressBar::elapsed()`](crate::ProgressBar::elapsed) stop at zero rather than
+/// going negative. Updates at or before the previous one aren't used to estimate the rate, but
+/// their progress counts towards the next update that's later, and the rate is zero until the
+/// clock is past the time the estimate started.
+pub trait Clock: Send + Sync {
+    /// The current time
+    fn now(&self) -> Instant;
+}
+
+impl<F: Fn() -> Instant + Send + Sync> Clock for F {
+    fn now(&self) -> Instant {
+        self()
+    }
+}
+
+/// The system's monotonic clock
+pub(crate) struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
@@ -1,7 +1,10 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fmt::{self, Write as _};
+use std::hash::{Hash, Hasher};
 use std::io;
 use std::ops::{Add, AddAssign, Sub};
 use std::slice::SliceIndex;
-use std::sync::{Arc, RwLock, RwLockWriteGuard};
+use std::sync::{Arc, Mutex, RwLock, RwLockWriteGuard};
 use std::thread::panicking;
 use std::time::Duration;
 #[cfg(not(target_arch = "wasm32"))]
@@ -12,7 +15,8 @@ use console::Term;
 use instant::Instant;
 
 use crate::multi::{MultiProgressAlignment, MultiState};
-use crate::TermLike;
+use crate::state::uncontended;
+use crate::{ProgressState, TermLike};
 
 /// Target for draw operations
 ///
@@ -56,6 +60,23 @@ impl ProgressDrawTarget {
         Self::term(Term::buffered_stderr(), refresh_rate)
     }
 
+    /// Draw to stderr like [`stderr_with_hz`](ProgressDrawTarget::stderr_with_hz) if it's a
+    /// terminal, and otherwise write plain text lines to it like
+    /// [`plain_with_hz`](ProgressDrawTarget::plain_with_hz).
+    ///
+    /// This keeps progress visible in CI logs and other redirected output, without the escape
+    /// codes a terminal target would write. Whether stderr is a terminal is only checked once,
+    /// when the draw target is created.
+    ///
+    /// Will panic if `refresh_rate` is `0`.
+    pub fn stderr_with_hz_or_plain(refresh_rate: u8) -> Self {
+        let term = Term::buffered_stderr();
+        match term.is_term() {
+            true => Self::term(term, refresh_rate),
+            false => Self::plain_with_hz(term, refresh_rate),
+        }
+    }
+
     pub(crate) fn new_remote(state: Arc<RwLock<MultiState>>, idx: usize) -> Self {
         Self {
             kind: TargetKind::Multi { state, idx },
@@ -77,6 +98,7 @@ impl ProgressDrawTarget {
                 last_line_count: VisualLines::default(),
                 rate_limiter: RateLimiter::new(refresh_rate),
                 draw_state: DrawState::default(),
+                unicode: supports_unicode(|var| std::env::var(var).ok()),
             },
         }
     }
@@ -106,9 +128,134 @@ impl ProgressDrawTarget {
         }
     }
 
+    /// Render into a shared string buffer instead of a terminal.
+    ///
+    /// After each draw the buffer holds the rendered lines of the latest frame, joined with
+    /// newlines. Lines are rendered for a width of 80 columns and may contain ANSI codes if the
+    /// style uses colors. Drawing isn't rate limited, which makes this target useful to check the
+    /// output of a [`ProgressStyle`](crate::ProgressStyle) in tests or to embed the rendered
+    /// progress elsewhere:
+    ///
+    /// ```rust
+    /// # use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+    /// let (target, buffer) = ProgressDrawTarget::string_buffer();
+    /// let pb = ProgressBar::with_draw_target(Some(10), target)
+    ///     .with_style(ProgressStyle::with_template("{pos}/{len}").unwrap());
+    /// pb.inc(3);
+    /// assert_eq!(*buffer.lock().unwrap(), "3/10");
+    /// ```
+    pub fn string_buffer() -> (Self, Arc<Mutex<String>>) {
+        let buffer = Arc::new(Mutex::new(String::new()));
+        let target = Self {
+            kind: TargetKind::StringBuffer {
+                buffer: buffer.clone(),
+                draw_state: DrawState::default(),
+            },
+        };
+        (target, buffer)
+    }
+
+    /// Write one JSON object per line to `writer` for each redraw, at most once a second.
+    ///
+    /// Instead of rendering the progress bar's template, this target reports the raw progress
+    /// as line-delimited JSON, which is useful when output is redirected to a log file:
+    ///
+    /// ```text
+    /// {"pos":50,"len":100,"fraction":0.5,"eta_secs":12.5,"per_sec":4,"message":"working"}
+    /// ```
+    ///
+    /// `len` is `null` for progress bars without a length, and `per_sec` is `null` while no rate
+    /// has been estimated yet. Finishing the progress bar always writes a final line.
+    ///
+    /// This target only applies to individual progress bars; it can't be used as the draw target
+    /// of a [`MultiProgress`](crate::MultiProgress).
+    pub fn json(writer: impl io::Write + Send + 'static) -> Self {
+        Self::json_with_hz(writer, 1)
+    }
+
+    /// Write one JSON object per line to `writer` at a max of `refresh_rate` times a second.
+    ///
+    /// For more information see [`ProgressDrawTarget::json`].
+    ///
+    /// Will panic if `refresh_rate` is `0`.
+    pub fn json_with_hz(writer: impl io::Write + Send + 'static, refresh_rate: u8) -> Self {
+        Self {
+            kind: TargetKind::Events {
+                sink: EventSink::Json(Mutex::new(Box::new(writer))),
+                rate_limiter: RateLimiter::new(refresh_rate),
+            },
+        }
+    }
+
+    /// Write one plain text line per redraw to `writer`, at most once a second.
+    ///
+    /// Instead of rendering the progress bar's template, each line reports the position and the
+    /// length (if any) followed by the message, without any escape codes:
+    ///
+    /// ```text
+    /// 50/100 working
+    /// ```
+    ///
+    /// Finishing the progress bar always writes a final line. Like
+    /// [`ProgressDrawTarget::json`], this target can't be used as the draw target of a
+    /// [`MultiProgress`](crate::MultiProgress).
+    pub fn plain(writer: impl io::Write + Send + 'static) -> Self {
+        Self::plain_with_hz(writer, 1)
+    }
+
+    /// Write one plain text line to `writer` at a max of `refresh_rate` times a second.
+    ///
+    /// For more information see [`ProgressDrawTarget::plain`].
+    ///
+    /// Will panic if `refresh_rate` is `0`.
+    pub fn plain_with_hz(writer: impl io::Write + Send + 'static, refresh_rate: u8) -> Self {
+        Self {
+            kind: TargetKind::Events {
+                sink: EventSink::Plain(Mutex::new(Box::new(writer))),
+                rate_limiter: RateLimiter::new(refresh_rate),
+            },
+        }
+    }
+
+    /// Emit one [`tracing`] event per redraw, at most once a second.
+    ///
+    /// Instead of rendering the progress bar's template, each event is logged at the `INFO`
+    /// level with the message as its message and these fields:
+    ///
+    /// - `pos`: the current position
+    /// - `len`: the length, omitted for progress bars without a length
+    /// - `eta`: the ETA in seconds, omitted while it's unknown
+    /// - `per_sec`: the number of steps per second
+    ///
+    /// Finishing the progress bar always emits a final event. Like [`ProgressDrawTarget::json`],
+    /// this target can't be used as the draw target of a [`MultiProgress`](crate::MultiProgress).
+    #[cfg(feature = "tracing")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "tracing")))]
+    pub fn tracing() -> Self {
+        Self::tracing_with_hz(1)
+    }
+
+    /// Emit one [`tracing`] event at a max of `refresh_rate` times a second.
+    ///
+    /// For more information see [`ProgressDrawTarget::tracing`].
+    ///
+    /// Will panic if `refresh_rate` is `0`.
+    #[cfg(feature = "tracing")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "tracing")))]
+    pub fn tracing_with_hz(refresh_rate: u8) -> Self {
+        Self {
+            kind: TargetKind::Events {
+                sink: EventSink::Tracing,
+                rate_limiter: RateLimiter::new(refresh_rate),
+            },
+        }
+    }
+
     /// A hidden draw target.
     ///
-    /// This forces a progress bar to be not rendered at all.
+    /// This forces a progress bar to be not rendered at all. The progress bar's state, including
+    /// the rate and ETA estimates, is still updated as usual, so it can be read programmatically
+    /// without any terminal overhead.
     pub fn hidden() -> Self {
         Self {
             kind: TargetKind::Hidden,
@@ -128,13 +275,51 @@ impl ProgressDrawTarget {
         }
     }
 
+    /// Shows the cursor of the terminal, in case it was hidden
+    #[cfg(feature = "ctrlc")]
+    pub(crate) fn show_cursor(&self) -> io::Result<()> {
+        match self.kind {
+            TargetKind::Term { ref term, .. } => {
+                term.show_cursor()?;
+                term.flush()
+            }
+            TargetKind::Multi { ref state, .. } => state.read().unwrap().show_cursor(),
+            TargetKind::TermLike { ref inner, .. } => {
+                inner.show_cursor()?;
+                inner.flush()
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether the target is a terminal that doesn't support Unicode
+    pub(crate) fn ascii_only(&self) -> bool {
+        match self.kind {
+            TargetKind::Term { unicode, .. } => !unicode,
+            TargetKind::Multi { ref state, .. } => state.read().unwrap().ascii_only(),
+            _ => false,
+        }
+    }
+
+    /// Flushes the underlying terminal or writer, if any
+    pub(crate) fn flush(&self) -> io::Result<()> {
+        match self.kind {
+            TargetKind::Term { ref term, .. } => term.flush(),
+            TargetKind::Multi { ref state, .. } => state.read().unwrap().flush(),
+            TargetKind::TermLike { ref inner, .. } => inner.flush(),
+            TargetKind::Events { ref sink, .. } => sink.flush(),
+            TargetKind::Hidden | TargetKind::StringBuffer { .. } => Ok(()),
+        }
+    }
+
     /// Returns the current width of the draw target.
     pub(crate) fn width(&self) -> Option<u16> {
         match self.kind {
             TargetKind::Term { ref term, .. } => Some(term.size().1),
             TargetKind::Multi { ref state, .. } => state.read().unwrap().width(),
             TargetKind::TermLike { ref inner, .. } => Some(inner.width()),
-            TargetKind::Hidden => None,
+            TargetKind::StringBuffer { .. } => Some(STRING_BUFFER_WIDTH),
+            TargetKind::Hidden | TargetKind::Events { .. } => None,
         }
     }
 
@@ -163,19 +348,21 @@ impl ProgressDrawTarget {
                 last_line_count,
                 rate_limiter,
                 draw_state,
+                unicode,
             } => {
-                if !term.is_term() {
-                    return None;
+                if !term.is_term() || !(force_draw || rate_limiter.allow(now)) {
+                    return None; // not a terminal, or rate limited
                 }
 
-                match force_draw || rate_limiter.allow(now) {
-                    true => Some(Drawable::Term {
-                        term,
-                        last_line_count,
-                        draw_state,
-                    }),
-                    false => None, // rate limited
-                }
+                // Only query the size for draws that happen, it's a system call
+                let resized = draw_state.width_changed(term.size().1);
+                Some(Drawable::Term {
+                    term,
+                    last_line_count,
+                    draw_state,
+                    force_draw: force_draw || resized,
+                    unicode: *unicode,
+                })
             }
             TargetKind::Multi { idx, state, .. } => {
                 let state = state.write().unwrap();
@@ -191,19 +378,88 @@ impl ProgressDrawTarget {
                 last_line_count,
                 rate_limiter,
                 draw_state,
-            } => match force_draw || rate_limiter.as_mut().map_or(true, |r| r.allow(now)) {
-                true => Some(Drawable::TermLike {
+            } => {
+                if !(force_draw || rate_limiter.as_mut().map_or(true, |r| r.allow(now))) {
+                    return None; // rate limited
+                }
+
+                let resized = draw_state.width_changed(inner.width());
+                Some(Drawable::TermLike {
                     term_like: &**inner,
                     last_line_count,
                     draw_state,
-                }),
-                false => None, // rate limited
-            },
+                    force_draw: force_draw || resized,
+                })
+            }
+            TargetKind::StringBuffer { buffer, draw_state } => {
+                Some(Drawable::StringBuffer { buffer, draw_state })
+            }
             // Hidden, finished, or no need to refresh yet
             _ => None,
         }
     }
 
+    /// Whether a draw at `now` that isn't forced would get past the refresh rate of the target
+    pub(crate) fn would_draw(&self, now: Instant) -> bool {
+        match &self.kind {
+            TargetKind::Term {
+                term, rate_limiter, ..
+            } => term.is_term() && rate_limiter.would_allow(now),
+            TargetKind::Multi { state, .. } => state.read().unwrap().would_draw(now),
+            TargetKind::TermLike { rate_limiter, .. } => {
+                rate_limiter.as_ref().map_or(true, |r| r.would_allow(now))
+            }
+            TargetKind::StringBuffer { .. } => true,
+            TargetKind::Events { rate_limiter, .. } => rate_limiter.would_allow(now),
+            TargetKind::Hidden => false,
+        }
+    }
+
+    /// Like [`Self::drawable`], but returns `None` instead of waiting if the target is a
+    /// [`MultiProgress`](crate::MultiProgress) that's being drawn by another thread
+    pub(crate) fn try_drawable(
+        &mut self,
+        force_draw: bool,
+        now: Instant,
+    ) -> Option<Option<Drawable<'_>>> {
+        if !matches!(self.kind, TargetKind::Multi { .. }) {
+            return Some(self.drawable(force_draw, now));
+        }
+
+        match &mut self.kind {
+            TargetKind::Multi { idx, state, .. } => {
+                let state = uncontended(state.try_write())?;
+                Some(Some(Drawable::Multi {
+                    idx: *idx,
+                    state,
+                    force_draw,
+                    now,
+                }))
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Report the state to event-based targets, which don't render lines
+    ///
+    /// Returns `None` for all other targets, which are drawn through [`Self::drawable`].
+    pub(crate) fn emit(
+        &mut self,
+        state: &ProgressState,
+        force_draw: bool,
+        now: Instant,
+    ) -> Option<io::Result<()>> {
+        match &mut self.kind {
+            TargetKind::Events { sink, rate_limiter } => {
+                match force_draw || rate_limiter.allow(now) {
+                    true => Some(sink.emit(state)),
+                    false => Some(Ok(())), // rate limited
+                }
+            }
+            _ => None,
+        }
+    }
+
     /// Properly disconnects from the draw target
     pub(crate) fn disconnect(&self, now: Instant) {
         match self.kind {
@@ -220,6 +476,8 @@ impl ProgressDrawTarget {
             }
             TargetKind::Hidden => {}
             TargetKind::TermLike { .. } => {}
+            TargetKind::StringBuffer { .. } => {}
+            TargetKind::Events { .. } => {}
         };
     }
 
@@ -242,6 +500,8 @@ enum TargetKind {
         last_line_count: VisualLines,
         rate_limiter: RateLimiter,
         draw_state: DrawState,
+        /// Whether the terminal can display Unicode, see `supports_unicode()`
+        unicode: bool,
     },
     Multi {
         state: Arc<RwLock<MultiState>>,
@@ -254,6 +514,126 @@ enum TargetKind {
         rate_limiter: Option<RateLimiter>,
         draw_state: DrawState,
     },
+    StringBuffer {
+        buffer: Arc<Mutex<String>>,
+        draw_state: DrawState,
+    },
+    Events {
+        sink: EventSink,
+        rate_limiter: RateLimiter,
+    },
+}
+
+const STRING_BUFFER_WIDTH: u16 = 80;
+
+/// Destination for event-based draw targets
+enum EventSink {
+    Json(Mutex<Box<dyn io::Write + Send>>),
+    Plain(Mutex<Box<dyn io::Write + Send>>),
+    #[cfg(feature = "tracing")]
+    Tracing,
+}
+
+impl EventSink {
+    fn emit(&self, state: &ProgressState) -> io::Result<()> {
+        let mut line = String::new();
+        let writer = match self {
+            #[cfg(feature = "tracing")]
+            Self::Tracing => {
+                tracing::info!(
+                    pos = state.pos(),
+                    len = state.len(),
+                    eta = state.try_eta().map(|eta| eta.as_secs_f64()),
+                    per_sec = state.per_sec(),
+                    "{}",
+                    state.message.expanded()
+                );
+                return Ok(());
+            }
+            Self::Json(writer) => {
+                write_json_event(&mut line, state).unwrap();
+                writer
+            }
+            Self::Plain(writer) => {
+                write_plain_event(&mut line, state).unwrap();
+                writer
+            }
+        };
+
+        line.push('\n');
+        let mut writer = writer.lock().unwrap();
+        writer.write_all(line.as_bytes())?;
+        writer.flush()
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        match self {
+            Self::Json(writer) | Self::Plain(writer) => writer.lock().unwrap().flush(),
+            #[cfg(feature = "tracing")]
+            Self::Tracing => Ok(()),
+        }
+    }
+}
+
+impl fmt::Debug for EventSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Json(_) => f.write_str("Json"),
+            Self::Plain(_) => f.write_str("Plain"),
+            #[cfg(feature = "tracing")]
+            Self::Tracing => f.write_str("Tracing"),
+        }
+    }
+}
+
+fn write_plain_event(out: &mut String, state: &ProgressState) -> fmt::Result {
+    write!(out, "{}", state.pos())?;
+    if let Some(len) = state.len() {
+        write!(out, "/{len}")?;
+    }
+    match state.message.expanded() {
+        "" => Ok(()),
+        message => write!(out, " {message}"),
+    }
+}
+
+fn write_json_event(out: &mut String, state: &ProgressState) -> fmt::Result {
+    write!(out, "{{\"pos\":{},\"len\":", state.pos())?;
+    match state.len() {
+        Some(len) => write!(out, "{len}")?,
+        None => out.push_str("null"),
+    }
+    write!(
+        out,
+        ",\"fraction\":{},\"eta_secs\":{},\"per_sec\":",
+        state.fraction(),
+        state.eta().as_secs_f64()
+    )?;
+    match state.per_sec() {
+        per_sec if per_sec.is_finite() => write!(out, "{per_sec}")?,
+        _ => out.push_str("null"),
+    }
+    out.push_str(",\"message\":");
+    write_json_str(out, state.message.expanded())?;
+    out.push('}');
+    Ok(())
+}
+
+fn write_json_str(out: &mut String, s: &str) -> fmt::Result {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32)?,
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    Ok(())
 }
 
 impl TargetKind {
@@ -281,6 +661,8 @@ pub(crate) enum Drawable<'a> {
         term: &'a Term,
         last_line_count: &'a mut VisualLines,
         draw_state: &'a mut DrawState,
+        force_draw: bool,
+        unicode: bool,
     },
     Multi {
         state: RwLockWriteGuard<'a, MultiState>,
@@ -292,10 +674,34 @@ pub(crate) enum Drawable<'a> {
         term_like: &'a dyn TermLike,
         last_line_count: &'a mut VisualLines,
         draw_state: &'a mut DrawState,
+        force_draw: bool,
+    },
+    StringBuffer {
+        buffer: &'a Mutex<String>,
+        draw_state: &'a mut DrawState,
     },
 }
 
 impl<'a> Drawable<'a> {
+    /// Whether the target is a terminal that doesn't support Unicode
+    pub(crate) fn ascii_only(&self) -> bool {
+        match self {
+            Drawable::Term { unicode, .. } => !unicode,
+            Drawable::Multi { state, .. } => state.ascii_only(),
+            _ => false,
+        }
+    }
+
+    /// The width of the target, like [`ProgressDrawTarget::width()`]
+    pub(crate) fn width(&self) -> Option<u16> {
+        match self {
+            Drawable::Term { term, .. } => Some(term.size().1),
+            Drawable::Multi { state, .. } => state.width(),
+            Drawable::TermLike { term_like, .. } => Some(term_like.width()),
+            Drawable::StringBuffer { .. } => Some(STRING_BUFFER_WIDTH),
+        }
+    }
+
     /// Adjust `last_line_count` such that the next draw operation keeps/clears additional lines
     pub(crate) fn adjust_last_line_count(&mut self, adjust: LineAdjust) {
         let last_line_count: &mut VisualLines = match self {
@@ -314,11 +720,20 @@ impl<'a> Drawable<'a> {
         }
     }
 
+    /// Reports the width of the progress bar's prefix, returning the width to pad it to
+    pub(crate) fn align_prefix(&mut self, width: usize) -> Option<usize> {
+        match self {
+            Drawable::Multi { state, idx, .. } => state.align_prefix(*idx, width),
+            _ => None,
+        }
+    }
+
     pub(crate) fn state(&mut self) -> DrawStateWrapper<'_> {
         let mut state = match self {
             Drawable::Term { draw_state, .. } => DrawStateWrapper::for_term(draw_state),
             Drawable::Multi { state, idx, .. } => state.draw_state(*idx),
             Drawable::TermLike { draw_state, .. } => DrawStateWrapper::for_term(draw_state),
+            Drawable::StringBuffer { draw_state, .. } => DrawStateWrapper::for_term(draw_state),
         };
 
         state.reset();
@@ -337,7 +752,12 @@ impl<'a> Drawable<'a> {
                 term,
                 last_line_count,
                 draw_state,
-            } => draw_state.draw_to_term(term, last_line_count),
+                force_draw,
+                ..
+            } => match !force_draw && draw_state.unchanged(term.width(), *last_line_count) {
+                true => Ok(()),
+                false => draw_state.draw_to_term(term, last_line_count),
+            },
             Drawable::Multi {
                 mut state,
                 force_draw,
@@ -348,7 +768,16 @@ impl<'a> Drawable<'a> {
                 term_like,
                 last_line_count,
                 draw_state,
-            } => draw_state.draw_to_term(term_like, last_line_count),
+                force_draw,
+            } => match !force_draw && draw_state.unchanged(term_like.width(), *last_line_count) {
+                true => Ok(()),
+                false => draw_state.draw_to_term(term_like, last_line_count),
+            },
+            Drawable::StringBuffer { buffer, draw_state } => {
+                let lines = &draw_state.lines[draw_state.orphan_lines_count..];
+                *buffer.lock().unwrap() = lines.join("\n");
+                Ok(())
+            }
         }
     }
 }
@@ -404,6 +833,30 @@ impl Drop for DrawStateWrapper<'_> {
     }
 }
 
+/// Whether a terminal can display Unicode characters, such as the default progress characters
+///
+/// Windows consoles can. Other terminals are assumed to, unless there's evidence otherwise: the
+/// Linux console, whose fonts lack most of the characters, or a locale (the first one of
+/// `LC_ALL`, `LC_CTYPE` and `LANG` that's set) that doesn't use UTF-8. Without a locale, as is
+/// common in containers and CI runners, Unicode is assumed. `var` looks up an environment
+/// variable.
+fn supports_unicode(var: impl Fn(&str) -> Option<String>) -> bool {
+    if cfg!(windows) {
+        return true;
+    }
+    if var("TERM").as_deref() == Some("linux") {
+        return false;
+    }
+
+    ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .find_map(|name| var(name).filter(|value| !value.is_empty()))
+        .map_or(true, |locale| {
+            let locale = locale.to_ascii_lowercase();
+            locale.contains("utf-8") || locale.contains("utf8")
+        })
+}
+
 #[derive(Debug)]
 struct RateLimiter {
     interval: u16, // in milliseconds
@@ -421,6 +874,12 @@ impl RateLimiter {
         }
     }
 
+    /// Whether [`Self::allow`] would return `true` at `now`, without using up any capacity
+    fn would_allow(&self, now: Instant) -> bool {
+        now >= self.prev
+            && (self.capacity > 0 || now - self.prev >= Duration::from_millis(self.interval as u64))
+    }
+
     fn allow(&mut self, now: Instant) -> bool {
         if now < self.prev {
             return false;
@@ -469,6 +928,15 @@ pub(crate) struct DrawState {
     pub(crate) move_cursor: bool,
     /// Controls how the multi progress is aligned if some of its progress bars get removed, default is `Top`
     pub(crate) alignment: MultiProgressAlignment,
+    /// The terminal width during the last draw, and the widths of the (non-orphan) lines drawn
+    /// then, including the padding of the last line
+    drawn: Option<(usize, Vec<usize>)>,
+    /// A hash of the lines drawn last time and the resulting `last_line_count`, if there were no
+    /// orphan lines
+    last_frame: Option<(u64, VisualLines)>,
+    /// The lines drawn last time, if there were no orphan lines and each of them took up exactly
+    /// one visual line, so that the next draw only needs to rewrite the lines that changed
+    last_lines: Vec<String>,
 }
 
 impl DrawState {
@@ -481,7 +949,25 @@ impl DrawState {
             return Ok(());
         }
 
-        if !self.lines.is_empty() && self.move_cursor {
+        // If the terminal was resized since the last draw, the lines drawn then have been
+        // re-wrapped to the new width, so they may take up a different number of visual lines
+        let width = term.width() as usize;
+        let resized = match &self.drawn {
+            Some((prev_width, widths)) if *prev_width != width => {
+                *last_line_count = last_line_count
+                    .saturating_sub(wrapped_line_count(widths, *prev_width))
+                    .saturating_add(wrapped_line_count(widths, width));
+                true
+            }
+            _ => false,
+        };
+
+        if !resized && self.draw_changed_lines(term, *last_line_count)? {
+            return Ok(());
+        }
+
+        // Only overwrite the previous lines in place if they are still where we drew them
+        if !self.lines.is_empty() && self.move_cursor && !resized {
             // Move up to first line (assuming the last line doesn't contain a '\n') and then move to then front of the line
             term.move_cursor_up(last_line_count.as_usize().saturating_sub(1))?;
             term.write_str("\r")?;
@@ -498,7 +984,6 @@ impl DrawState {
             term.move_cursor_up(n.saturating_sub(1))?;
         }
 
-        let width = term.width() as usize;
         let visual_lines = self.visual_line_count(.., width);
         let shift = match self.alignment {
             MultiProgressAlignment::Bottom if visual_lines < *last_line_count => {
@@ -519,6 +1004,7 @@ impl DrawState {
             self.visual_line_count(..self.orphan_lines_count, term_width);
         let mut real_len = VisualLines::default();
         let mut last_line_filler = 0;
+        let mut drawn_widths = Vec::new();
         for (idx, line) in self.lines.iter().enumerate() {
             let line_width = console::measure_text_width(line);
             let diff = if line.is_empty() {
@@ -546,6 +1032,9 @@ impl DrawState {
                 }
             }
             real_len += diff;
+            if self.orphan_lines_count <= idx {
+                drawn_widths.push(line_width);
+            }
             if idx != 0 {
                 term.write_line("")?;
             }
@@ -557,17 +1046,123 @@ impl DrawState {
             }
         }
         term.write_str(&" ".repeat(last_line_filler))?;
+        if let Some(last) = drawn_widths.last_mut() {
+            *last += last_line_filler;
+        }
+        let all_drawn = drawn_widths.len() == len;
+        self.drawn = Some((term_width, drawn_widths));
 
         term.flush()?;
         *last_line_count = real_len - orphan_visual_line_count + shift;
+        self.last_frame = match self.orphan_lines_count {
+            0 => Some((self.lines_hash(), *last_line_count)),
+            _ => None,
+        };
+        self.last_lines.clear();
+        if self.orphan_lines_count == 0 && all_drawn && *last_line_count == len.into() {
+            self.last_lines.clone_from(&self.lines);
+        }
         Ok(())
     }
 
+    /// Rewrite only the lines that differ from the ones drawn last time
+    ///
+    /// Returns `false` without writing anything if the whole frame has to be repainted instead:
+    /// when there are orphan lines, when the number of lines changed or when a line doesn't fit
+    /// on a single visual line.
+    fn draw_changed_lines(
+        &mut self,
+        term: &(impl TermLike + ?Sized),
+        last_line_count: VisualLines,
+    ) -> io::Result<bool> {
+        let len = self.lines.len();
+        if len == 0
+            || self.orphan_lines_count != 0
+            || self.last_lines.len() != len
+            || last_line_count != len.into()
+            || len > term.height() as usize
+        {
+            return Ok(false);
+        }
+
+        let term_width = term.width() as usize;
+        let widths = self
+            .lines
+            .iter()
+            .map(|line| console::measure_text_width(line))
+            .collect::<Vec<_>>();
+        if widths.iter().any(|&width| width > term_width) {
+            return Ok(false);
+        }
+
+        // The cursor is at the end of the last line, where the previous draw left it
+        let last = len - 1;
+        let mut row = last;
+        for (idx, line) in self.lines.iter().enumerate() {
+            if *line == self.last_lines[idx] {
+                continue;
+            }
+
+            if idx < row {
+                term.move_cursor_up(row - idx)?;
+            } else if idx > row {
+                term.move_cursor_down(idx - row)?;
+            }
+            row = idx;
+            term.clear_line()?;
+            term.write_str(line)?;
+        }
+
+        // Leave the cursor on the right terminal side of the last line again, so that next user
+        // writes/prints will happen on the next line
+        let last_line_filler = term_width - widths[last];
+        if row != last {
+            term.move_cursor_down(last - row)?;
+            if last_line_filler > 0 {
+                // Only the padding after the unchanged last line needs to be rewritten
+                term.write_str("\r")?;
+                term.move_cursor_right(term_width - 1)?;
+                term.write_str(" ")?;
+            } else {
+                term.clear_line()?;
+                term.write_str(&self.lines[last])?;
+            }
+        } else if self.lines[last] != self.last_lines[last] {
+            term.write_str(&" ".repeat(last_line_filler))?;
+        }
+
+        let mut drawn_widths = widths;
+        drawn_widths[last] += last_line_filler;
+        self.drawn = Some((term_width, drawn_widths));
+        term.flush()?;
+        self.last_frame = Some((self.lines_hash(), last_line_count));
+        self.last_lines.clone_from(&self.lines);
+        Ok(true)
+    }
+
+    /// Whether drawing would repaint exactly what is already on the terminal
+    fn unchanged(&self, width: u16, last_line_count: VisualLines) -> bool {
+        self.orphan_lines_count == 0
+            && !self.width_changed(width)
+            && self.last_frame == Some((self.lines_hash(), last_line_count))
+    }
+
+    fn lines_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.lines.hash(&mut hasher);
+        hasher.finish()
+    }
+
     fn reset(&mut self) {
         self.lines.clear();
         self.orphan_lines_count = 0;
     }
 
+    /// Whether the terminal width differs from the one used for the last draw
+    fn width_changed(&self, width: u16) -> bool {
+        matches!(&self.drawn, Some((prev, _)) if *prev != width as usize)
+    }
+
     pub(crate) fn visual_line_count(
         &self,
         range: impl SliceIndex<[String], Output = [String]>,
@@ -622,6 +1217,15 @@ impl Sub for VisualLines {
     }
 }
 
+/// Calculate the number of visual lines taken up by lines of the given printable widths
+fn wrapped_line_count(widths: &[usize], width: usize) -> VisualLines {
+    widths
+        .iter()
+        .map(|&w| usize::max((w as f64 / width as f64).ceil() as usize, 1))
+        .sum::<usize>()
+        .into()
+}
+
 /// Calculate the number of visual lines in the given lines, after
 /// accounting for line wrapping and non-printable characters.
 pub(crate) fn visual_line_count(lines: &[impl AsRef<str>], width: usize) -> VisualLines {
@@ -639,7 +1243,257 @@ pub(crate) fn visual_line_count(lines: &[impl AsRef<str>], width: usize) -> Visu
 
 #[cfg(test)]
 mod tests {
-    use crate::{MultiProgress, ProgressBar, ProgressDrawTarget};
+    use std::io;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use portable_atomic::{AtomicU16, AtomicUsize, Ordering};
+
+    use super::{
+        supports_unicode, DrawState, Drawable, EventSink, Instant, TargetKind, VisualLines,
+    };
+    use crate::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle, TermLike};
+
+    /// Records the cursor movements, clears, writes and flushes issued to it
+    #[derive(Clone, Debug, Default)]
+    struct ResizableTerm {
+        width: Arc<AtomicU16>,
+        ops: Arc<Mutex<Vec<String>>>,
+        writes: Arc<Mutex<Vec<String>>>,
+        flushes: Arc<AtomicUsize>,
+    }
+
+    impl ResizableTerm {
+        fn take_ops(&self) -> Vec<String> {
+            std::mem::take(&mut *self.ops.lock().unwrap())
+        }
+
+        fn push(&self, op: String) -> io::Result<()> {
+            self.ops.lock().unwrap().push(op);
+            Ok(())
+        }
+    }
+
+    impl TermLike for ResizableTerm {
+        fn width(&self) -> u16 {
+            self.width.load(Ordering::SeqCst)
+        }
+        fn move_cursor_up(&self, n: usize) -> io::Result<()> {
+            self.push(format!("up {n}"))
+        }
+        fn move_cursor_down(&self, n: usize) -> io::Result<()> {
+            self.push(format!("down {n}"))
+        }
+        fn move_cursor_right(&self, n: usize) -> io::Result<()> {
+            self.push(format!("right {n}"))
+        }
+        fn move_cursor_left(&self, n: usize) -> io::Result<()> {
+            self.push(format!("left {n}"))
+        }
+        fn write_line(&self, _: &str) -> io::Result<()> {
+            self.push("newline".into())
+        }
+        fn write_str(&self, s: &str) -> io::Result<()> {
+            self.writes.lock().unwrap().push(s.into());
+            Ok(())
+        }
+        fn clear_line(&self) -> io::Result<()> {
+            self.push("clear".into())
+        }
+        fn flush(&self) -> io::Result<()> {
+            self.flushes.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn explicit_flush() {
+        let term = ResizableTerm::default();
+        let pb = ProgressBar::with_draw_target(
+            Some(10),
+            ProgressDrawTarget::term_like(Box::new(term.clone())),
+        );
+        let flushes = term.flushes.load(Ordering::SeqCst);
+        pb.flush().unwrap();
+        assert_eq!(term.flushes.load(Ordering::SeqCst), flushes + 1);
+
+        // Progress bars in a `MultiProgress` flush the `MultiProgress` draw target
+        let term = ResizableTerm::default();
+        let mp =
+            MultiProgress::with_draw_target(ProgressDrawTarget::term_like(Box::new(term.clone())));
+        let pb = mp.add(ProgressBar::new(10));
+        let flushes = term.flushes.load(Ordering::SeqCst);
+        mp.flush().unwrap();
+        pb.flush().unwrap();
+        assert_eq!(term.flushes.load(Ordering::SeqCst), flushes + 2);
+
+        ProgressBar::hidden().flush().unwrap();
+    }
+
+    #[test]
+    fn redraw_only_rewrites_changed_lines() {
+        let term = ResizableTerm {
+            width: Arc::new(AtomicU16::new(40)),
+            ..Default::default()
+        };
+        let take_writes = || std::mem::take(&mut *term.writes.lock().unwrap());
+        let mut last_line_count = VisualLines::default();
+        let mut state = DrawState::default();
+
+        let lines = |lines: &[&str]| lines.iter().map(|&line| line.to_owned()).collect();
+        state.lines = lines(&["one", "two", "three"]);
+        state.draw_to_term(&term, &mut last_line_count).unwrap();
+        term.take_ops();
+        assert_eq!(take_writes()[..3], ["one", "two", "three"]);
+
+        // Only the changed middle line is rewritten, then the cursor goes back to the last line
+        state.lines = lines(&["one", "TWO", "three"]);
+        state.draw_to_term(&term, &mut last_line_count).unwrap();
+        assert_eq!(term.take_ops(), ["up 1", "clear", "down 1", "right 39"]);
+        assert_eq!(take_writes(), ["TWO", "\r", " "]);
+        assert_eq!(last_line_count, VisualLines::from(3usize));
+
+        // A changed last line is rewritten along with its padding
+        state.lines = lines(&["one", "TWO", "THREE"]);
+        state.draw_to_term(&term, &mut last_line_count).unwrap();
+        assert_eq!(term.take_ops(), ["clear"]);
+        assert_eq!(take_writes(), ["THREE".to_owned(), " ".repeat(35)]);
+
+        // Adding a line repaints the whole frame
+        state.lines = lines(&["one", "TWO", "THREE", "four"]);
+        state.draw_to_term(&term, &mut last_line_count).unwrap();
+        let writes = take_writes();
+        for line in ["one", "TWO", "THREE", "four"] {
+            assert!(writes.iter().any(|write| write == line), "{writes:?}");
+        }
+        assert_eq!(last_line_count, VisualLines::from(4usize));
+
+        // As does a line that wraps
+        state.lines = lines(&["one", &"x".repeat(50), "THREE", "four"]);
+        state.draw_to_term(&term, &mut last_line_count).unwrap();
+        assert_eq!(take_writes().len(), 5);
+        assert_eq!(last_line_count, VisualLines::from(5usize));
+    }
+
+    #[test]
+    fn shrinking_terminal_clears_rewrapped_lines() {
+        let term = ResizableTerm {
+            width: Arc::new(AtomicU16::new(40)),
+            ..Default::default()
+        };
+        let mut last_line_count = VisualLines::default();
+        let mut state = DrawState {
+            lines: vec!["a".repeat(40), "b".repeat(10)],
+            ..Default::default()
+        };
+        state.draw_to_term(&term, &mut last_line_count).unwrap();
+        assert_eq!(last_line_count, VisualLines::from(2usize));
+        term.take_ops();
+
+        // Both lines (the second one padded to the full width) now wrap onto two lines each
+        term.width.store(20, Ordering::SeqCst);
+        assert!(state.width_changed(20));
+        state.lines = vec!["a".repeat(20), "b".repeat(10)];
+        state.draw_to_term(&term, &mut last_line_count).unwrap();
+        let clears = term.take_ops().iter().filter(|op| *op == "clear").count();
+        assert_eq!(clears, 4);
+        assert_eq!(last_line_count, VisualLines::from(2usize));
+        assert!(!state.width_changed(20));
+
+        // Growing the terminal again doesn't clear more than needed
+        term.width.store(80, Ordering::SeqCst);
+        state.draw_to_term(&term, &mut last_line_count).unwrap();
+        let clears = term.take_ops().iter().filter(|op| *op == "clear").count();
+        assert_eq!(clears, 2);
+    }
+
+    #[test]
+    fn resize_disables_cursor_reuse_for_one_draw() {
+        let term = ResizableTerm {
+            width: Arc::new(AtomicU16::new(40)),
+            ..Default::default()
+        };
+        let mut last_line_count = VisualLines::default();
+        let mut state = DrawState {
+            move_cursor: true,
+            ..Default::default()
+        };
+
+        state.lines = vec!["a".repeat(40)];
+        state.draw_to_term(&term, &mut last_line_count).unwrap();
+        state.draw_to_term(&term, &mut last_line_count).unwrap();
+        assert!(!term.take_ops().contains(&"clear".to_string()));
+
+        term.width.store(10, Ordering::SeqCst);
+        state.lines = vec!["a".repeat(10)];
+        state.draw_to_term(&term, &mut last_line_count).unwrap();
+        let clears = term.take_ops().iter().filter(|op| *op == "clear").count();
+        assert_eq!(clears, 4);
+    }
+
+    #[test]
+    fn resize_redraws_on_next_allowed_draw() {
+        let width = Arc::new(AtomicU16::new(40));
+        let term = ResizableTerm {
+            width: width.clone(),
+            ..Default::default()
+        };
+        let mut target = ProgressDrawTarget::term_like_with_hz(Box::new(term), 1);
+
+        // use up the rate limiter's burst capacity
+        let now = Instant::now();
+        while let Some(mut drawable) = target.drawable(false, now) {
+            drawable.state().lines.push("a".repeat(40));
+            drawable.draw().unwrap();
+        }
+
+        // The width is only checked once the rate limiter allows a draw, which is then forced
+        width.store(20, Ordering::SeqCst);
+        assert!(target.drawable(false, now).is_none());
+        match target.drawable(false, now + Duration::from_secs(1)) {
+            Some(Drawable::TermLike { force_draw, .. }) => assert!(force_draw),
+            _ => panic!("expected a draw after the rate limit"),
+        };
+    }
+
+    #[test]
+    fn identical_frames_are_skipped() {
+        let term = ResizableTerm {
+            width: Arc::new(AtomicU16::new(40)),
+            ..Default::default()
+        };
+        let pb = ProgressBar::with_draw_target(
+            Some(1000),
+            ProgressDrawTarget::term_like(Box::new(term.clone())),
+        )
+        .with_style(ProgressStyle::with_template("{percent}%").unwrap());
+
+        pb.tick();
+        assert!(!term.take_ops().is_empty());
+        for pos in 1..5 {
+            pb.set_position(pos);
+            pb.tick();
+        }
+        assert_eq!(term.take_ops(), Vec::<String>::new());
+
+        pb.set_position(20);
+        pb.tick();
+        assert!(!term.take_ops().is_empty());
+
+        // A resize invalidates the last frame
+        term.width.store(30, Ordering::SeqCst);
+        pb.tick();
+        assert!(!term.take_ops().is_empty());
+
+        // Forced draws aren't skipped, though they only rewrite the lines that changed
+        pb.set_position(1000);
+        pb.tick();
+        term.take_ops();
+        let flushes = term.flushes.load(Ordering::SeqCst);
+        pb.finish();
+        assert_eq!(term.take_ops(), Vec::<String>::new());
+        assert_eq!(term.flushes.load(Ordering::SeqCst), flushes + 1);
+    }
 
     #[test]
     fn multi_is_hidden() {
@@ -650,6 +1504,275 @@ mod tests {
         assert!(pb.is_hidden());
     }
 
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn json_target_emits_parsable_lines() {
+        let buf = SharedBuffer::default();
+        let pb = ProgressBar::with_draw_target(Some(100), ProgressDrawTarget::json(buf.clone()));
+        assert!(!pb.is_hidden());
+        pb.set_message("working");
+        for _ in 0..100 {
+            pb.inc(1);
+        }
+        pb.finish_with_message("done \"quoted\"\n\u{1}");
+
+        let output = String::from_utf8(buf.0.lock().unwrap().clone()).unwrap();
+        let events = output
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>();
+
+        // rate limited, but the final state is always reported
+        assert!(events.len() > 1 && events.len() < 100);
+        assert_eq!(events[0]["message"], "working");
+        assert_eq!(events[0]["len"], 100);
+        let last = events.last().unwrap();
+        assert_eq!(last["pos"], 100);
+        assert_eq!(last["fraction"], 1.0);
+        assert_eq!(last["eta_secs"], 0.0);
+        assert!(last["per_sec"].is_number());
+        assert_eq!(last["message"], "done \"quoted\"\n\u{1}");
+    }
+
+    #[test]
+    fn json_target_without_length() {
+        let buf = SharedBuffer::default();
+        let pb = ProgressBar::with_draw_target(None, ProgressDrawTarget::json(buf.clone()));
+        pb.tick();
+
+        let output = String::from_utf8(buf.0.lock().unwrap().clone()).unwrap();
+        let event: serde_json::Value =
+            serde_json::from_str(output.lines().next().unwrap()).unwrap();
+        assert!(event["len"].is_null());
+        assert!(event["per_sec"].as_f64().map_or(true, |rate| rate == 0.0));
+        assert_eq!(event["pos"], 0);
+    }
+
+    #[test]
+    fn plain_target_emits_throttled_lines() {
+        let time = Arc::new(Mutex::new(Instant::now()));
+        let clock = time.clone();
+
+        let buf = SharedBuffer::default();
+        let pb = ProgressBar::with_draw_target(
+            Some(600),
+            ProgressDrawTarget::plain_with_hz(buf.clone(), 1),
+        )
+        .with_clock(move || *clock.lock().unwrap());
+        pb.set_message("working");
+
+        // One update every 100ms for a minute
+        for _ in 0..600 {
+            *time.lock().unwrap() += Duration::from_millis(100);
+            pb.inc(1);
+            pb.tick();
+        }
+        pb.finish_with_message("done");
+
+        let output = String::from_utf8(buf.0.lock().unwrap().clone()).unwrap();
+        let lines = output.lines().collect::<Vec<_>>();
+        // After the initial burst, lines are written once a second
+        assert!(lines.len() > 60 && lines.len() < 100, "{}", lines.len());
+        assert_eq!(lines[0], "1/600 working");
+        assert_eq!(*lines.last().unwrap(), "600/600 done");
+        assert!(!output.contains('\x1b'));
+
+        let buf = SharedBuffer::default();
+        let pb = ProgressBar::with_draw_target(None, ProgressDrawTarget::plain(buf.clone()));
+        pb.inc(3);
+        pb.tick();
+        let output = String::from_utf8(buf.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(output.lines().last(), Some("3"));
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn tracing_target_emits_throttled_events() {
+        use std::collections::HashMap;
+        use std::fmt;
+
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata, Subscriber};
+
+        type Fields = HashMap<&'static str, String>;
+
+        /// Records the fields of each event
+        #[derive(Clone, Default)]
+        struct Recorder(Arc<Mutex<Vec<Fields>>>);
+
+        struct FieldVisitor<'a>(&'a mut Fields);
+
+        impl Visit for FieldVisitor<'_> {
+            fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+                self.0.insert(field.name(), format!("{value:?}"));
+            }
+        }
+
+        impl Subscriber for Recorder {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+            fn new_span(&self, _: &Attributes<'_>) -> Id {
+                Id::from_u64(1)
+            }
+            fn record(&self, _: &Id, _: &Record<'_>) {}
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+            fn event(&self, event: &Event<'_>) {
+                let mut fields = Fields::new();
+                event.record(&mut FieldVisitor(&mut fields));
+                self.0.lock().unwrap().push(fields);
+            }
+            fn enter(&self, _: &Id) {}
+            fn exit(&self, _: &Id) {}
+        }
+
+        let time = Arc::new(Mutex::new(Instant::now()));
+        let clock = time.clone();
+        let recorder = Recorder::default();
+        tracing::subscriber::with_default(recorder.clone(), || {
+            let pb = ProgressBar::with_draw_target(Some(600), ProgressDrawTarget::tracing())
+                .with_clock(move || *clock.lock().unwrap());
+            pb.set_message("working");
+
+            // One update every 100ms for a minute
+            for _ in 0..600 {
+                *time.lock().unwrap() += Duration::from_millis(100);
+                pb.inc(1);
+                pb.tick();
+            }
+            pb.finish_with_message("done");
+        });
+        let events = std::mem::take(&mut *recorder.0.lock().unwrap());
+
+        // After the initial burst, events are emitted once a second
+        assert!(events.len() > 60 && events.len() < 100, "{}", events.len());
+        let first = &events[0];
+        assert_eq!(
+            (&*first["pos"], &*first["len"], &*first["message"]),
+            ("1", "600", "working")
+        );
+        let last = events.last().unwrap();
+        assert_eq!(
+            (&*last["pos"], &*last["eta"], &*last["message"]),
+            ("600", "0.0", "done")
+        );
+        assert!(events
+            .iter()
+            .all(|event| event["per_sec"].parse::<f64>().is_ok()));
+
+        // Unknown values are left out
+        let recorder = Recorder::default();
+        tracing::subscriber::with_default(recorder.clone(), || {
+            let pb = ProgressBar::with_draw_target(None, ProgressDrawTarget::tracing());
+            pb.inc(3);
+            pb.abandon();
+        });
+        let events = std::mem::take(&mut *recorder.0.lock().unwrap());
+        assert!(events.iter().all(|event| !event.contains_key("len")));
+        assert_eq!(events[0]["pos"], "3");
+        assert!(!events[0].contains_key("eta"));
+        assert_eq!(events.last().unwrap()["eta"], "0.0");
+    }
+
+    #[test]
+    fn stderr_or_plain_depends_on_tty() {
+        let target = ProgressDrawTarget::stderr_with_hz_or_plain(20);
+        match console::Term::stderr().is_term() {
+            true => assert!(matches!(target.kind, TargetKind::Term { .. })),
+            false => assert!(matches!(
+                target.kind,
+                TargetKind::Events {
+                    sink: EventSink::Plain(_),
+                    ..
+                }
+            )),
+        }
+    }
+
+    #[test]
+    fn string_buffer_captures_latest_frame() {
+        let (target, buffer) = ProgressDrawTarget::string_buffer();
+        let pb = ProgressBar::with_draw_target(Some(10), target).with_style(
+            ProgressStyle::with_template("[{bar:10}] {percent}%\n{msg}")
+                .unwrap()
+                .progress_chars("#>-"),
+        );
+        pb.set_message("halfway");
+        pb.inc(5);
+        assert_eq!(*buffer.lock().unwrap(), "[#####>----] 50%\nhalfway");
+
+        pb.println("hello");
+        assert_eq!(*buffer.lock().unwrap(), "[#####>----] 50%\nhalfway");
+
+        pb.finish_and_clear();
+        assert_eq!(*buffer.lock().unwrap(), "");
+    }
+
+    #[test]
+    fn string_buffer_as_multi_target() {
+        let (target, buffer) = ProgressDrawTarget::string_buffer();
+        let mp = MultiProgress::with_draw_target(target);
+        let style = ProgressStyle::with_template("{prefix} {pos}/{len}").unwrap();
+        let a = mp.add(
+            ProgressBar::new(5)
+                .with_style(style.clone())
+                .with_prefix("a"),
+        );
+        let b = mp.add(ProgressBar::new(7).with_style(style).with_prefix("b"));
+        a.inc(1);
+        b.inc(2);
+        assert_eq!(*buffer.lock().unwrap(), "a 1/5\nb 2/7");
+    }
+
+    #[test]
+    fn unicode_support_follows_locale() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| value.to_string())
+            }
+        };
+        if cfg!(windows) {
+            assert!(supports_unicode(env(&[])));
+            return;
+        }
+
+        assert!(supports_unicode(env(&[("LANG", "en_US.UTF-8")])));
+        assert!(supports_unicode(env(&[
+            ("LC_ALL", "C.utf8"),
+            ("LANG", "C")
+        ])));
+        assert!(supports_unicode(env(&[
+            ("LC_ALL", ""),
+            ("LANG", "en_US.UTF-8")
+        ])));
+        assert!(!supports_unicode(env(&[
+            ("LC_ALL", "C"),
+            ("LANG", "en_US.UTF-8")
+        ])));
+        // An unset locale isn't evidence of a terminal without Unicode support
+        assert!(supports_unicode(env(&[])));
+        assert!(supports_unicode(env(&[("LANG", "")])));
+        assert!(!supports_unicode(env(&[("TERM", "linux")])));
+        assert!(!supports_unicode(env(&[
+            ("TERM", "linux"),
+            ("LANG", "en_US.UTF-8")
+        ])));
+    }
+
     #[test]
     fn real_line_count_test() {
         #[derive(Debug)]
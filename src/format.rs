@@ -71,6 +71,50 @@ pub struct HumanCount(pub u64);
 #[derive(Debug)]
 pub struct HumanFloatCount(pub f64);
 
+/// Formats counts for human readability using SI prefixes
+///
+/// Unlike [`DecimalBytes`], this has no unit, and numbers from a thousand on are shown with a
+/// single decimal.
+///
+/// # Examples
+/// ```rust
+/// # use indicatif::SiCount;
+/// assert_eq!("999",  format!("{}", SiCount(999.0)));
+/// assert_eq!("12.5", format!("{}", SiCount(12.5)));
+/// assert_eq!("1.5k", format!("{}", SiCount(1_500.0)));
+/// assert_eq!("2.3M", format!("{}", SiCount(2_300_000.0)));
+/// assert_eq!("4.0G", format!("{}", SiCount(4e9)));
+/// ```
+#[derive(Debug)]
+pub struct SiCount(pub f64);
+
+/// How [`format_duration()`] renders a duration
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Precision {
+    /// A single rounded unit, as rendered by `{elapsed}` and `{eta}` (e.g. `62m`)
+    Coarse,
+    /// Hours, minutes and seconds, as rendered by `{elapsed_precise}` and `{eta_precise}`
+    /// (e.g. `01:02:03`)
+    Precise,
+}
+
+/// Formats a duration the same way the progress bar templates do
+///
+/// # Examples
+/// ```rust
+/// # use std::time::Duration;
+/// # use indicatif::{format_duration, Precision};
+/// let d = Duration::from_secs(3723);
+/// assert_eq!("62m", format_duration(d, Precision::Coarse));
+/// assert_eq!("01:02:03", format_duration(d, Precision::Precise));
+/// ```
+pub fn format_duration(duration: Duration, precision: Precision) -> String {
+    match precision {
+        Precision::Coarse => format!("{:#}", HumanDuration(duration)),
+        Precision::Precise => FormattedDuration(duration).to_string(),
+    }
+}
+
 impl fmt::Display for FormattedDuration {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut t = self.0.as_secs();
@@ -183,6 +227,34 @@ impl fmt::Display for HumanCount {
     }
 }
 
+impl fmt::Display for SiCount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const PREFIXES: [&str; 8] = ["k", "M", "G", "T", "P", "E", "Z", "Y"];
+
+        // Round to the displayed decimal before picking the prefix, so that a number rounding
+        // up to a thousand moves on to the next prefix instead of showing as e.g. `1000.0k`
+        let round = |number: f64| (number * 10.0).round() / 10.0;
+        let number = self.0;
+        if !number.is_finite() {
+            return write!(f, "{number}");
+        } else if round(number).abs() < 1000.0 {
+            return match number.fract() == 0.0 {
+                true => write!(f, "{number:.0}"),
+                false => write!(f, "{:.1}", round(number)),
+            };
+        }
+
+        let mut scaled = number;
+        for (i, prefix) in PREFIXES.iter().enumerate() {
+            scaled /= 1000.0;
+            if round(scaled).abs() < 1000.0 || i == PREFIXES.len() - 1 {
+                return write!(f, "{:.1}{prefix}", round(scaled));
+            }
+        }
+        unreachable!()
+    }
+}
+
 impl fmt::Display for HumanFloatCount {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use fmt::Write;
@@ -335,6 +407,27 @@ mod tests {
         assert_eq!("3 years", format!("{}", HumanDuration(3 * YEAR)));
     }
 
+    #[test]
+    fn si_count() {
+        assert_eq!("0", format!("{}", SiCount(0.0)));
+        assert_eq!("999", format!("{}", SiCount(999.0)));
+        assert_eq!("0.5", format!("{}", SiCount(0.5)));
+        assert_eq!("1.0k", format!("{}", SiCount(1_000.0)));
+        assert_eq!("1.5k", format!("{}", SiCount(1_500.0)));
+        assert_eq!("2.3M", format!("{}", SiCount(2_300_000.0)));
+        assert_eq!("18.4E", format!("{}", SiCount(u64::MAX as f64)));
+        assert_eq!("-1.5k", format!("{}", SiCount(-1_500.0)));
+
+        // Rounding up to a thousand carries into the next prefix
+        assert_eq!("999.9", format!("{}", SiCount(999.94)));
+        assert_eq!("1.0k", format!("{}", SiCount(999.95)));
+        assert_eq!("999.9k", format!("{}", SiCount(999_940.0)));
+        assert_eq!("1.0M", format!("{}", SiCount(999_950.0)));
+        assert_eq!("1.0G", format!("{}", SiCount(999_999_999.0)));
+        assert_eq!("13.0", format!("{}", SiCount(12.97)));
+        assert_eq!("inf", format!("{}", SiCount(f64::INFINITY)));
+    }
+
     #[test]
     fn human_count() {
         assert_eq!("42", format!("{}", HumanCount(42)));
@@ -367,4 +460,19 @@ mod tests {
             format!("{}", HumanFloatCount(1234567890.1234321))
         );
     }
+
+    #[test]
+    fn format_duration_precisions() {
+        let cases = [
+            (400 * MILLI, "0s", "00:00:00"),
+            (Duration::from_secs(59), "59s", "00:00:59"),
+            (Duration::from_secs(3723), "62m", "01:02:03"),
+            (2 * HOUR, "2h", "02:00:00"),
+            (DAY + HOUR, "25h", "1d 01:00:00"),
+        ];
+        for (duration, coarse, precise) in cases {
+            assert_eq!(coarse, format_duration(duration, Precision::Coarse));
+            assert_eq!(precise, format_duration(duration, Precision::Precise));
+        }
+    }
 }
@@ -190,6 +190,10 @@ impl TermLike for InMemoryTerm {
         state.history.push(Move::Flush);
         state.parser.flush()
     }
+
+    fn show_cursor(&self) -> std::io::Result<()> {
+        self.state.lock().unwrap().write_str("\x1b[?25h")
+    }
 }
 
 struct InMemoryTermState {
@@ -246,6 +250,10 @@ mod test {
             .cursor_position()
     }
 
+    fn cursor_hidden(in_mem: &InMemoryTerm) -> bool {
+        in_mem.state.lock().unwrap().parser.screen().hide_cursor()
+    }
+
     #[test]
     fn line_wrapping() {
         let in_mem = InMemoryTerm::new(10, 5);
@@ -396,4 +404,41 @@ NewLine
         in_mem.move_cursor_right(0).unwrap();
         assert_eq!(cursor_pos(&in_mem), (1, 1));
     }
+
+    #[test]
+    fn cursor_stays_visible() {
+        use crate::{MultiProgress, ProgressBar, ProgressDrawTarget};
+
+        let in_mem = InMemoryTerm::new(10, 80);
+        let target = || ProgressDrawTarget::term_like(Box::new(in_mem.clone()));
+
+        // Progress bars never hide the cursor, so there's nothing to restore on any finish path
+        let pb = ProgressBar::with_draw_target(Some(10), target());
+        pb.inc(5);
+        assert!(!cursor_hidden(&in_mem));
+        pb.finish_and_clear();
+        assert!(!cursor_hidden(&in_mem));
+
+        let pb = ProgressBar::with_draw_target(Some(10), target());
+        pb.inc(5);
+        pb.abandon();
+        drop(pb);
+        assert!(!cursor_hidden(&in_mem));
+
+        let mp = MultiProgress::with_draw_target(target());
+        let pb1 = mp.add(ProgressBar::new(10));
+        let pb2 = mp.add(ProgressBar::new(10));
+        pb1.inc(1);
+        pb2.finish();
+        drop(pb1);
+        drop(pb2);
+        drop(mp);
+        assert!(!cursor_hidden(&in_mem));
+
+        // A cursor hidden by the application is shown again on request
+        in_mem.write_str("\x1b[?25l").unwrap();
+        assert!(cursor_hidden(&in_mem));
+        in_mem.show_cursor().unwrap();
+        assert!(!cursor_hidden(&in_mem));
+    }
 }
@@ -0,0 +1,217 @@
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, RwLock, Weak};
+
+use crate::multi::MultiState;
+use crate::progress_bar::{ProgressBar, WeakProgressBar};
+
+/// Progress bars that are cleaned up when the process is interrupted
+static REGISTRY: Mutex<Registry> = Mutex::new(Registry {
+    installed: false,
+    targets: Vec::new(),
+});
+
+/// Set by the installed Ctrl-C handler once it restored the progress bars
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Whether the installed Ctrl-C handler exits the process, see [`set_exit_on_interrupt()`]
+static EXIT_ON_INTERRUPT: AtomicBool = AtomicBool::new(true);
+
+struct Registry {
+    /// Whether the Ctrl-C handler was installed
+    installed: bool,
+    targets: Vec<Target>,
+}
+
+pub(crate) enum Target {
+    Bar(WeakProgressBar),
+    Multi(Weak<RwLock<MultiState>>),
+}
+
+impl Target {
+    fn is_alive(&self) -> bool {
+        match self {
+            Self::Bar(bar) => bar.upgrade().is_some(),
+            Self::Multi(state) => state.strong_count() > 0,
+        }
+    }
+
+    fn restore(&self) {
+        match self {
+            Self::Bar(bar) => {
+                if let Some(pb) = bar.upgrade() {
+                    abandon(&pb);
+                    let _ = pb.state().draw_target.show_cursor();
+                }
+            }
+            Self::Multi(state) => {
+                if let Some(state) = state.upgrade() {
+                    // Don't hold the lock of the `MultiProgress` while drawing its progress bars
+                    let bars = state.read().unwrap().bars();
+                    for pb in bars.iter().filter_map(WeakProgressBar::upgrade) {
+                        abandon(&pb);
+                    }
+                    let _ = state.read().unwrap().show_cursor();
+                }
+            }
+        }
+    }
+}
+
+fn abandon(pb: &ProgressBar) {
+    if !pb.is_finished() {
+        pb.abandon();
+    }
+}
+
+/// Registers `target` and installs the Ctrl-C handler, unless that was done before
+pub(crate) fn register(target: Target) -> Result<(), ctrlc::Error> {
+    let mut registry = track(target);
+    if !registry.installed {
+        // Fails instead of replacing a handler that was installed before, which we couldn't call
+        ctrlc::try_set_handler(|| {
+            handle_interrupt();
+            let again = INTERRUPTED.swap(true, Ordering::SeqCst);
+            if again || EXIT_ON_INTERRUPT.load(Ordering::SeqCst) {
+                process::exit(130);
+            }
+        })?;
+        registry.installed = true;
+    }
+    Ok(())
+}
+
+fn track(target: Target) -> std::sync::MutexGuard<'static, Registry> {
+    let mut registry = REGISTRY.lock().unwrap();
+    registry.targets.retain(Target::is_alive);
+    registry.targets.push(target);
+    registry
+}
+
+/// Abandons the unfinished progress bars that registered a Ctrl-C handler and shows the cursor
+///
+/// This is what the handler installed by [`ProgressBar::install_ctrlc_handler()`] and
+/// [`MultiProgress::install_ctrlc_handler()`](crate::MultiProgress::install_ctrlc_handler) does
+/// before exiting the process. If the application has its own Ctrl-C handler, installing
+/// another one fails, but the progress bars are still registered, so call this function from
+/// the application's handler instead.
+#[cfg_attr(docsrs, doc(cfg(feature = "ctrlc")))]
+pub fn handle_interrupt() {
+    let targets = std::mem::take(&mut REGISTRY.lock().unwrap().targets);
+    for target in &targets {
+        target.restore();
+    }
+}
+
+/// Sets whether the Ctrl-C handler installed by this crate exits the process
+///
+/// By default, the handler exits with status 130 once it restored the progress bars. Passing
+/// `false` leaves the process running instead, so the application can check [`interrupted()`]
+/// (e.g. between units of work) and shut down from its own threads. A second Ctrl-C always
+/// exits, so the process can still be stopped if it never checks.
+#[cfg_attr(docsrs, doc(cfg(feature = "ctrlc")))]
+pub fn set_exit_on_interrupt(exit: bool) {
+    EXIT_ON_INTERRUPT.store(exit, Ordering::SeqCst);
+}
+
+/// Returns whether the Ctrl-C handler installed by this crate was triggered
+///
+/// This is only useful after [`set_exit_on_interrupt(false)`](set_exit_on_interrupt), as the
+/// handler exits the process otherwise.
+#[cfg_attr(docsrs, doc(cfg(feature = "ctrlc")))]
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::{MultiProgress, ProgressDrawTarget, ProgressStyle, TermLike};
+
+    /// Records the lines, clears and shown cursors issued to it
+    #[derive(Clone, Debug, Default)]
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl Recorder {
+        fn push(&self, op: String) -> io::Result<()> {
+            self.0.lock().unwrap().push(op);
+            Ok(())
+        }
+
+        fn take(&self) -> Vec<String> {
+            std::mem::take(&mut *self.0.lock().unwrap())
+        }
+    }
+
+    impl TermLike for Recorder {
+        fn width(&self) -> u16 {
+            80
+        }
+        fn move_cursor_up(&self, _: usize) -> io::Result<()> {
+            Ok(())
+        }
+        fn move_cursor_down(&self, _: usize) -> io::Result<()> {
+            Ok(())
+        }
+        fn move_cursor_right(&self, _: usize) -> io::Result<()> {
+            Ok(())
+        }
+        fn move_cursor_left(&self, _: usize) -> io::Result<()> {
+            Ok(())
+        }
+        fn write_line(&self, s: &str) -> io::Result<()> {
+            self.push(s.into())
+        }
+        fn write_str(&self, s: &str) -> io::Result<()> {
+            // Skip the padding that overwrites the rest of the last line
+            match s.trim_end() {
+                "" => Ok(()),
+                s => self.push(s.into()),
+            }
+        }
+        fn clear_line(&self) -> io::Result<()> {
+            self.push("clear".into())
+        }
+        fn flush(&self) -> io::Result<()> {
+            Ok(())
+        }
+        fn show_cursor(&self) -> io::Result<()> {
+            self.push("show cursor".into())
+        }
+    }
+
+    #[test]
+    fn interrupt_abandons_and_shows_cursor() {
+        let style = ProgressStyle::with_template("{pos}/{len}").unwrap();
+        let term = Recorder::default();
+        let pb = ProgressBar::with_draw_target(
+            Some(10),
+            ProgressDrawTarget::term_like(Box::new(term.clone())),
+        )
+        .with_style(style.clone());
+        pb.inc(3);
+
+        let multi_term = Recorder::default();
+        let mp = MultiProgress::with_draw_target(ProgressDrawTarget::term_like(Box::new(
+            multi_term.clone(),
+        )));
+        let child = mp.add(ProgressBar::new(10).with_style(style));
+        child.inc(5);
+
+        // Simulate the handler without installing it
+        drop(track(Target::Bar(pb.downgrade())));
+        drop(track(Target::Multi(Arc::downgrade(&mp.state))));
+        term.take();
+        multi_term.take();
+        handle_interrupt();
+
+        assert!(pb.is_finished() && child.is_finished());
+        assert_eq!(pb.position(), 3);
+        // The abandoned frames are the same as the last ones, so they aren't rewritten
+        assert_eq!(term.take(), ["show cursor"]);
+        assert_eq!(multi_term.take(), ["show cursor"]);
+    }
+}
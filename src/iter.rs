@@ -29,13 +29,22 @@ where
             .map(|len| self.progress_count(u64::try_from(len).unwrap()))
     }
 
-    /// Wrap an iterator with default styling.
-    fn progress(self) -> ProgressBarIter<Self>
-    where
-        Self: ExactSizeIterator,
-    {
-        let len = u64::try_from(self.len()).unwrap();
-        self.progress_count(len)
+    /// Wrap an iterator with default styling. Uses the upper bound of [`Iterator::size_hint()`]
+    /// as length, or shows a spinner if there is none.
+    ///
+    /// The length keeps following the upper bound while iterating, so the bar still fills up if
+    /// it turns out to be loose (e.g. for [`Iterator::filter()`]), and it grows if the iterator
+    /// yields more items than its size hint promised.
+    fn progress(self) -> ProgressBarIter<Self> {
+        let (lower, upper) = self.size_hint();
+        let bar = match upper {
+            Some(len) => ProgressBar::new(u64::try_from(len).unwrap()),
+            None => ProgressBar::new_spinner(),
+        };
+        let mut iter = self.progress_with(bar);
+        // An exact size hint doesn't need to be followed
+        iter.size_hint = upper.filter(|&upper| upper != lower);
+        iter
     }
 
     /// Wrap an iterator with an explicit element count.
@@ -43,6 +52,24 @@ where
         self.progress_with(ProgressBar::new(len))
     }
 
+    /// Wrap an iterator like [`progress()`](ProgressIterator::progress), and finish the progress
+    /// bar with `finish` instead of clearing it
+    ///
+    /// The progress bar is finished once the iterator is exhausted, or when it's dropped before,
+    /// e.g. when breaking out of a `for` loop (unless the progress bar was cloned):
+    ///
+    /// ```rust
+    /// # use indicatif::{ProgressFinish, ProgressIterator};
+    /// for i in (0..100).progress_with_finish(ProgressFinish::Abandon) {
+    ///     if i == 42 {
+    ///         break; // leaves the progress bar at 42/100
+    ///     }
+    /// }
+    /// ```
+    fn progress_with_finish(self, finish: ProgressFinish) -> ProgressBarIter<Self> {
+        self.progress().with_finish(finish)
+    }
+
     /// Wrap an iterator with a custom progress bar.
     fn progress_with(self, progress: ProgressBar) -> ProgressBarIter<Self>;
 
@@ -57,11 +84,66 @@ where
     }
 }
 
+/// Wraps a [`futures::Stream`](https://docs.rs/futures/0.3/futures/stream/trait.Stream.html)
+/// to display its progress.
+///
+/// The progress bar is advanced by one for each item the stream yields, and finished once the
+/// stream ends.
+///
+/// ```rust
+/// # futures::executor::block_on(async {
+/// use futures::stream::{self, StreamExt};
+/// use indicatif::ProgressStream;
+///
+/// let stream = stream::iter('a'..='z').progress();
+/// assert_eq!(stream.progress.length(), Some(26));
+/// assert_eq!(stream.count().await, 26);
+/// # }); // block_on
+/// ```
+#[cfg(feature = "futures")]
+#[cfg_attr(docsrs, doc(cfg(feature = "futures")))]
+pub trait ProgressStream
+where
+    Self: Sized + futures_core::Stream,
+{
+    /// Wrap a stream with default styling. Uses the upper bound of
+    /// [`Stream::size_hint()`](futures_core::Stream::size_hint) as length, if there is one.
+    fn progress(self) -> ProgressBarIter<Self> {
+        let bar = match self.size_hint().1 {
+            Some(len) => ProgressBar::new(u64::try_from(len).unwrap()),
+            None => ProgressBar::new_spinner(),
+        };
+        self.progress_with(bar)
+    }
+
+    /// Wrap a stream with an explicit element count.
+    fn progress_count(self, len: u64) -> ProgressBarIter<Self> {
+        self.progress_with(ProgressBar::new(len))
+    }
+
+    /// Wrap a stream with a custom progress bar.
+    fn progress_with(self, progress: ProgressBar) -> ProgressBarIter<Self>;
+}
+
+#[cfg(feature = "futures")]
+impl<S: futures_core::Stream> ProgressStream for S {
+    fn progress_with(self, progress: ProgressBar) -> ProgressBarIter<Self> {
+        ProgressBarIter {
+            it: self,
+            progress,
+            size_hint: None,
+        }
+    }
+}
+
 /// Wraps an iterator to display its progress.
 #[derive(Debug)]
 pub struct ProgressBarIter<T> {
     pub(crate) it: T,
     pub progress: ProgressBar,
+    /// The upper bound of the size hint of `it` the length was last updated for, if the length
+    /// follows the size hint, see [`ProgressIterator::progress()`]
+    pub(crate) size_hint: Option<usize>,
 }
 
 impl<T> ProgressBarIter<T> {
@@ -119,14 +201,40 @@ impl<S, T: Iterator<Item = S>> Iterator for ProgressBarIter<T> {
 
     fn next(&mut self) -> Option<Self::Item> {
         let item = self.it.next();
+        self.advance(item.is_some());
+        item
+    }
+}
 
-        if item.is_some() {
+impl<T: Iterator> ProgressBarIter<T> {
+    fn advance(&mut self, got_item: bool) {
+        if got_item {
             self.progress.inc(1);
-        } else if !self.progress.is_finished() {
-            self.progress.finish_using_style();
         }
 
-        item
+        if let Some(last) = self.size_hint {
+            let remaining = match got_item {
+                true => self.it.size_hint().1,
+                false => Some(0),
+            };
+            // Each item normally counts the size hint down by one, which keeps it in line with
+            // the length without having to update it (and take the progress bar's lock)
+            let expected = match got_item {
+                true => last.checked_sub(1),
+                false => Some(last),
+            };
+            if let Some(remaining) = remaining {
+                if Some(remaining) != expected {
+                    let len = self.progress.position().saturating_add(remaining as u64);
+                    self.progress.set_length(len);
+                }
+            }
+            self.size_hint = remaining;
+        }
+
+        if !got_item && !self.progress.is_finished() {
+            self.progress.finish_using_style();
+        }
     }
 }
 
@@ -139,13 +247,7 @@ impl<T: ExactSizeIterator> ExactSizeIterator for ProgressBarIter<T> {
 impl<T: DoubleEndedIterator> DoubleEndedIterator for ProgressBarIter<T> {
     fn next_back(&mut self) -> Option<Self::Item> {
         let item = self.it.next_back();
-
-        if item.is_some() {
-            self.progress.inc(1);
-        } else if !self.progress.is_finished() {
-            self.progress.finish_using_style();
-        }
-
+        self.advance(item.is_some());
         item
     }
 }
@@ -290,11 +392,17 @@ impl<S: futures_core::Stream + Unpin> futures_core::Stream for ProgressBarIter<S
         let item = std::pin::Pin::new(&mut this.it).poll_next(cx);
         match &item {
             std::task::Poll::Ready(Some(_)) => this.progress.inc(1),
-            std::task::Poll::Ready(None) => this.progress.finish_using_style(),
-            std::task::Poll::Pending => {}
+            std::task::Poll::Ready(None) if !this.progress.is_finished() => {
+                this.progress.finish_using_style()
+            }
+            std::task::Poll::Ready(None) | std::task::Poll::Pending => {}
         }
         item
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.it.size_hint()
+    }
 }
 
 impl<W: io::Write> io::Write for ProgressBarIter<W> {
@@ -323,15 +431,21 @@ impl<W: io::Write> io::Write for ProgressBarIter<W> {
 
 impl<S, T: Iterator<Item = S>> ProgressIterator for T {
     fn progress_with(self, progress: ProgressBar) -> ProgressBarIter<Self> {
-        ProgressBarIter { it: self, progress }
+        ProgressBarIter {
+            it: self,
+            progress,
+            size_hint: None,
+        }
     }
 }
 
 #[cfg(test)]
 mod test {
+    use std::sync::{Arc, Mutex};
+
     use crate::iter::{ProgressBarIter, ProgressIterator};
     use crate::progress_bar::ProgressBar;
-    use crate::ProgressStyle;
+    use crate::{ProgressFinish, ProgressStatus, ProgressStyle};
 
     #[test]
     fn it_can_wrap_an_iterator() {
@@ -353,4 +467,272 @@ mod test {
             v.iter().progress_with_style(style)
         });
     }
+
+    #[test]
+    fn progress_with_finish() {
+        // Records the status and position the progress bar is finished with
+        type Finished = Arc<Mutex<Vec<(ProgressStatus, u64)>>>;
+        fn track<T>(mut it: ProgressBarIter<T>) -> (ProgressBarIter<T>, Finished) {
+            let finished = Finished::default();
+            let record = finished.clone();
+            it.progress = it.progress.with_finish_callback(move |state| {
+                record.lock().unwrap().push((state.status(), state.pos()));
+            });
+            (it, finished)
+        }
+
+        let (it, finished) = track((0..10).progress_with_finish(ProgressFinish::AndLeave));
+        assert_eq!(it.count(), 10);
+        assert_eq!(*finished.lock().unwrap(), [(ProgressStatus::Finished, 10)]);
+
+        let (it, finished) = track((0..10).progress_with_finish(ProgressFinish::Abandon));
+        for i in it {
+            if i == 2 {
+                break;
+            }
+        }
+        assert_eq!(*finished.lock().unwrap(), [(ProgressStatus::Abandoned, 3)]);
+
+        // The default is to clear the progress bar
+        let (it, finished) = track((0..10).progress());
+        for _ in it.take(5) {}
+        assert_eq!(finished.lock().unwrap()[0].0, ProgressStatus::Cleared);
+    }
+
+    #[test]
+    fn progress_uses_size_hint() {
+        let it = vec![1, 2, 3, 4, 5].into_iter().progress();
+        let pb = it.progress.clone();
+        assert_eq!(pb.length(), Some(5));
+        assert_eq!(it.sum::<i32>(), 15);
+        assert_eq!((pb.position(), pb.length()), (5, Some(5)));
+        assert!(pb.is_finished());
+
+        // The upper bound of a filter is loose, so the length shrinks to the actual count
+        let it = (0..10).filter(|x| x % 3 == 0).progress();
+        let pb = it.progress.clone();
+        assert_eq!(pb.length(), Some(10));
+        assert_eq!(it.collect::<Vec<_>>(), [0, 3, 6, 9]);
+        assert_eq!((pb.position(), pb.length()), (4, Some(4)));
+        assert_eq!(pb.state().state.fraction(), 1.0);
+
+        // Without an upper bound, there's no length
+        let it = (0..).take_while(|&x| x < 3).progress();
+        let pb = it.progress.clone();
+        assert_eq!(pb.length(), None);
+        assert_eq!(it.count(), 3);
+        assert_eq!((pb.position(), pb.length()), (3, None));
+    }
+
+    #[test]
+    fn progress_only_follows_inexact_size_hint() {
+        let it = (0..10).progress();
+        assert_eq!(it.size_hint, None);
+
+        // The upper bound of `take_while` counts down with each item, so the length is only
+        // updated once it ends early
+        let mut it = (0..10).take_while(|&x| x < 4).progress();
+        let pb = it.progress.clone();
+        assert_eq!(it.size_hint, Some(10));
+        it.by_ref().take(3).for_each(drop);
+        assert_eq!((it.size_hint, pb.length()), (Some(7), Some(10)));
+        assert_eq!(it.count(), 1);
+        assert_eq!((pb.position(), pb.length()), (4, Some(4)));
+    }
+
+    #[test]
+    fn progress_grows_past_wrong_size_hint() {
+        /// Yields three items while claiming there's at most one left
+        struct Understated(u8);
+
+        impl Iterator for Understated {
+            type Item = u8;
+
+            fn next(&mut self) -> Option<u8> {
+                self.0 = self.0.checked_sub(1)?;
+                Some(self.0)
+            }
+
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                (0, Some(1))
+            }
+        }
+
+        let it = Understated(3).progress();
+        let pb = it.progress.clone();
+        assert_eq!(pb.length(), Some(1));
+        assert_eq!(it.count(), 3);
+        assert_eq!((pb.position(), pb.length()), (3, Some(3)));
+    }
+
+    #[test]
+    fn it_can_wrap_a_writer() {
+        use std::io::Write;
+
+        // Accepts at most 3 bytes per write
+        struct ShortWriter(Vec<u8>);
+
+        impl Write for ShortWriter {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                let len = buf.len().min(3);
+                self.0.extend_from_slice(&buf[..len]);
+                Ok(len)
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let finished = Arc::new(Mutex::new(None));
+        let record = finished.clone();
+        let pb = ProgressBar::hidden()
+            .with_finish(ProgressFinish::AndLeave)
+            .with_finish_callback(move |state| {
+                *record.lock().unwrap() = Some((state.status(), state.pos()));
+            });
+
+        let mut writer = pb.wrap_write(ShortWriter(Vec::new()));
+        assert_eq!(writer.write(b"hello").unwrap(), 3);
+        assert_eq!(pb.position(), 3);
+        writer.write_all(b"0123456789").unwrap();
+        assert_eq!(pb.position(), 13);
+        assert_eq!(writer.it.0, b"hel0123456789");
+
+        drop(pb);
+        assert_eq!(*finished.lock().unwrap(), None);
+        drop(writer);
+        assert_eq!(
+            *finished.lock().unwrap(),
+            Some((ProgressStatus::Finished, 13))
+        );
+    }
+
+    #[test]
+    fn wrap_read_seek_sets_length() {
+        use std::io::{self, Cursor, Read, Seek, SeekFrom};
+
+        let pb = ProgressBar::hidden();
+        let copied = io::copy(
+            &mut pb.wrap_read_seek(Cursor::new([7; 1000])).unwrap(),
+            &mut io::sink(),
+        )
+        .unwrap();
+        assert_eq!(copied, 1000);
+        assert_eq!(pb.length(), Some(1000));
+        assert_eq!(pb.position(), 1000);
+
+        // Only the bytes after the current position are counted, and that position is kept
+        let mut cursor = Cursor::new([7; 1000]);
+        cursor.set_position(400);
+        let pb = ProgressBar::hidden();
+        let mut reader = pb.wrap_read_seek(cursor).unwrap();
+        assert_eq!(pb.length(), Some(600));
+        assert_eq!(reader.it.position(), 400);
+        assert_eq!(reader.read(&mut [0; 100]).unwrap(), 100);
+        assert_eq!(pb.position(), 100);
+
+        /// A reader at position 10 that fails the seeks in `fails`
+        struct Flaky {
+            pos: u64,
+            fails: fn(SeekFrom) -> bool,
+        }
+
+        impl Read for Flaky {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Ok(0)
+            }
+        }
+
+        impl Seek for Flaky {
+            fn seek(&mut self, from: SeekFrom) -> io::Result<u64> {
+                if (self.fails)(from) {
+                    // A failed seek to the end may still have moved the reader
+                    if let SeekFrom::End(_) = from {
+                        self.pos = 0;
+                    }
+                    return Err(io::ErrorKind::Unsupported.into());
+                }
+                self.pos = match from {
+                    SeekFrom::Start(pos) => pos,
+                    SeekFrom::End(_) => 100,
+                    SeekFrom::Current(_) => self.pos,
+                };
+                Ok(self.pos)
+            }
+        }
+
+        let flaky = |fails| Flaky { pos: 10, fails };
+
+        // Readers that can't seek leave the length unknown
+        let pb = ProgressBar::hidden();
+        let reader = pb.wrap_read_seek(flaky(|_| true)).unwrap();
+        assert_eq!(pb.length(), None);
+        assert_eq!(reader.it.pos, 10);
+
+        // The position is restored even if seeking to the end fails
+        let pb = ProgressBar::hidden();
+        let reader = pb
+            .wrap_read_seek(flaky(|from| matches!(from, SeekFrom::End(_))))
+            .unwrap();
+        assert_eq!(pb.length(), None);
+        assert_eq!(reader.it.pos, 10);
+
+        // A reader that can't be seeked back isn't returned
+        let pb = ProgressBar::hidden();
+        assert!(pb
+            .wrap_read_seek(flaky(|from| matches!(from, SeekFrom::Start(_))))
+            .is_err());
+    }
+
+    #[cfg(feature = "futures")]
+    #[test]
+    fn it_can_wrap_a_stream() {
+        use futures::stream::{self, StreamExt};
+
+        use crate::iter::ProgressStream;
+
+        let stream = stream::iter(0..5).progress();
+        let pb = stream.progress.clone();
+        assert_eq!(pb.length(), Some(5));
+        let items = futures::executor::block_on(stream.collect::<Vec<_>>());
+        assert_eq!(items, [0, 1, 2, 3, 4]);
+        assert_eq!(pb.position(), 5);
+        assert!(pb.is_finished());
+
+        // Without an upper bound, the progress bar has no length
+        let stream = stream::repeat(1).progress();
+        assert_eq!(stream.progress.length(), None);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn it_can_wrap_async_io() {
+        let data = vec![7u8; 100_000];
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        let read = ProgressBar::hidden();
+        let copied = runtime
+            .block_on(tokio::io::copy(
+                &mut read.wrap_async_read(&data[..]),
+                &mut tokio::io::sink(),
+            ))
+            .unwrap();
+        assert_eq!(copied, data.len() as u64);
+        assert_eq!(read.position(), copied);
+
+        let write = ProgressBar::hidden();
+        let mut target = Vec::new();
+        let copied = runtime
+            .block_on(tokio::io::copy(
+                &mut &data[..],
+                &mut write.wrap_async_write(&mut target),
+            ))
+            .unwrap();
+        assert_eq!(copied, data.len() as u64);
+        assert_eq!(write.position(), copied);
+        assert_eq!(target, data);
+    }
 }
@@ -171,27 +171,34 @@
 //!     .progress_chars("##-"));
 //! ```
 //!
+//! A template can span multiple lines by including `\n`, e.g. to put a title above the bar.
+//! All lines are cleared and redrawn together.
+//!
 //! The following keys exist:
 //!
 //! * `bar`: renders a progress bar. By default 20 characters wide.  The
 //!   style string is used to color the elapsed part, the alternative
 //!   style is used for the bar that is yet to render.
-//! * `wide_bar`: like `bar` but always fills the remaining space. It should not be used with
-//! `wide_msg`.
+//! * `wide_bar`: like `bar` but always fills the remaining space. It can't be used with
+//!   `wide_msg` on the same line.
 //! * `spinner`: renders the spinner (current tick string). Note that spinners do not automatically tick by default. You either
-//! need to call `enable_steady_tick` or manually call `tick`.
+//!   need to call `enable_steady_tick` or manually call `tick`.
 //! * `prefix`: renders the prefix set on the progress bar.
 //! * `msg`: renders the currently set message on the progress bar.
-//! * `wide_msg`: like `msg` but always fills the remaining space and truncates. It should not be used
-//! with `wide_bar`.
+//! * `wide_msg`: like `msg` but always fills the remaining space and truncates with an ellipsis
+//!   (or without one when using [`WrapMode::Truncate`](style::WrapMode::Truncate)). It can't be
+//!   used with `wide_bar` on the same line.
 //! * `pos`: renders the current position of the bar as integer
 //! * `human_pos`: renders the current position of the bar as an integer, with commas as the
-//! thousands separator.
+//!   thousands separator (see [`ProgressStyle::thousands_separator()`]).
 //! * `len`: renders the amount of work to be done as an integer
 //! * `human_len`: renders the total length of the bar as an integer, with commas as the thousands
-//! separator.
+//!   separator.
+//! * `pos_si` and `len_si`: like `pos` and `len`, but abbreviated with SI prefixes and one
+//!   decimal above a thousand, e.g. `1.5k` or `2.3M` (see [`SiCount`]).
 //! * `percent`: renders the current position of the bar as a percentage of the total length (as an integer).
-//! * `percent_precise`: renders the current position of the bar as a percentage of the total length (with 3 fraction digits).
+//! * `percent_precise`: renders the current position of the bar as a percentage of the total length (with 3 fraction digits),
+//!   e.g. `0.370` for a bar at 37/10000. Useful for long jobs where `percent` stays at `0` for a while.
 //! * `bytes`: renders the current position of the bar as bytes (alias of `binary_bytes`).
 //! * `total_bytes`: renders the total length of the bar as bytes (alias of `binary_total_bytes`).
 //! * `decimal_bytes`: renders the current position of the bar as bytes using
@@ -205,6 +212,7 @@
 //! * `elapsed_precise`: renders the elapsed time as `HH:MM:SS`.
 //! * `elapsed`: renders the elapsed time as `42s`, `1m` etc.
 //! * `per_sec`: renders the speed in steps per second.
+//! * `per_sec_si`: renders the speed in steps per second like `pos_si`, e.g. `1.2k/s`.
 //! * `bytes_per_sec`: renders the speed in bytes per second (alias of `binary_bytes_per_sec`).
 //! * `decimal_bytes_per_sec`: renders the speed in bytes per second using
 //!   power-of-10 units, i.e. `MB`, `kB`, etc.
@@ -212,8 +220,21 @@
 //!   power-of-two units, i.e. `MiB`, `KiB`, etc.
 //! * `eta_precise`: the remaining time (like `elapsed_precise`).
 //! * `eta`: the remaining time (like `elapsed`).
+//! * `eta_smooth`: the remaining time (like `eta_precise`), rounded to the second under a minute,
+//!   to 10 seconds under 10 minutes and to the minute above that. Small increases caused by
+//!   estimation noise are not displayed.
 //! * `duration_precise`: the extrapolated total duration (like `elapsed_precise`).
 //! * `duration`: the extrapolated total duration time (like `elapsed`).
+//! * `elapsed_of_total`: renders the elapsed time and the extrapolated total duration (like
+//!   `elapsed_precise`), e.g. `00:00:42 / 00:02:10`. The total is shown as `?` while it can't be
+//!   estimated, e.g. without a length.
+//! * `stall`: renders how long the position hasn't changed (like `elapsed`), e.g. `stalled 12s`,
+//!   once that exceeds the style's [stall threshold](ProgressStyle::stall_threshold), and
+//!   nothing otherwise.
+//! * `sparkline`: renders the rates of the last 16 updates as a sparkline of block characters,
+//!   e.g. `▁▃▅▇█▇▅`, see [`ProgressState::rate_history`].
+//! * `metric:NAME`: renders the value of the metric `NAME` set with
+//!   [`ProgressBar::set_metric`], or nothing if it's not set.
 //!
 //! If the list above does not contain the value you need, consider creating a custom
 //! [`ProgressTracker`][crate::style::ProgressTracker] implementation.
@@ -241,35 +262,53 @@
 //!
 //! * `rayon`: adds rayon support
 //! * `improved_unicode`: adds improved unicode support (graphemes, better width calculation)
+//! * `serde`: implements `Serialize` and `Deserialize` for [`ProgressSnapshot`]
+//! * `ctrlc`: adds `ProgressBar::install_ctrlc_handler()` to clean up progress bars on Ctrl-C
+//! * `tracing`: adds `ProgressDrawTarget::tracing()` to report progress as `tracing` events
+//! * `ratatui`: adds `ProgressBar::render_text()` to show progress bars in a ratatui application
 
 #![cfg_attr(docsrs, feature(doc_cfg))]
 #![warn(unreachable_pub)]
 
+mod clock;
 mod draw_target;
 mod format;
 #[cfg(feature = "in_memory")]
 mod in_memory;
+#[cfg(feature = "ctrlc")]
+mod interrupt;
 mod iter;
 mod multi;
 mod progress_bar;
+#[cfg(feature = "ratatui")]
+mod ratatui;
 #[cfg(feature = "rayon")]
 mod rayon;
+mod spinner;
 mod state;
 pub mod style;
 mod term_like;
 
+pub use crate::clock::Clock;
 pub use crate::draw_target::ProgressDrawTarget;
 pub use crate::format::{
-    BinaryBytes, DecimalBytes, FormattedDuration, HumanBytes, HumanCount, HumanDuration,
-    HumanFloatCount,
+    format_duration, BinaryBytes, DecimalBytes, FormattedDuration, HumanBytes, HumanCount,
+    HumanDuration, HumanFloatCount, Precision, SiCount,
 };
 #[cfg(feature = "in_memory")]
 pub use crate::in_memory::InMemoryTerm;
+#[cfg(feature = "ctrlc")]
+pub use crate::interrupt::{handle_interrupt, interrupted, set_exit_on_interrupt};
+#[cfg(feature = "futures")]
+pub use crate::iter::ProgressStream;
 pub use crate::iter::{ProgressBarIter, ProgressIterator};
-pub use crate::multi::{MultiProgress, MultiProgressAlignment};
-pub use crate::progress_bar::{ProgressBar, WeakProgressBar};
+pub use crate::multi::{MultiProgress, MultiProgressAlignment, MultiProgressOverflow};
+pub use crate::progress_bar::{FinishGuard, ProgressBar, WeakProgressBar};
 #[cfg(feature = "rayon")]
 pub use crate::rayon::ParallelProgressIterator;
-pub use crate::state::{ProgressFinish, ProgressState};
-pub use crate::style::ProgressStyle;
+pub use crate::spinner::Spinner;
+pub use crate::state::{
+    EstimatorKind, ProgressFinish, ProgressSnapshot, ProgressState, ProgressStatus,
+};
+pub use crate::style::{ProgressStyle, ProgressStyleBuilder, TemplateError};
 pub use crate::term_like::TermLike;
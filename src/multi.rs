@@ -2,13 +2,16 @@ use std::fmt::{Debug, Formatter};
 use std::io;
 use std::sync::{Arc, RwLock};
 use std::thread::panicking;
+use std::time::Duration;
 #[cfg(not(target_arch = "wasm32"))]
 use std::time::Instant;
 
 use crate::draw_target::{
     visual_line_count, DrawState, DrawStateWrapper, LineAdjust, ProgressDrawTarget, VisualLines,
 };
-use crate::progress_bar::ProgressBar;
+use crate::progress_bar::{ProgressBar, WeakProgressBar};
+use crate::state::ProgressSnapshot;
+use crate::style::ProgressStyle;
 #[cfg(target_arch = "wasm32")]
 use instant::Instant;
 
@@ -53,6 +56,30 @@ impl MultiProgress {
         state.draw_target = target;
     }
 
+    /// A convenience builder-like function for a multi progress that shows at most `max` progress
+    /// bars, see [`MultiProgress::set_max_visible()`]
+    pub fn with_max_visible(self, max: usize) -> Self {
+        self.set_max_visible(max);
+        self
+    }
+
+    /// Limits how many progress bars are shown at once
+    ///
+    /// With more progress bars than that, only `max` of them are drawn, followed by a line like
+    /// `(+45 more)` counting the others. Which progress bars are drawn is set with
+    /// [`MultiProgress::set_overflow()`]. Lines printed with
+    /// [`println`](MultiProgress::println) are always shown. Use `0` to show all progress bars,
+    /// which is the default.
+    pub fn set_max_visible(&self, max: usize) {
+        self.state.write().unwrap().max_visible = max;
+    }
+
+    /// Sets which progress bars are shown when there are more than the
+    /// [maximum](MultiProgress::set_max_visible)
+    pub fn set_overflow(&self, overflow: MultiProgressOverflow) {
+        self.state.write().unwrap().overflow = overflow;
+    }
+
     /// Set whether we should try to move the cursor when possible instead of clearing lines.
     ///
     /// This can reduce flickering, but do not enable it if you intend to change the number of
@@ -70,6 +97,39 @@ impl MultiProgress {
         self.state.write().unwrap().alignment = alignment;
     }
 
+    /// Set whether the `prefix` of all progress bars is padded to the same width
+    ///
+    /// When enabled, each progress bar pads its prefix to the width of the longest prefix among
+    /// the progress bars in the [`MultiProgress`], so that whatever follows the prefix in the
+    /// template starts at the same column. Each progress bar picks up a changed width (e.g. after
+    /// adding or removing a progress bar) the next time it's drawn.
+    pub fn set_align_prefixes(&self, align: bool) {
+        self.state.write().unwrap().align_prefixes = align;
+    }
+
+    /// Limits how often the whole group is repainted to `refresh_rate` times a second
+    ///
+    /// Updates to the progress bars are still recorded right away, but are only shown with the
+    /// next repaint of the group, so many rapidly updating progress bars don't each trigger a
+    /// repaint. Finished progress bars and [`println`](MultiProgress::println) calls are always
+    /// shown immediately. This applies on top of the refresh rate of the draw target. Use `0` to
+    /// disable the limit.
+    pub fn set_max_refresh_rate(&self, refresh_rate: u8) {
+        self.state.write().unwrap().refresh_interval = match refresh_rate {
+            0 => Duration::ZERO,
+            hz => Duration::from_secs(1) / u32::from(hz),
+        };
+    }
+
+    /// Sets the style of progress bars added afterwards that don't have a style set
+    ///
+    /// Progress bars keep a style set with [`ProgressBar::set_style()`] or
+    /// [`ProgressBar::with_style()`], including the spinner style of
+    /// [`ProgressBar::new_spinner()`]. Progress bars that were already added keep their style.
+    pub fn set_default_style(&self, style: ProgressStyle) {
+        self.state.write().unwrap().default_style = Some(style);
+    }
+
     /// Adds a progress bar.
     ///
     /// The progress bar added will have the draw target changed to a
@@ -161,17 +221,59 @@ impl MultiProgress {
         self.state.write().unwrap().remove_idx(idx);
     }
 
+    /// Moves a progress bar to position `index`.
+    ///
+    /// If `index >= MultiProgressState::objects.len()`, the progress bar is moved to the end of
+    /// the list. Finished progress bars can be moved as well. The new order is used from the next
+    /// time the [`MultiProgress`] is drawn.
+    ///
+    /// If the passed progress bar is not a member of the [`MultiProgress`], the `move_to` method
+    /// does nothing.
+    pub fn move_to(&self, pb: &ProgressBar, index: usize) {
+        let state = pb.state();
+        let idx = match &state.draw_target.remote() {
+            Some((state, idx)) => {
+                // Check that this progress bar is owned by the current MultiProgress.
+                assert!(Arc::ptr_eq(&self.state, state));
+                *idx
+            }
+            _ => return,
+        };
+
+        self.state.write().unwrap().move_to(idx, index);
+    }
+
     fn internalize(&self, location: InsertLocation, pb: ProgressBar) -> ProgressBar {
         let mut state = self.state.write().unwrap();
         let idx = state.insert(location);
+        state.members[idx].bar = pb.downgrade();
+        let default_style = state.default_style.clone();
         drop(state);
 
+        if let Some(style) = default_style {
+            pb.state().set_default_style(style);
+        }
         pb.set_draw_target(ProgressDrawTarget::new_remote(self.state.clone(), idx));
         pb
     }
 
+    /// Abandons the progress bars and shows the cursor when the process is interrupted with Ctrl-C
+    ///
+    /// This covers all progress bars in the [`MultiProgress`] at the time of the interrupt. See
+    /// [`ProgressBar::install_ctrlc_handler()`] for details.
+    #[cfg(feature = "ctrlc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "ctrlc")))]
+    pub fn install_ctrlc_handler(&self) -> Result<(), ctrlc::Error> {
+        crate::interrupt::register(crate::interrupt::Target::Multi(Arc::downgrade(&self.state)))
+    }
+
     /// Print a log line above all progress bars in the [`MultiProgress`]
     ///
+    /// The bars are cleared, `msg` is printed and the bars are redrawn below it while holding the
+    /// lock of the [`MultiProgress`], so lines printed from different threads never interleave
+    /// with each other or with updates of the progress bars. Each line of a multi-line `msg` is
+    /// printed on its own line.
+    ///
     /// If the draw target is hidden (e.g. when standard output is not a terminal), `println()`
     /// will not do anything.
     pub fn println<I: AsRef<str>>(&self, msg: I) -> io::Result<()> {
@@ -197,12 +299,47 @@ impl MultiProgress {
         self.state.write().unwrap().clear(Instant::now())
     }
 
+    /// Clears all progress bars and prints summary lines in their place
+    ///
+    /// `summary` is passed a snapshot of each progress bar in the [`MultiProgress`], in their
+    /// visual order, and returns the lines to print. Progress bars that have already been dropped
+    /// are not included. Clearing and printing the summary happens in a single draw, so no other
+    /// progress bar output can end up in between.
+    ///
+    /// Like [`MultiProgress::clear`], this does not remove the progress bars; they are drawn
+    /// again below the summary when they are updated.
+    pub fn clear_with_summary<F>(&self, summary: F) -> io::Result<()>
+    where
+        F: FnOnce(&[ProgressSnapshot]) -> Vec<String>,
+    {
+        // Progress bars are locked before the `MultiState`, so take the snapshots first
+        let bars = self.state.read().unwrap().bars();
+        let snapshots = bars
+            .iter()
+            .filter_map(WeakProgressBar::upgrade)
+            .map(|bar| bar.state().state.snapshot())
+            .collect::<Vec<_>>();
+
+        let lines = summary(&snapshots);
+        self.state
+            .write()
+            .unwrap()
+            .clear_with_lines(lines, Instant::now())
+    }
+
+    /// Flushes the underlying terminal or writer
+    ///
+    /// Output written to a buffered writer may otherwise only become visible once the buffer
+    /// fills up.
+    pub fn flush(&self) -> io::Result<()> {
+        self.state.read().unwrap().flush()
+    }
+
     pub fn is_hidden(&self) -> bool {
         self.state.read().unwrap().is_hidden()
     }
 }
 
-#[derive(Debug)]
 pub(crate) struct MultiState {
     /// The collection of states corresponding to progress bars
     members: Vec<MultiStateMember>,
@@ -220,6 +357,20 @@ pub(crate) struct MultiState {
     orphan_lines: Vec<String>,
     /// The count of currently visible zombie lines.
     zombie_lines_count: VisualLines,
+    /// Whether the prefixes of all progress bars are padded to the same width
+    align_prefixes: bool,
+    /// Minimum time between two repaints of the group that aren't forced
+    refresh_interval: Duration,
+    /// When the group was last repainted
+    last_paint: Option<Instant>,
+    /// The style of added progress bars that don't have one set
+    default_style: Option<ProgressStyle>,
+    /// How many progress bars are shown at most, or 0 for all of them
+    max_visible: usize,
+    /// Which progress bars are shown when there are more than `max_visible`
+    overflow: MultiProgressOverflow,
+    /// Incremented whenever a progress bar is updated, to tell which ones were updated last
+    updates: u64,
 }
 
 impl MultiState {
@@ -232,27 +383,36 @@ impl MultiState {
             alignment: MultiProgressAlignment::default(),
             orphan_lines: Vec::new(),
             zombie_lines_count: VisualLines::default(),
+            align_prefixes: false,
+            refresh_interval: Duration::ZERO,
+            last_paint: None,
+            default_style: None,
+            max_visible: 0,
+            overflow: MultiProgressOverflow::default(),
+            updates: 0,
         }
     }
 
     pub(crate) fn mark_zombie(&mut self, index: usize) {
         let width = self.width().map(usize::from);
 
-        let member = &mut self.members[index];
-
         // If the zombie is the first visual bar then we can reap it right now instead of
         // deferring it to the next draw.
         if index != self.ordering.first().copied().unwrap() {
-            member.is_zombie = true;
+            self.members[index].is_zombie = true;
             return;
         }
 
-        let line_count = member
-            .draw_state
-            .as_ref()
-            .zip(width)
-            .map(|(d, width)| d.visual_line_count(.., width))
-            .unwrap_or_default();
+        // A progress bar left out because of `max_visible` has no lines on the screen
+        let line_count = match self.shown().0.contains(&index) {
+            true => self.members[index]
+                .draw_state
+                .as_ref()
+                .zip(width)
+                .map(|(d, width)| d.visual_line_count(.., width))
+                .unwrap_or_default(),
+            false => VisualLines::default(),
+        };
 
         // Track the total number of zombie lines on the screen
         self.zombie_lines_count = self.zombie_lines_count.saturating_add(line_count);
@@ -279,6 +439,16 @@ impl MultiState {
             None => return Ok(()),
         };
 
+        // Coalesce updates of the progress bars into one repaint per refresh interval. Their
+        // latest draw states are already stored, so nothing is lost by skipping this one.
+        if !force_draw && extra_lines.is_none() && self.orphan_lines.is_empty() {
+            if let Some(last_paint) = self.last_paint {
+                if now.saturating_duration_since(last_paint) < self.refresh_interval {
+                    return Ok(());
+                }
+            }
+        }
+
         // Assumption: if extra_lines is not None, then it has at least one line
         debug_assert_eq!(
             extra_lines.is_some(),
@@ -286,6 +456,7 @@ impl MultiState {
         );
 
         let mut reap_indices = vec![];
+        let (shown, hidden) = self.shown();
 
         // Reap all consecutive 'zombie' progress bars from head of the list.
         let mut adjust = VisualLines::default();
@@ -295,11 +466,14 @@ impl MultiState {
                 break;
             }
 
-            let line_count = member
-                .draw_state
-                .as_ref()
-                .map(|d| d.visual_line_count(.., width))
-                .unwrap_or_default();
+            let line_count = match shown.contains(&index) {
+                true => member
+                    .draw_state
+                    .as_ref()
+                    .map(|d| d.visual_line_count(.., width))
+                    .unwrap_or_default(),
+                false => VisualLines::default(),
+            };
             // Track the total number of zombie lines on the screen.
             self.zombie_lines_count += line_count;
 
@@ -325,6 +499,7 @@ impl MultiState {
             None => return Ok(()),
         };
 
+        self.last_paint = Some(now);
         let mut draw_state = drawable.state();
         draw_state.orphan_lines_count = self.orphan_lines.len();
         draw_state.alignment = self.alignment;
@@ -337,12 +512,15 @@ impl MultiState {
         // Add lines from `ProgressBar::println` call.
         draw_state.lines.append(&mut self.orphan_lines);
 
-        for index in &self.ordering {
+        for index in &shown {
             let member = &self.members[*index];
             if let Some(state) = &member.draw_state {
                 draw_state.lines.extend_from_slice(&state.lines[..]);
             }
         }
+        if hidden > 0 {
+            draw_state.lines.push(format!("(+{hidden} more)"));
+        }
 
         drop(draw_state);
         let drawable = drawable.draw();
@@ -361,6 +539,16 @@ impl MultiState {
         drawable
     }
 
+    /// Whether a repaint at `now` that isn't forced would get past the refresh interval of the
+    /// group and the refresh rate of its draw target
+    pub(crate) fn would_draw(&self, now: Instant) -> bool {
+        let refreshed = match self.last_paint {
+            Some(last_paint) => now.saturating_duration_since(last_paint) >= self.refresh_interval,
+            None => true,
+        };
+        refreshed && self.draw_target.would_draw(now)
+    }
+
     pub(crate) fn println<I: AsRef<str>>(&mut self, msg: I, now: Instant) -> io::Result<()> {
         let msg = msg.as_ref();
 
@@ -374,7 +562,9 @@ impl MultiState {
     }
 
     pub(crate) fn draw_state(&mut self, idx: usize) -> DrawStateWrapper<'_> {
+        self.updates += 1;
         let member = self.members.get_mut(idx).unwrap();
+        member.last_update = self.updates;
         // alignment is handled by the `MultiProgress`'s underlying draw target, so there is no
         // point in propagating it here.
         let state = member.draw_state.get_or_insert(DrawState::default());
@@ -386,6 +576,46 @@ impl MultiState {
         self.draw_target.is_hidden()
     }
 
+    /// The progress bars that are drawn in their visual order, and how many are left out because
+    /// of `max_visible`
+    fn shown(&self) -> (Vec<usize>, usize) {
+        let mut shown = self
+            .ordering
+            .iter()
+            .copied()
+            .filter(|&idx| self.members[idx].draw_state.is_some())
+            .collect::<Vec<_>>();
+        if self.max_visible == 0 || shown.len() <= self.max_visible {
+            return (shown, 0);
+        }
+
+        let hidden = shown.len() - self.max_visible;
+        match self.overflow {
+            MultiProgressOverflow::First => shown.truncate(self.max_visible),
+            MultiProgressOverflow::MostRecent => {
+                let mut updates = shown
+                    .iter()
+                    .map(|&idx| self.members[idx].last_update)
+                    .collect::<Vec<_>>();
+                updates.sort_unstable_by(|a, b| b.cmp(a));
+                // Updates are numbered uniquely, so exactly `max_visible` bars are kept
+                let oldest = updates[self.max_visible - 1];
+                shown.retain(|&idx| self.members[idx].last_update >= oldest);
+            }
+        }
+        (shown, hidden)
+    }
+
+    /// Records the width of the prefix of the progress bar at `idx`, and returns the width its
+    /// prefix should be padded to, if prefixes are aligned
+    pub(crate) fn align_prefix(&mut self, idx: usize, width: usize) -> Option<usize> {
+        self.members[idx].prefix_width = width;
+        match self.align_prefixes {
+            true => self.members.iter().map(|m| m.prefix_width).max(),
+            false => None,
+        }
+    }
+
     pub(crate) fn suspend<F: FnOnce() -> R, R>(&mut self, f: F, now: Instant) -> R {
         self.clear(now).unwrap();
         let ret = f();
@@ -397,6 +627,19 @@ impl MultiState {
         self.draw_target.width()
     }
 
+    pub(crate) fn ascii_only(&self) -> bool {
+        self.draw_target.ascii_only()
+    }
+
+    pub(crate) fn flush(&self) -> io::Result<()> {
+        self.draw_target.flush()
+    }
+
+    #[cfg(feature = "ctrlc")]
+    pub(crate) fn show_cursor(&self) -> io::Result<()> {
+        self.draw_target.show_cursor()
+    }
+
     fn insert(&mut self, location: InsertLocation) -> usize {
         let idx = if let Some(idx) = self.free_set.pop() {
             self.members[idx] = MultiStateMember::default();
@@ -435,6 +678,38 @@ impl MultiState {
         idx
     }
 
+    fn move_to(&mut self, idx: usize, pos: usize) {
+        self.ordering.retain(|&x| x != idx);
+        let pos = Ord::min(pos, self.ordering.len());
+        self.ordering.insert(pos, idx);
+    }
+
+    /// The progress bars in their visual order
+    pub(crate) fn bars(&self) -> Vec<WeakProgressBar> {
+        self.ordering
+            .iter()
+            .map(|&idx| self.members[idx].bar.clone())
+            .collect()
+    }
+
+    /// Replaces all progress bars (and zombie lines) on the screen with `lines`
+    fn clear_with_lines(&mut self, lines: Vec<String>, now: Instant) -> io::Result<()> {
+        let mut drawable = match self.draw_target.drawable(true, now) {
+            Some(drawable) => drawable,
+            None => return Ok(()),
+        };
+
+        drawable.adjust_last_line_count(LineAdjust::Clear(self.zombie_lines_count));
+        self.zombie_lines_count = VisualLines::default();
+
+        let mut draw_state = drawable.state();
+        // Like lines printed with `println()`, the summary stays on the screen
+        draw_state.orphan_lines_count = lines.len();
+        draw_state.lines = lines;
+        drop(draw_state);
+        drawable.draw()
+    }
+
     fn clear(&mut self, now: Instant) -> io::Result<()> {
         match self.draw_target.drawable(true, now) {
             Some(mut drawable) => {
@@ -475,6 +750,31 @@ struct MultiStateMember {
     draw_state: Option<DrawState>,
     /// Whether the corresponding progress bar (more precisely, `BarState`) has been dropped.
     is_zombie: bool,
+    /// The width of the prefix of the corresponding progress bar when it was last drawn
+    prefix_width: usize,
+    /// When the corresponding progress bar was last updated, see `MultiState::updates`
+    last_update: u64,
+    /// The corresponding progress bar
+    bar: WeakProgressBar,
+}
+
+impl Debug for MultiState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MultiState")
+            .field("members", &self.members)
+            .field("free_set", &self.free_set)
+            .field("ordering", &self.ordering)
+            .field("draw_target", &self.draw_target)
+            .field("alignment", &self.alignment)
+            .field("orphan_lines", &self.orphan_lines)
+            .field("zombie_lines_count", &self.zombie_lines_count)
+            .field("align_prefixes", &self.align_prefixes)
+            .field("refresh_interval", &self.refresh_interval)
+            .field("max_visible", &self.max_visible)
+            .field("overflow", &self.overflow)
+            .field("last_paint", &self.last_paint)
+            .finish_non_exhaustive()
+    }
 }
 
 impl Debug for MultiStateMember {
@@ -482,6 +782,7 @@ impl Debug for MultiStateMember {
         f.debug_struct("MultiStateElement")
             .field("draw_state", &self.draw_state)
             .field("is_zombie", &self.is_zombie)
+            .field("prefix_width", &self.prefix_width)
             .finish_non_exhaustive()
     }
 }
@@ -502,16 +803,22 @@ impl Debug for MultiStateMember {
 /// [0/100] progress bar 2   =>   [0/100] progress bar 1
 /// [0/100] progress bar 3        [0/100] progress bar 3
 /// ```
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Default)]
 pub enum MultiProgressAlignment {
+    #[default]
     Top,
     Bottom,
 }
 
-impl Default for MultiProgressAlignment {
-    fn default() -> Self {
-        Self::Top
-    }
+/// Which progress bars a [`MultiProgress`] shows when there are more than the
+/// [maximum](MultiProgress::set_max_visible)
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum MultiProgressOverflow {
+    /// The first progress bars in their visual order (default)
+    #[default]
+    First,
+    /// The progress bars that were updated most recently, in their visual order
+    MostRecent,
 }
 
 enum InsertLocation {
@@ -524,7 +831,12 @@ enum InsertLocation {
 
 #[cfg(test)]
 mod tests {
-    use crate::{MultiProgress, ProgressBar, ProgressDrawTarget};
+    use std::io;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    use crate::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle, TermLike};
 
     #[test]
     fn late_pb_drop() {
@@ -693,4 +1005,73 @@ mod tests {
         let pb = mp.add(ProgressBar::new(10));
         mp.add(pb);
     }
+
+    /// Counts how often it's flushed, which happens once per repaint
+    #[derive(Clone, Debug, Default)]
+    struct PaintCounter(Arc<AtomicUsize>);
+
+    impl TermLike for PaintCounter {
+        fn width(&self) -> u16 {
+            80
+        }
+        fn move_cursor_up(&self, _: usize) -> io::Result<()> {
+            Ok(())
+        }
+        fn move_cursor_down(&self, _: usize) -> io::Result<()> {
+            Ok(())
+        }
+        fn move_cursor_right(&self, _: usize) -> io::Result<()> {
+            Ok(())
+        }
+        fn move_cursor_left(&self, _: usize) -> io::Result<()> {
+            Ok(())
+        }
+        fn write_line(&self, _: &str) -> io::Result<()> {
+            Ok(())
+        }
+        fn write_str(&self, _: &str) -> io::Result<()> {
+            Ok(())
+        }
+        fn clear_line(&self) -> io::Result<()> {
+            Ok(())
+        }
+        fn flush(&self) -> io::Result<()> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn multi_progress_max_refresh_rate() {
+        let term = PaintCounter::default();
+        let mp =
+            MultiProgress::with_draw_target(ProgressDrawTarget::term_like(Box::new(term.clone())));
+        mp.set_max_refresh_rate(10);
+
+        let time = Arc::new(Mutex::new(Instant::now()));
+        let bars = (0..5)
+            .map(|_| {
+                let clock = time.clone();
+                mp.add(
+                    ProgressBar::new(10)
+                        .with_style(ProgressStyle::with_template("{msg}").unwrap())
+                        .with_clock(move || *clock.lock().unwrap()),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        // One simulated second of updates from every bar, every 10ms
+        for step in 0..100 {
+            *time.lock().unwrap() += Duration::from_millis(10);
+            for pb in &bars {
+                pb.set_message(format!("step {step}"));
+            }
+        }
+        let paints = term.0.load(Ordering::SeqCst);
+        assert!((1..=11).contains(&paints), "{paints} repaints");
+
+        // Finishing is shown right away
+        bars[0].finish();
+        assert_eq!(term.0.load(Ordering::SeqCst), paints + 1);
+    }
 }
@@ -1,26 +1,34 @@
 #[cfg(test)]
 use portable_atomic::{AtomicBool, Ordering};
 use std::borrow::Cow;
-use std::sync::{Arc, Condvar, Mutex, MutexGuard, Weak};
-use std::time::Duration;
+use std::sync::{Arc, Condvar, Mutex, PoisonError, Weak};
 #[cfg(not(target_arch = "wasm32"))]
 use std::time::Instant;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::{fmt, io, thread};
 
+use console::Style;
 #[cfg(target_arch = "wasm32")]
 use instant::Instant;
 #[cfg(test)]
 use once_cell::sync::Lazy;
 
 use crate::draw_target::ProgressDrawTarget;
-use crate::state::{AtomicPosition, BarState, ProgressFinish, Reset, TabExpandedString};
+use crate::state::{
+    backdate, uncontended, AggregateLink, AtomicPosition, BarState, BarStateGuard, EstimatorKind,
+    ProgressFinish, Reset, TabExpandedString, UpdateHook,
+};
 use crate::style::ProgressStyle;
-use crate::{ProgressBarIter, ProgressIterator, ProgressState};
+use crate::{Clock, ProgressBarIter, ProgressIterator, ProgressState, ProgressStatus};
 
 /// A progress bar or spinner
 ///
 /// The progress bar is an [`Arc`] around its internal state. When the progress bar is cloned it
 /// just increments the refcount (so the original and its clone share the same state).
+///
+/// Positions and lengths are `u64` values, which saturate at `u64::MAX` when incremented. There
+/// is no wider variant; to track more than `u64::MAX` units (16 EiB when counting bytes), count
+/// in larger units such as KiB instead.
 #[derive(Clone)]
 pub struct ProgressBar {
     state: Arc<Mutex<BarState>>,
@@ -47,10 +55,24 @@ impl ProgressBar {
         Self::with_draw_target(Some(len), ProgressDrawTarget::stderr())
     }
 
+    /// Creates a new progress bar that counts down from `start` to zero
+    ///
+    /// The position starts at `start` and is decreased with [`dec()`](ProgressBar::dec), e.g. to
+    /// show the number of items left to clean up. The bar fills up as the position approaches
+    /// zero, the ETA estimates the time until it gets there, and finishing the progress bar sets
+    /// the position to zero. Like [`ProgressBar::new()`], it draws to stderr.
+    pub fn new_countdown(start: u64) -> Self {
+        let bar = Self::new(start);
+        bar.state().state.countdown_start = Some(start);
+        bar.pos.set(start);
+        bar
+    }
+
     /// Creates a completely hidden progress bar
     ///
     /// This progress bar still responds to API changes but it does not have a length or render in
-    /// any way.
+    /// any way. Its position, rate and ETA are tracked as usual, and no threads are spawned unless
+    /// a steady tick is enabled.
     pub fn hidden() -> Self {
         Self::with_draw_target(None, ProgressDrawTarget::hidden())
     }
@@ -111,8 +133,71 @@ impl ProgressBar {
     }
 
     /// A convenience builder-like function for a progress bar with a given elapsed time
+    ///
+    /// If `elapsed` reaches back further than the platform's clock can represent, the earliest
+    /// representable start time is used instead.
     pub fn with_elapsed(self, elapsed: Duration) -> Self {
-        self.state().state.started = Instant::now().checked_sub(elapsed).unwrap();
+        let (mut state, now) = self.state_now();
+        state.state.started = backdate(now, elapsed);
+        drop(state);
+        self
+    }
+
+    /// A convenience builder-like function for a progress bar with a given rate estimator
+    ///
+    /// The estimator drives the `{eta}`, `{duration}` and `{per_sec}` template keys. Any rate
+    /// data recorded so far is discarded. See [`EstimatorKind`] for the available algorithms.
+    pub fn with_estimator(self, kind: EstimatorKind) -> Self {
+        let (mut state, now) = self.state_now();
+        state.state.set_estimator(kind, now);
+        drop(state);
+        self
+    }
+
+    /// A convenience builder-like function for a progress bar that estimates its rate from the
+    /// given number of most recent updates
+    ///
+    /// This is a shorthand for `with_estimator(EstimatorKind::Samples(samples))`. Panics if
+    /// `samples` is zero.
+    pub fn with_eta_samples(self, samples: usize) -> Self {
+        self.with_estimator(EstimatorKind::Samples(samples))
+    }
+
+    /// A convenience builder-like function for a progress bar that doesn't estimate its rate
+    ///
+    /// This saves recording each update for progress bars that are updated very often but never
+    /// show their rate or ETA. [`eta()`](ProgressBar::eta) and [`per_sec()`](ProgressBar::per_sec)
+    /// (and the corresponding template keys) are always zero then, unless a
+    /// [fixed rate](ProgressBar::set_fixed_rate) is set.
+    pub fn without_eta(self) -> Self {
+        self.state().state.estimate = false;
+        self
+    }
+
+    /// A convenience builder-like function for a progress bar that reads the time from `clock`
+    ///
+    /// By default, progress bars use the system's monotonic clock. A custom [`Clock`] makes it
+    /// possible to drive the elapsed time, rate and ETA from simulated time, e.g. to test them
+    /// deterministically. The elapsed time and the rate estimate start over at the clock's
+    /// current time.
+    ///
+    /// The clock is also used to decide when to redraw, including the refresh rate limit of the
+    /// draw target. Only the throttling of very frequent [`inc`](ProgressBar::inc) and
+    /// [`set_position`](ProgressBar::set_position) calls always uses the system clock.
+    ///
+    /// ```rust
+    /// # use std::sync::{Arc, Mutex};
+    /// # use std::time::{Duration, Instant};
+    /// # use indicatif::ProgressBar;
+    /// let time = Arc::new(Mutex::new(Instant::now()));
+    /// let clock = time.clone();
+    /// let pb = ProgressBar::hidden().with_clock(move || *clock.lock().unwrap());
+    ///
+    /// *time.lock().unwrap() += Duration::from_secs(5);
+    /// assert_eq!(pb.elapsed(), Duration::from_secs(5));
+    /// ```
+    pub fn with_clock(self, clock: impl Clock + 'static) -> Self {
+        self.state().set_clock(Arc::new(clock));
         self
     }
 
@@ -132,6 +217,108 @@ impl ProgressBar {
         self
     }
 
+    /// A convenience builder-like function for a progress bar that's only painted once it ran
+    /// for `duration`
+    ///
+    /// This avoids flicker for tasks that are often quick. A progress bar that finishes sooner is
+    /// only painted in its final state, or not at all if it's cleared when finished.
+    pub fn with_min_display_duration(self, duration: Duration) -> Self {
+        self.state().min_display_duration = duration;
+        self
+    }
+
+    /// A convenience builder-like function that adds a segment to a stacked bar
+    ///
+    /// The segment starts out with a count of zero and is drawn with `style`, see
+    /// [`set_segment()`](ProgressBar::set_segment).
+    ///
+    /// ```rust
+    /// # use console::Style;
+    /// # use indicatif::ProgressBar;
+    /// let pb = ProgressBar::new(100)
+    ///     .with_segment("done", Style::new().green())
+    ///     .with_segment("in flight", Style::new().yellow());
+    /// pb.set_segment("done", 10);
+    /// pb.set_segment("in flight", 20);
+    /// ```
+    pub fn with_segment(self, name: impl Into<String>, style: Style) -> Self {
+        let name = name.into();
+        let mut state = self.state();
+        match state
+            .state
+            .segments
+            .iter_mut()
+            .find(|(segment, ..)| *segment == name)
+        {
+            Some(segment) => segment.2 = style,
+            None => state.state.segments.push((name, 0, style)),
+        }
+        drop(state);
+        self
+    }
+
+    /// Registers a callback that runs once, when the progress bar is finished
+    ///
+    /// The callback is invoked right after the final draw, so it sees the final state, no matter
+    /// whether the progress bar was finished explicitly or when it was dropped. Unlike the
+    /// [`on_update()`](ProgressBar::on_update) callback, it runs while the progress bar's internal
+    /// lock is held and must not call any methods on the progress bar.
+    pub fn with_finish_callback(self, f: impl FnOnce(&ProgressState) + Send + 'static) -> Self {
+        self.state().finish_callback = Some(Box::new(f));
+        self
+    }
+
+    /// Creates a progress bar that tracks the combined progress of `children`
+    ///
+    /// The position and length of the returned progress bar are the sums of the positions and
+    /// lengths of its children, so its completion is the mean of the children's completion
+    /// weighted by their length. It is updated whenever one of the children is, and estimates
+    /// its ETA from the combined rate of progress. Children without a length count as if their
+    /// length was equal to their position.
+    ///
+    /// Once a child is finished or dropped, its last position and length keep contributing to
+    /// the total. A progress bar can only contribute to one aggregate at a time; once it is
+    /// added to another aggregate, its contribution to the previous one is frozen as well. A
+    /// progress bar that's listed more than once only counts once.
+    ///
+    /// Like [`ProgressBar::new`], the aggregate draws to stderr by default and can be added to a
+    /// [`MultiProgress`](crate::MultiProgress).
+    ///
+    /// ```rust
+    /// # use indicatif::ProgressBar;
+    /// let children = [ProgressBar::new(10), ProgressBar::new(30)];
+    /// let total = ProgressBar::sum_of(&children);
+    /// assert_eq!(total.length(), Some(40));
+    /// ```
+    pub fn sum_of(children: &[ProgressBar]) -> Self {
+        let mut unique = Vec::<&ProgressBar>::with_capacity(children.len());
+        for child in children {
+            if !unique
+                .iter()
+                .any(|seen| Arc::ptr_eq(&seen.state, &child.state))
+            {
+                unique.push(child);
+            }
+        }
+
+        // The aggregate's lock is always taken after the lock of a child, never while holding it
+        let parent = Self::new(0);
+        let contributions = unique
+            .iter()
+            .map(|child| child.state().state.contribution())
+            .collect();
+        parent.state().set_aggregate_children(contributions);
+
+        // Linking reports the current contribution again, in case it changed in the meantime
+        for (idx, child) in unique.into_iter().enumerate() {
+            child.state().set_aggregate(AggregateLink {
+                parent: Arc::downgrade(&parent.state),
+                idx,
+            });
+        }
+        parent
+    }
+
     /// Creates a new spinner
     ///
     /// This spinner by default draws directly to stderr. This adds the default spinner style to it.
@@ -152,7 +339,8 @@ impl ProgressBar {
     pub fn set_tab_width(&mut self, tab_width: usize) {
         let mut state = self.state();
         state.set_tab_width(tab_width);
-        state.draw(true, Instant::now()).unwrap();
+        let now = state.now();
+        state.draw(true, now).unwrap();
     }
 
     /// Spawns a background thread to tick the progress bar
@@ -163,6 +351,20 @@ impl ProgressBar {
     /// When steady ticks are enabled, calling [`ProgressBar::tick()`] on a progress bar does not
     /// have any effect.
     pub fn enable_steady_tick(&self, interval: Duration) {
+        self.enable_ticker(interval, false);
+    }
+
+    /// Like [`enable_steady_tick()`](ProgressBar::enable_steady_tick), but ticks on multiples
+    /// of `interval` of the system clock
+    ///
+    /// Progress bars ticking with the same interval this way animate in sync, which looks calmer
+    /// in a [`MultiProgress`](crate::MultiProgress). The time until the next tick is recomputed
+    /// after every tick, so the ticks stay aligned even if the system clock is adjusted.
+    pub fn enable_aligned_steady_tick(&self, interval: Duration) {
+        self.enable_ticker(interval, true);
+    }
+
+    fn enable_ticker(&self, interval: Duration, aligned: bool) {
         // The way we test for ticker termination is with a single static `AtomicBool`. Since cargo
         // runs tests concurrently, we have a `TICKER_TEST` lock to make sure tests using ticker
         // don't step on each other. This check catches attempts to use tickers in tests without
@@ -183,44 +385,167 @@ impl ProgressBar {
             return;
         }
 
-        self.stop_and_replace_ticker(Some(interval));
+        self.stop_and_replace_ticker(Some(interval), aligned);
+    }
+
+    /// The interval of the steady tick, if it's enabled
+    ///
+    /// This is `None` after [`disable_steady_tick()`](ProgressBar::disable_steady_tick) and once
+    /// the progress bar is finished, which stops the steady tick.
+    pub fn steady_tick_interval(&self) -> Option<Duration> {
+        self.ticker.lock().unwrap().as_ref().map(Ticker::interval)
+    }
+
+    /// Changes the interval of the steady tick without restarting its thread
+    ///
+    /// The next tick happens `interval` after this call. An interval of zero stops future ticks,
+    /// like [`disable_steady_tick()`](ProgressBar::disable_steady_tick). If the steady tick isn't
+    /// enabled yet, this enables it like [`enable_steady_tick()`](ProgressBar::enable_steady_tick).
+    pub fn set_steady_tick(&self, interval: Duration) {
+        if interval.is_zero() {
+            return self.disable_steady_tick();
+        }
+
+        if let Some(ticker) = &*self.ticker.lock().unwrap() {
+            return ticker.set_interval(interval);
+        }
+
+        self.enable_steady_tick(interval);
     }
 
     /// Undoes [`ProgressBar::enable_steady_tick()`]
     pub fn disable_steady_tick(&self) {
-        self.stop_and_replace_ticker(None);
+        self.stop_and_replace_ticker(None, false);
     }
 
-    fn stop_and_replace_ticker(&self, interval: Option<Duration>) {
+    fn stop_and_replace_ticker(&self, interval: Option<Duration>, aligned: bool) {
         let mut ticker_state = self.ticker.lock().unwrap();
         if let Some(ticker) = ticker_state.take() {
             ticker.stop();
         }
 
-        *ticker_state = interval.map(|interval| Ticker::new(interval, &self.state));
+        *ticker_state = interval.map(|interval| Ticker::new(interval, aligned, &self.state));
+    }
+
+    /// Registers a callback that is invoked with the current state whenever the bar is updated
+    ///
+    /// The callback fires on the same occasions as a redraw: after position and length changes,
+    /// message and prefix changes, ticks and when the bar is finished. Like redraws, rapid
+    /// [`inc`](ProgressBar::inc) and [`set_position`](ProgressBar::set_position) calls are
+    /// batched, and while a [steady tick](ProgressBar::enable_steady_tick) is enabled position
+    /// changes are reported on the next tick. The last call always reflects the final state.
+    ///
+    /// The callback is passed a copy of the state taken during the update, and runs after the
+    /// progress bar's internal lock is released, so it may call methods on the progress bar. It
+    /// runs on one thread at a time: updates made while it's running, e.g. from other threads or
+    /// by the callback itself, are passed to it once it returns, with intermediate states
+    /// skipped. Registering a new callback replaces the previous one.
+    pub fn on_update(&self, f: impl Fn(&ProgressState) + Send + 'static) {
+        self.state().on_update = Some(Arc::new(UpdateHook::new(Box::new(f))));
+    }
+
+    /// Limits how often the progress bar repaints in response to updates
+    ///
+    /// Updates made within `interval` of the last paint still change the bar's state, but are
+    /// only shown with the next paint after the interval has passed. This is independent of the
+    /// draw target's refresh rate and of the [steady tick](ProgressBar::enable_steady_tick).
+    /// Finishing the progress bar always repaints it. Use [`Duration::ZERO`] to disable the limit.
+    pub fn set_draw_rate_limit(&self, interval: Duration) {
+        self.state().draw_rate_limit = interval;
+    }
+
+    /// Whether a [`tick()`](ProgressBar::tick) at `now` would repaint the progress bar
+    ///
+    /// This reflects the [draw rate limit](ProgressBar::set_draw_rate_limit) and the refresh rate
+    /// of the draw target (including the one of a [`MultiProgress`](crate::MultiProgress))
+    /// given the time of the last paint, and the minimum display duration. Finished progress bars
+    /// are always repainted, and hidden ones never are. It doesn't take into account that a frame
+    /// identical to the previous one isn't written to the terminal again.
+    ///
+    /// [`inc()`](ProgressBar::inc), [`dec()`](ProgressBar::dec) and
+    /// [`set_position()`](ProgressBar::set_position) are first throttled to one update per
+    /// millisecond of real time (with bursts of up to ten), which isn't reflected here, so one of
+    /// those calls may not repaint even if this returns `true`.
+    pub fn should_draw(&self, now: Instant) -> bool {
+        self.state().should_draw(now)
     }
 
     /// Manually ticks the spinner or progress bar
     ///
-    /// This automatically happens on any other change to a progress bar.
+    /// This always advances the spinner to its next frame. The bar is repainted right away,
+    /// unless a [steady tick](ProgressBar::enable_steady_tick) is enabled, in which case the new
+    /// frame is shown with the next steady tick.
+    ///
+    /// Without a steady tick, this automatically happens on any other change to a progress bar.
     pub fn tick(&self) {
-        self.tick_inner(Instant::now());
+        if self.ticker.lock().unwrap().is_none() {
+            let (mut state, now) = self.state_now();
+            state.tick(now);
+        } else {
+            self.state().advance_frame();
+        }
+    }
+
+    /// Redraws the progress bar right away
+    ///
+    /// Unlike [`tick()`](ProgressBar::tick), this doesn't advance the spinner, and the bar is
+    /// repainted even if it was drawn recently. This is useful to show changes that the progress
+    /// bar can't observe itself, like new data behind a custom key added with
+    /// [`ProgressStyle::with_key()`]. A progress bar that was finished and cleared stays cleared.
+    pub fn redraw(&self) {
+        let (mut state, now) = self.state_now();
+        let _ = state.draw(true, now);
     }
 
-    fn tick_inner(&self, now: Instant) {
+    fn tick_inner(&self) {
         // Only tick if a `Ticker` isn't installed
         if self.ticker.lock().unwrap().is_none() {
-            self.state().tick(now);
+            let (mut state, now) = self.state_now();
+            state.tick(now);
         }
     }
 
     /// Advances the position of the progress bar by `delta`
+    ///
+    /// The position saturates at `u64::MAX` rather than wrapping around.
+    ///
+    /// The position is updated atomically without locking the progress bar, so many threads can
+    /// call this concurrently. The lock is only taken to update the estimate and redraw, which
+    /// happens at most about once per millisecond.
     pub fn inc(&self, delta: u64) {
-        self.pos.inc(delta);
-        let now = Instant::now();
-        if self.pos.allow(now) {
-            self.tick_inner(now);
+        self.inc_and_get(delta);
+    }
+
+    /// Advances the position of the progress bar by `delta` and returns the new position
+    ///
+    /// Like [`inc()`](ProgressBar::inc), but the position resulting from this increment is
+    /// returned, so concurrent callers can act on it without racing a separate
+    /// [`position()`](ProgressBar::position) call.
+    pub fn inc_and_get(&self, delta: u64) -> u64 {
+        let pos = self.pos.inc(delta);
+        // The fast path throttle always uses real time, regardless of the bar's clock
+        if self.pos.allow(Instant::now()) {
+            self.tick_inner();
         }
+        pos
+    }
+
+    /// Decreases the position of the progress bar by `delta`
+    ///
+    /// The position saturates at zero. This is mostly useful for progress bars that
+    /// [count down](ProgressBar::new_countdown), for which the rate and ETA are based on how
+    /// quickly the position decreases. Like [`inc()`](ProgressBar::inc), this doesn't lock the
+    /// progress bar on every call.
+    pub fn dec(&self, delta: u64) {
+        self.pos.dec(delta);
+        if self.pos.allow(Instant::now()) {
+            self.tick_inner();
+        }
+    }
+
+    /// Whether and how the progress bar was finished
+    pub fn status(&self) -> ProgressStatus {
+        self.state().state.status()
     }
 
     /// A quick convenience check if the progress bar is hidden
@@ -228,6 +553,33 @@ impl ProgressBar {
         self.state().draw_target.is_hidden()
     }
 
+    /// Sets whether the progress bar is shown
+    ///
+    /// A progress bar that isn't visible keeps its state and can be updated as usual, but isn't
+    /// drawn until it's made visible again, at which point it shows its current state. In a
+    /// [`MultiProgress`](crate::MultiProgress), the other progress bars take up its lines while
+    /// it's not visible. Unlike [`finish_and_clear()`](ProgressBar::finish_and_clear), this
+    /// doesn't finish the progress bar.
+    pub fn set_visible(&self, visible: bool) {
+        let (mut state, now) = self.state_now();
+        state.state.visible = visible;
+        let _ = state.draw(true, now);
+    }
+
+    /// Whether the progress bar is shown, see [`set_visible()`](ProgressBar::set_visible)
+    pub fn is_visible(&self) -> bool {
+        self.state().state.visible
+    }
+
+    /// The number of terminal rows the progress bar took up when it was last drawn
+    ///
+    /// This accounts for multi-line templates and lines that wrap at the terminal width. It is
+    /// `0` until the progress bar is first drawn, while it isn't visible and after it was
+    /// cleared.
+    pub fn line_count(&self) -> usize {
+        self.state().line_count
+    }
+
     /// Indicates that the progress bar finished
     pub fn is_finished(&self) -> bool {
         self.state().state.is_finished()
@@ -245,32 +597,85 @@ impl ProgressBar {
     /// [`ProgressBar::suspend()`]: ProgressBar::suspend
     /// [`MultiProgress`]: crate::MultiProgress
     pub fn println<I: AsRef<str>>(&self, msg: I) {
-        self.state().println(Instant::now(), msg.as_ref());
+        let (mut state, now) = self.state_now();
+        state.println(now, msg.as_ref());
+    }
+
+    /// Print a log line above the progress bar, unless that would block
+    ///
+    /// Like [`println()`](ProgressBar::println), but returns `false` instead of waiting if
+    /// another thread is currently updating or drawing the progress bar (or the
+    /// [`MultiProgress`](crate::MultiProgress) it was added to). The line is dropped in that case,
+    /// so this is meant for logging where latency matters more than completeness.
+    pub fn try_println<I: AsRef<str>>(&self, msg: I) -> bool {
+        let mut state = match uncontended(self.state.try_lock()) {
+            Some(state) => state,
+            None => return false,
+        };
+        let now = state.now();
+        state.try_println(now, msg.as_ref())
     }
 
     /// Update the `ProgressBar`'s inner [`ProgressState`]
     pub fn update(&self, f: impl FnOnce(&mut ProgressState)) {
-        self.state()
-            .update(Instant::now(), f, self.ticker.lock().unwrap().is_none());
+        let (mut state, now) = self.state_now();
+        state.update(now, f, self.ticker.lock().unwrap().is_none());
     }
 
     /// Sets the position of the progress bar
     pub fn set_position(&self, pos: u64) {
         self.pos.set(pos);
-        let now = Instant::now();
-        if self.pos.allow(now) {
-            self.tick_inner(now);
+        // The fast path throttle always uses real time, regardless of the bar's clock
+        if self.pos.allow(Instant::now()) {
+            self.tick_inner();
         }
     }
 
     /// Sets the length of the progress bar
     pub fn set_length(&self, len: u64) {
-        self.state().set_length(Instant::now(), len);
+        let (mut state, now) = self.state_now();
+        state.set_length(now, len);
+    }
+
+    /// Sets the position and the length of the progress bar at once
+    ///
+    /// Unlike calling [`ProgressBar::set_length()`] and [`ProgressBar::set_position()`] one after
+    /// the other, this never draws the new position against the old length (or vice versa), and
+    /// the rate estimate only sees the position once both are set. Like
+    /// [`ProgressBar::set_length()`], the position is capped to the length unless overshooting
+    /// is allowed.
+    pub fn set_pos_and_len(&self, pos: u64, len: u64) {
+        let (mut state, now) = self.state_now();
+        state.set_pos_and_len(now, pos, len);
     }
 
     /// Increase the length of the progress bar
     pub fn inc_length(&self, delta: u64) {
-        self.state().inc_length(Instant::now(), delta);
+        let (mut state, now) = self.state_now();
+        state.inc_length(now, delta);
+    }
+
+    /// Sets whether changing the length resets the ETA calculation
+    ///
+    /// By default, the rate measured so far is kept when the length changes through
+    /// [`set_length`](ProgressBar::set_length) or [`inc_length`](ProgressBar::inc_length). If
+    /// discovering more work also means the pace changes, enabling this makes the ETA start over
+    /// from fresh samples, as if [`reset_eta`](ProgressBar::reset_eta) was called.
+    pub fn set_reset_eta_on_length_change(&self, reset: bool) {
+        self.state().reset_eta_on_length_change = reset;
+    }
+
+    /// Sets whether the position may stay past the length
+    ///
+    /// By default, [`set_length`](ProgressBar::set_length) moves the position back to the new
+    /// length if it would otherwise be past it, and `{percent}` never shows more than 100%. With
+    /// overshoot allowed, the position is kept and `{percent}` shows the actual ratio, e.g. `150`.
+    ///
+    /// Either way, a position past the length draws a full bar, [`ProgressState::fraction()`] is
+    /// `1.0` and the ETA is zero. The position can always be moved past the length with
+    /// [`inc`](ProgressBar::inc) or [`set_position`](ProgressBar::set_position).
+    pub fn set_allow_overshoot(&self, allow: bool) {
+        self.state().state.allow_overshoot = allow;
     }
 
     /// Sets the current prefix of the progress bar
@@ -280,7 +685,8 @@ impl ProgressBar {
     pub fn set_prefix(&self, prefix: impl Into<Cow<'static, str>>) {
         let mut state = self.state();
         state.state.prefix = TabExpandedString::new(prefix.into(), state.tab_width);
-        state.update_estimate_and_draw(Instant::now());
+        let now = state.now();
+        state.update_estimate_and_draw(now);
     }
 
     /// Sets the current message of the progress bar
@@ -290,7 +696,55 @@ impl ProgressBar {
     pub fn set_message(&self, msg: impl Into<Cow<'static, str>>) {
         let mut state = self.state();
         state.state.message = TabExpandedString::new(msg.into(), state.tab_width);
-        state.update_estimate_and_draw(Instant::now());
+        let now = state.now();
+        state.update_estimate_and_draw(now);
+    }
+
+    /// Sets a named numeric metric of the progress bar
+    ///
+    /// Metrics can be rendered with the `{metric:NAME}` placeholder (see [`ProgressStyle`]), and
+    /// read with [`ProgressState::metric()`] from a custom [`ProgressTracker`] to derive other
+    /// values, e.g. the throughput relative to a configured cap.
+    ///
+    /// [`ProgressTracker`]: crate::style::ProgressTracker
+    pub fn set_metric(&self, name: impl Into<String>, value: f64) {
+        let mut state = self.state();
+        state.state.metrics.insert(name.into(), value);
+        let now = state.now();
+        state.update_estimate_and_draw(now);
+    }
+
+    /// Sets the count of a segment of a stacked bar
+    ///
+    /// Once a progress bar has segments, the `bar` and `wide_bar` keys render each segment as a
+    /// region proportional to its count, in the order the segments were added. The regions are
+    /// laid out relative to the length of the progress bar, or to the sum of the counts if that's
+    /// larger or there's no length. A segment that wasn't added with
+    /// [`with_segment()`](ProgressBar::with_segment) is added unstyled.
+    pub fn set_segment(&self, name: &str, count: u64) {
+        let mut state = self.state();
+        state.state.set_segment(name, count);
+        let now = state.now();
+        state.update_estimate_and_draw(now);
+    }
+
+    /// Abandons the progress bar and shows the cursor when the process is interrupted with Ctrl-C
+    ///
+    /// The first call (on any progress bar or [`MultiProgress`](crate::MultiProgress)) installs a
+    /// handler that abandons all unfinished progress bars registered this way, shows the cursor
+    /// and exits the process with status 130, see
+    /// [`set_exit_on_interrupt()`](crate::set_exit_on_interrupt) to keep it running instead.
+    /// Later calls only register the progress bar.
+    ///
+    /// A handler the application installed before is never replaced, as it couldn't be called
+    /// afterwards: this fails with [`ctrlc::Error::MultipleHandlers`] if it was installed with
+    /// `ctrlc`, or with [`ctrlc::Error::System`] otherwise (e.g. with `signal-hook`), but the
+    /// progress bar is registered either way. Chain to this crate's cleanup by calling
+    /// [`handle_interrupt()`](crate::handle_interrupt) from that handler then.
+    #[cfg(feature = "ctrlc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "ctrlc")))]
+    pub fn install_ctrlc_handler(&self) -> Result<(), ctrlc::Error> {
+        crate::interrupt::register(crate::interrupt::Target::Bar(self.downgrade()))
     }
 
     /// Creates a new weak reference to this [`ProgressBar`]
@@ -302,28 +756,105 @@ impl ProgressBar {
         }
     }
 
+    /// Sets a rate in steps per second to use instead of the estimated one
+    ///
+    /// This is useful if the throughput is known in advance, e.g. for a transfer over a link with
+    /// a fixed bandwidth. The rate is used by [`eta()`](ProgressBar::eta),
+    /// [`duration()`](ProgressBar::duration) and [`per_sec()`](ProgressBar::per_sec) and the
+    /// corresponding template keys while the progress bar is in progress. Passing `None` goes back
+    /// to the estimated rate. Panics if the rate is negative, infinite or NaN.
+    pub fn set_fixed_rate(&self, steps_per_sec: Option<f64>) {
+        if let Some(rate) = steps_per_sec {
+            assert!(
+                rate.is_finite() && rate >= 0.0,
+                "fixed rate must be finite and non-negative"
+            );
+        }
+        self.state().state.fixed_rate = steps_per_sec;
+    }
+
     /// Resets the ETA calculation
     ///
     /// This can be useful if the progress bars made a large jump or was paused for a prolonged
-    /// time.
+    /// time. Only the rate data behind the ETA and the `per_sec` keys is discarded, everything
+    /// else is kept.
     pub fn reset_eta(&self) {
-        self.state().reset(Instant::now(), Reset::Eta);
+        let (mut state, now) = self.state_now();
+        state.reset(now, Reset::Eta);
+    }
+
+    /// Pauses the progress bar
+    ///
+    /// While paused, the elapsed time stops counting and the ETA and rate estimates are frozen at
+    /// their current values. The position can still be updated; any progress made while paused is
+    /// accounted for once the bar is [resumed](ProgressBar::resume). Pausing a bar that is
+    /// already paused has no effect.
+    pub fn pause(&self) {
+        let (mut state, now) = self.state_now();
+        state.pause(now);
+    }
+
+    /// Resumes a progress bar paused with [`ProgressBar::pause()`]
+    ///
+    /// The time spent paused is excluded from the elapsed time and the rate estimate. Resuming a
+    /// bar that isn't paused has no effect.
+    pub fn resume(&self) {
+        let (mut state, now) = self.state_now();
+        state.resume(now);
+    }
+
+    /// Indicates that the progress bar is paused
+    pub fn is_paused(&self) -> bool {
+        self.state().state.is_paused()
+    }
+
+    /// Restores the position and elapsed time from an earlier run, e.g. a resumed download
+    ///
+    /// The bar continues as if `pos` steps had been made over the last `elapsed`, which is
+    /// reflected by [`elapsed()`](ProgressBar::elapsed) and by the rate once the progress bar is
+    /// finished. The rate estimate, and with it the ETA, starts over from the checkpoint: any rate
+    /// data recorded so far is discarded, and the estimate is built from the progress made after
+    /// resuming, as the earlier run's rate may not apply anymore.
+    ///
+    /// Like with [`with_elapsed()`](ProgressBar::with_elapsed), if `elapsed` reaches back further
+    /// than the platform's clock can represent, the earliest representable start time is used,
+    /// and the finished rate covers that shorter time.
+    pub fn resume_from(&self, pos: u64, elapsed: Duration) {
+        let (mut state, now) = self.state_now();
+        state.resume_from(now, pos, elapsed);
     }
 
     /// Resets elapsed time and the ETA calculation
+    ///
+    /// Like [`reset_eta()`](ProgressBar::reset_eta), but the elapsed time also starts counting
+    /// from zero again. The position and everything else is kept.
     pub fn reset_elapsed(&self) {
-        self.state().reset(Instant::now(), Reset::Elapsed);
+        let (mut state, now) = self.state_now();
+        state.reset(now, Reset::Elapsed);
     }
 
     /// Resets all of the progress bar state
+    ///
+    /// This resets the progress so the bar can be reused, e.g. for the next phase of a task:
+    ///
+    /// * the position goes back to zero, or to the starting position of a
+    ///   [countdown](ProgressBar::new_countdown),
+    /// * a finished or abandoned bar is in progress again,
+    /// * the elapsed time, the ETA calculation and the stall timer start over, and so does the
+    ///   `eta_smooth` key and any other [`ProgressTracker`](crate::style::ProgressTracker).
+    ///
+    /// The length, the message, the prefix, the metrics, the segment counts, the style and the
+    /// draw target are kept, as is the spinner's frame. A paused bar stays paused. Use
+    /// [`set_message()`](ProgressBar::set_message) and friends to change those for the next
+    /// phase.
     pub fn reset(&self) {
-        self.state().reset(Instant::now(), Reset::All);
+        let (mut state, now) = self.state_now();
+        state.reset(now, Reset::All);
     }
 
     /// Finishes the progress bar and leaves the current message
     pub fn finish(&self) {
-        self.state()
-            .finish_using_style(Instant::now(), ProgressFinish::AndLeave);
+        self.finish_with(ProgressFinish::AndLeave);
     }
 
     /// Finishes the progress bar and sets a message
@@ -331,20 +862,17 @@ impl ProgressBar {
     /// For the message to be visible, the `{msg}` placeholder must be present in the template (see
     /// [`ProgressStyle`]).
     pub fn finish_with_message(&self, msg: impl Into<Cow<'static, str>>) {
-        self.state()
-            .finish_using_style(Instant::now(), ProgressFinish::WithMessage(msg.into()));
+        self.finish_with(ProgressFinish::WithMessage(msg.into()));
     }
 
     /// Finishes the progress bar and completely clears it
     pub fn finish_and_clear(&self) {
-        self.state()
-            .finish_using_style(Instant::now(), ProgressFinish::AndClear);
+        self.finish_with(ProgressFinish::AndClear);
     }
 
     /// Finishes the progress bar and leaves the current message and progress
     pub fn abandon(&self) {
-        self.state()
-            .finish_using_style(Instant::now(), ProgressFinish::Abandon);
+        self.finish_with(ProgressFinish::Abandon);
     }
 
     /// Finishes the progress bar and sets a message, and leaves the current progress
@@ -352,19 +880,54 @@ impl ProgressBar {
     /// For the message to be visible, the `{msg}` placeholder must be present in the template (see
     /// [`ProgressStyle`]).
     pub fn abandon_with_message(&self, msg: impl Into<Cow<'static, str>>) {
-        self.state().finish_using_style(
-            Instant::now(),
-            ProgressFinish::AbandonWithMessage(msg.into()),
-        );
+        self.finish_with(ProgressFinish::AbandonWithMessage(msg.into()));
     }
 
     /// Finishes the progress bar using the behavior stored in the [`ProgressStyle`]
     ///
     /// See [`ProgressBar::with_finish()`].
     pub fn finish_using_style(&self) {
-        let mut state = self.state();
-        let finish = state.on_finish.clone();
-        state.finish_using_style(Instant::now(), finish);
+        let finish = self.state().on_finish.clone();
+        self.finish_with(finish);
+    }
+
+    fn finish_with(&self, finish: ProgressFinish) {
+        let (mut state, now) = self.state_now();
+        state.finish_using_style(now, finish);
+        drop(state);
+        self.stop_ticker();
+    }
+
+    /// Stops the steady ticker, if any, and waits for its thread to exit
+    ///
+    /// A finished progress bar's ticker would exit on its next wakeup, but that may be a while.
+    /// This must be called without holding the state lock, which the ticker thread may be
+    /// waiting for.
+    fn stop_ticker(&self) {
+        self.stop_and_replace_ticker(None, false);
+    }
+
+    /// Returns a guard that finishes the progress bar using `finish` when it's dropped
+    ///
+    /// This leaves an informative progress bar behind when a scope is left early, e.g. through
+    /// `?` or a panic, regardless of the behavior set with [`ProgressBar::with_finish()`]. The
+    /// guard doesn't do anything if the progress bar was already finished by then.
+    ///
+    /// ```rust,no_run
+    /// # use indicatif::{ProgressBar, ProgressFinish};
+    /// # fn download(pb: &ProgressBar) -> std::io::Result<()> { Ok(()) }
+    /// fn run(pb: &ProgressBar) -> std::io::Result<()> {
+    ///     let _guard = pb.finish_on_drop(ProgressFinish::AbandonWithMessage("interrupted".into()));
+    ///     download(pb)?;
+    ///     pb.finish_with_message("done");
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn finish_on_drop(&self, finish: ProgressFinish) -> FinishGuard {
+        FinishGuard {
+            pb: self.clone(),
+            finish: Some(finish),
+        }
     }
 
     /// Sets a different draw target for the progress bar
@@ -412,7 +975,28 @@ impl ProgressBar {
     ///
     /// [`MultiProgress`]: crate::MultiProgress
     pub fn suspend<F: FnOnce() -> R, R>(&self, f: F) -> R {
-        self.state().suspend(Instant::now(), f)
+        let (mut state, now) = self.state_now();
+        state.suspend(now, f)
+    }
+
+    /// Flushes the underlying terminal or writer
+    ///
+    /// Output written to a buffered writer may otherwise only become visible once the buffer
+    /// fills up. If the progress bar was added to a [`MultiProgress`](crate::MultiProgress), this
+    /// flushes the draw target of the `MultiProgress`.
+    pub fn flush(&self) -> io::Result<()> {
+        self.state().draw_target.flush()
+    }
+
+    /// Hide the progress bar temporarily and execute `f`, unless that would block
+    ///
+    /// Like [`suspend()`](ProgressBar::suspend), but returns `None` without executing `f` if
+    /// another thread is currently updating or drawing the progress bar (or the
+    /// [`MultiProgress`](crate::MultiProgress) it was added to).
+    pub fn try_suspend<F: FnOnce() -> R, R>(&self, f: F) -> Option<R> {
+        let mut state = uncontended(self.state.try_lock())?;
+        let now = state.now();
+        state.try_suspend(now, f)
     }
 
     /// Wraps an [`Iterator`] with the progress bar
@@ -447,11 +1031,48 @@ impl ProgressBar {
         ProgressBarIter {
             progress: self.clone(),
             it: read,
+            size_hint: None,
+        }
+    }
+
+    /// Wraps an [`io::Read`] that can seek with the progress bar, setting its length
+    ///
+    /// The length is set to the number of bytes left to read from the current position, which
+    /// is found by seeking to the end and back. If seeking fails, the length is left unchanged,
+    /// so a progress bar without a length keeps being drawn as a spinner.
+    ///
+    /// Returns an error if the reader can't be seeked back to where it was, in which case the
+    /// reader is dropped rather than read from the wrong position.
+    ///
+    /// ```rust,no_run
+    /// # use std::fs::File;
+    /// # use std::io;
+    /// # use indicatif::ProgressBar;
+    /// # fn test () -> io::Result<()> {
+    /// let source = File::open("work.txt")?;
+    /// let mut target = File::create("done.txt")?;
+    /// let pb = ProgressBar::new_spinner();
+    /// io::copy(&mut pb.wrap_read_seek(source)?, &mut target);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn wrap_read_seek<R: io::Read + io::Seek>(
+        &self,
+        mut read: R,
+    ) -> io::Result<ProgressBarIter<R>> {
+        if let Some(len) = remaining_len(&mut read)? {
+            self.set_length(len);
         }
+        Ok(self.wrap_read(read))
     }
 
     /// Wraps an [`io::Write`] with the progress bar
     ///
+    /// The progress bar is incremented by the number of bytes each write reports as written,
+    /// which may be less than the length of the buffer passed to it. Once the wrapper and all
+    /// other clones of the progress bar are dropped, it's finished according to its
+    /// [`ProgressFinish`].
+    ///
     /// ```rust,no_run
     /// # use std::fs::File;
     /// # use std::io;
@@ -468,6 +1089,7 @@ impl ProgressBar {
         ProgressBarIter {
             progress: self.clone(),
             it: write,
+            size_hint: None,
         }
     }
 
@@ -494,6 +1116,7 @@ impl ProgressBar {
         ProgressBarIter {
             progress: self.clone(),
             it: write,
+            size_hint: None,
         }
     }
 
@@ -517,6 +1140,7 @@ impl ProgressBar {
         ProgressBarIter {
             progress: self.clone(),
             it: read,
+            size_hint: None,
         }
     }
 
@@ -539,6 +1163,7 @@ impl ProgressBar {
         ProgressBarIter {
             progress: self.clone(),
             it: stream,
+            size_hint: None,
         }
     }
 
@@ -557,6 +1182,11 @@ impl ProgressBar {
         self.state().state.eta()
     }
 
+    /// Returns the current ETA, or `None` if it's unknown, see [`ProgressState::try_eta()`]
+    pub fn try_eta(&self) -> Option<Duration> {
+        self.state().state.try_eta()
+    }
+
     /// Returns the current rate of progress
     pub fn per_sec(&self) -> f64 {
         self.state().state.per_sec()
@@ -572,6 +1202,29 @@ impl ProgressBar {
         self.state().state.elapsed()
     }
 
+    /// Renders the progress bar as it would currently be drawn, without drawing it
+    ///
+    /// This uses the width of the draw target, or 80 columns for draw targets that don't have
+    /// one (such as a hidden draw target). The lines of a multi-line template are separated by
+    /// `\n`.
+    pub fn render_line(&self) -> String {
+        let state = self.state();
+        let width = state.draw_target.width().unwrap_or(80);
+        render(&state, width)
+    }
+
+    /// Renders the progress bar at the given width, see [`ProgressBar::render_line()`]
+    pub fn render_line_with_width(&self, width: u16) -> String {
+        render(&self.state(), width)
+    }
+
+    /// Returns the time since the position last changed
+    ///
+    /// See [`ProgressState::time_since_progress()`].
+    pub fn time_since_progress(&self) -> Duration {
+        self.state().state.time_since_progress()
+    }
+
     /// Index in the `MultiState`
     pub(crate) fn index(&self) -> Option<usize> {
         self.state().draw_target.remote().map(|(_, idx)| idx)
@@ -588,11 +1241,66 @@ impl ProgressBar {
     }
 
     #[inline]
-    pub(crate) fn state(&self) -> MutexGuard<'_, BarState> {
-        self.state.lock().unwrap()
+    /// Locks the state and reads the current time from the progress bar's clock
+    fn state_now(&self) -> (BarStateGuard<'_>, Instant) {
+        let state = self.state();
+        let now = state.now();
+        (state, now)
+    }
+
+    pub(crate) fn state(&self) -> BarStateGuard<'_> {
+        BarStateGuard::lock(&self.state)
+    }
+}
+
+/// Finishes a [`ProgressBar`] when dropped, unless it's already finished
+///
+/// See [`ProgressBar::finish_on_drop()`].
+#[derive(Debug)]
+#[must_use = "the progress bar is finished as soon as the guard is dropped"]
+pub struct FinishGuard {
+    pb: ProgressBar,
+    finish: Option<ProgressFinish>,
+}
+
+impl Drop for FinishGuard {
+    fn drop(&mut self) {
+        // The guard may be dropped while unwinding from a panic that poisoned the lock, e.g. in
+        // a finish callback, and panicking again would abort the process
+        let state = self.pb.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut state = BarStateGuard::new(state);
+        if let (false, Some(finish)) = (state.state.is_finished(), self.finish.take()) {
+            let now = state.now();
+            state.finish_using_style(now, finish);
+            drop(state);
+            self.pb.stop_ticker();
+        }
     }
 }
 
+fn render(state: &BarState, width: u16) -> String {
+    let mut lines = Vec::new();
+    state.style.format_state(&state.state, &mut lines, width);
+    lines.join("\n")
+}
+
+/// Returns the number of bytes between the current position of `seek` and its end, or `None`
+/// if they can't be found
+///
+/// The position is restored afterwards, even if seeking to the end failed. Only failing to
+/// restore it is an error.
+fn remaining_len(seek: &mut impl io::Seek) -> io::Result<Option<u64>> {
+    let pos = match seek.stream_position() {
+        Ok(pos) => pos,
+        Err(_) => return Ok(None),
+    };
+    let end = seek.seek(io::SeekFrom::End(0));
+    if end.as_ref().ok() != Some(&pos) {
+        seek.seek(io::SeekFrom::Start(pos))?;
+    }
+    Ok(end.ok().map(|end| end.saturating_sub(pos)))
+}
+
 /// A weak reference to a [`ProgressBar`].
 ///
 /// Useful for creating custom steady tick implementations
@@ -624,80 +1332,142 @@ impl WeakProgressBar {
 }
 
 pub(crate) struct Ticker {
-    stopping: Arc<(Mutex<bool>, Condvar)>,
+    control: Arc<(Mutex<TickerSettings>, Condvar)>,
     join_handle: Option<thread::JoinHandle<()>>,
 }
 
+/// The state shared between a [`Ticker`] and its thread
+struct TickerSettings {
+    /// Whether the ticker was requested to stop
+    stopping: bool,
+    interval: Duration,
+}
+
 impl Drop for Ticker {
     fn drop(&mut self) {
         self.stop();
-        self.join_handle.take().map(|handle| handle.join());
+        // The ticker thread runs `on_update` callbacks, which may finish the progress bar or
+        // disable the steady tick: it exits by itself then, and can't wait for itself
+        match self.join_handle.take() {
+            Some(handle) if handle.thread().id() != thread::current().id() => {
+                let _ = handle.join();
+            }
+            _ => {}
+        }
     }
 }
 
 #[cfg(test)]
 static TICKER_RUNNING: AtomicBool = AtomicBool::new(false);
 
+/// Something a [`Ticker`] ticks, i.e. the state of a progress bar or spinner
+pub(crate) trait TickTarget: Send + Sync {
+    /// Ticks unless finished, returning whether to keep on ticking
+    fn steady_tick(&self) -> bool;
+}
+
+impl TickTarget for Mutex<BarState> {
+    fn steady_tick(&self) -> bool {
+        let mut state = BarStateGuard::lock(self);
+        if state.state.is_finished() {
+            return false;
+        }
+
+        let now = state.now();
+        state.tick(now);
+        true
+    }
+}
+
 impl Ticker {
-    pub(crate) fn new(interval: Duration, bar_state: &Arc<Mutex<BarState>>) -> Self {
+    pub(crate) fn new<T: TickTarget + 'static>(
+        interval: Duration,
+        aligned: bool,
+        target: &Arc<T>,
+    ) -> Self {
         debug_assert!(!interval.is_zero());
 
-        // A `Mutex<bool>` is used as a flag to indicate whether the ticker was requested to stop.
-        // The `Condvar` is used a notification mechanism: when the ticker is dropped, we notify
-        // the thread and interrupt the ticker wait.
-        #[allow(clippy::mutex_atomic)]
-        let stopping = Arc::new((Mutex::new(false), Condvar::new()));
+        // The `Condvar` is used a notification mechanism: when the ticker is dropped or its
+        // interval changes, we notify the thread and interrupt the ticker wait.
+        let settings = Arc::new((
+            Mutex::new(TickerSettings {
+                stopping: false,
+                interval,
+            }),
+            Condvar::new(),
+        ));
         let control = TickerControl {
-            stopping: stopping.clone(),
-            state: Arc::downgrade(bar_state),
+            settings: settings.clone(),
+            target: Arc::<T>::downgrade(target),
         };
 
-        let join_handle = thread::spawn(move || control.run(interval));
+        let join_handle = thread::spawn(move || control.run(aligned));
         Self {
-            stopping,
+            control: settings,
             join_handle: Some(join_handle),
         }
     }
 
+    pub(crate) fn interval(&self) -> Duration {
+        self.control.0.lock().unwrap().interval
+    }
+
+    /// Changes the interval, restarting the current wait
+    pub(crate) fn set_interval(&self, interval: Duration) {
+        debug_assert!(!interval.is_zero());
+        self.control.0.lock().unwrap().interval = interval;
+        self.control.1.notify_one();
+    }
+
     pub(crate) fn stop(&self) {
-        *self.stopping.0.lock().unwrap() = true;
-        self.stopping.1.notify_one();
+        self.control.0.lock().unwrap().stopping = true;
+        self.control.1.notify_one();
     }
 }
 
 struct TickerControl {
-    stopping: Arc<(Mutex<bool>, Condvar)>,
-    state: Weak<Mutex<BarState>>,
+    settings: Arc<(Mutex<TickerSettings>, Condvar)>,
+    target: Weak<dyn TickTarget>,
 }
 
 impl TickerControl {
-    fn run(&self, interval: Duration) {
+    fn run(&self, aligned: bool) {
         #[cfg(test)]
         TICKER_RUNNING.store(true, Ordering::SeqCst);
 
-        while let Some(arc) = self.state.upgrade() {
-            let mut state = arc.lock().unwrap();
-            if state.state.is_finished() {
+        'ticking: while let Some(arc) = self.target.upgrade() {
+            // The lock is released before sleeping
+            if !arc.steady_tick() {
                 break;
             }
-
-            state.tick(Instant::now());
-
-            drop(state); // Don't forget to drop the lock before sleeping
             drop(arc); // Also need to drop Arc otherwise BarState won't be dropped
 
-            // Wait for `interval` but return early if we are notified to stop
-            let result = self
-                .stopping
-                .1
-                .wait_timeout_while(self.stopping.0.lock().unwrap(), interval, |stopped| {
-                    !*stopped
-                })
-                .unwrap();
-
-            // If the wait didn't time out, it means we were notified to stop
-            if !result.1.timed_out() {
-                break;
+            let mut settings = self.settings.0.lock().unwrap();
+            loop {
+                let interval = settings.interval;
+                let wait = match aligned {
+                    true => SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map_or(interval, |now| until_boundary(now, interval)),
+                    false => interval,
+                };
+
+                // Wait for `wait` but return early if we are notified to stop or the interval
+                // changed, in which case we wait again using the new interval
+                let (guard, result) = self
+                    .settings
+                    .1
+                    .wait_timeout_while(settings, wait, |settings| {
+                        !settings.stopping && settings.interval == interval
+                    })
+                    .unwrap();
+                settings = guard;
+
+                if settings.stopping {
+                    break 'ticking;
+                } else if result.timed_out() {
+                    break;
+                }
             }
         }
 
@@ -706,13 +1476,219 @@ impl TickerControl {
     }
 }
 
+/// The time from `now` until the next multiple of `interval`, which is a full `interval` if `now`
+/// is a multiple itself
+fn until_boundary(now: Duration, interval: Duration) -> Duration {
+    let past = now.as_nanos() % interval.as_nanos();
+    interval - Duration::from_nanos(past as u64)
+}
+
 // Tests using the global TICKER_RUNNING flag need to be serialized
 #[cfg(test)]
 pub(crate) static TICKER_TEST: Lazy<Mutex<()>> = Lazy::new(Mutex::default);
 
 #[cfg(test)]
 mod tests {
+    use std::sync::mpsc;
+
     use super::*;
+    use crate::MultiProgress;
+
+    #[test]
+    fn finish_callback_runs_once() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let calls2 = calls.clone();
+        let pb = ProgressBar::hidden().with_finish_callback(move |state| {
+            calls2
+                .lock()
+                .unwrap()
+                .push((state.is_finished(), state.pos()))
+        });
+        pb.set_length(10);
+        pb.inc(3);
+        pb.finish();
+        pb.finish_with_message("again");
+        drop(pb);
+        assert_eq!(*calls.lock().unwrap(), [(true, 10)]);
+
+        // Finishing on drop runs the callback too
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let calls2 = calls.clone();
+        let pb = ProgressBar::hidden()
+            .with_finish(ProgressFinish::Abandon)
+            .with_finish_callback(move |state| {
+                calls2
+                    .lock()
+                    .unwrap()
+                    .push((state.is_finished(), state.pos()))
+            });
+        pb.inc(3);
+        drop(pb);
+        assert_eq!(*calls.lock().unwrap(), [(true, 3)]);
+    }
+
+    #[test]
+    fn finish_guard_survives_poisoned_lock() {
+        let pb = ProgressBar::hidden().with_finish_callback(|_| panic!("callback failed"));
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _guard = pb.finish_on_drop(ProgressFinish::Abandon);
+            pb.finish();
+        }));
+
+        // The guard dropped during unwinding without panicking again, which would have aborted
+        let err = result.unwrap_err();
+        assert_eq!(err.downcast_ref::<&str>(), Some(&"callback failed"));
+        assert!(pb.state.is_poisoned());
+
+        // Guards dropped outside of a panic don't trip over the poisoned lock either
+        drop(pb.finish_on_drop(ProgressFinish::Abandon));
+    }
+
+    #[test]
+    fn on_update_sees_monotonic_positions() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let pb = ProgressBar::hidden();
+        pb.set_length(100);
+        let seen2 = seen.clone();
+        pb.on_update(move |state| seen2.lock().unwrap().push((state.pos(), state.len())));
+
+        for _ in 0..50 {
+            pb.inc(1);
+        }
+        pb.set_position(60);
+        pb.set_message("almost done");
+        pb.set_length(80);
+        pb.finish();
+
+        let seen = seen.lock().unwrap();
+        assert!(seen.len() >= 4);
+        assert!(seen.windows(2).all(|w| w[0].0 <= w[1].0));
+        assert_eq!(seen.last(), Some(&(80, Some(80))));
+    }
+
+    #[test]
+    fn on_update_runs_outside_the_lock() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let pb = ProgressBar::hidden();
+        pb.set_length(10);
+        let (seen2, weak) = (seen.clone(), pb.downgrade());
+        pb.on_update(move |state| {
+            // The callback can use the progress bar, even to update it
+            let pb = weak.upgrade().unwrap();
+            if state.pos() == 5 && pb.message().is_empty() {
+                pb.set_message("halfway");
+            }
+            seen2
+                .lock()
+                .unwrap()
+                .push((pb.position(), state.message.expanded().to_owned()));
+        });
+
+        pb.set_position(5);
+        pb.finish();
+        assert_eq!(
+            *seen.lock().unwrap(),
+            [
+                (5, String::new()),
+                (5, "halfway".to_owned()),
+                (10, "halfway".to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn finish_from_on_update_with_steady_tick() {
+        let _guard = TICKER_TEST.lock().unwrap();
+        let pb = ProgressBar::hidden();
+        pb.set_length(10);
+        let weak = pb.downgrade();
+        pb.on_update(move |state| match weak.upgrade() {
+            Some(pb) if state.pos() >= 5 => pb.finish(),
+            _ => {}
+        });
+        pb.enable_steady_tick(Duration::from_millis(5));
+        pb.inc(5);
+
+        // The next tick reports the position and finishes the bar from the ticker thread
+        let start = Instant::now();
+        while !pb.is_finished() {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(5));
+        }
+        pb.disable_steady_tick();
+        assert!(pb.ticker.lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn sum_of_is_weighted_mean_of_children() {
+        let children = [
+            ProgressBar::hidden(),
+            ProgressBar::hidden(),
+            ProgressBar::hidden(),
+        ];
+        for (child, len) in children.iter().zip([10, 20, 70]) {
+            child.set_length(len);
+        }
+        let total = ProgressBar::sum_of(&children);
+        total.set_draw_target(ProgressDrawTarget::hidden());
+        assert_eq!(total.length(), Some(100));
+
+        for (child, pos) in children.iter().zip([5, 20, 7]) {
+            child.set_position(pos);
+            child.tick();
+        }
+
+        let weighted = children
+            .iter()
+            .map(|child| {
+                let state = child.state();
+                state.state.fraction() * state.state.len().unwrap() as f32
+            })
+            .sum::<f32>()
+            / 100.0;
+        assert_eq!(total.position(), 32);
+        assert!((total.state().state.fraction() - weighted).abs() < f32::EPSILON);
+
+        // Finished and dropped children keep their final contribution
+        children[0].abandon();
+        let [_, second, third] = children;
+        drop(second);
+        third.set_position(70);
+        third.tick();
+        assert_eq!(total.position(), 95);
+        assert_eq!(total.length(), Some(100));
+
+        // Children listed twice only count once
+        let total = ProgressBar::sum_of(&[third.clone(), third.clone()]);
+        assert_eq!(total.length(), Some(70));
+        third.set_position(60);
+        third.tick();
+        assert_eq!(total.position(), 60);
+    }
+
+    #[test]
+    fn concurrent_inc_counts_every_increment() {
+        const THREADS: u64 = 8;
+        const INCREMENTS: u64 = 100_000;
+
+        let pb = ProgressBar::hidden();
+        pb.set_length(THREADS * INCREMENTS);
+        let threads = (0..THREADS)
+            .map(|_| {
+                let pb = pb.clone();
+                thread::spawn(move || {
+                    for _ in 0..INCREMENTS {
+                        pb.inc(1);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(pb.position(), THREADS * INCREMENTS);
+    }
 
     #[allow(clippy::float_cmp)]
     #[test]
@@ -792,6 +1768,272 @@ mod tests {
         assert!(!TICKER_RUNNING.load(Ordering::SeqCst));
     }
 
+    #[test]
+    fn explicit_tick_advances_with_steady_tick() {
+        let _guard = TICKER_TEST.lock().unwrap();
+
+        let pb = ProgressBar::new_spinner();
+        pb.tick();
+        pb.tick();
+        assert_eq!(pb.state().state.tick, 2);
+
+        pb.enable_steady_tick(Duration::from_secs(3600));
+        // Give the thread time to start up and do its first tick
+        thread::sleep(Duration::from_millis(50));
+        let before = pb.state().state.tick;
+
+        pb.tick();
+        pb.tick();
+        pb.tick();
+        assert_eq!(pb.state().state.tick, before + 3);
+
+        // Other changes don't advance the frame while the ticker is running
+        pb.inc(1);
+        pb.set_message("busy");
+        assert_eq!(pb.state().state.tick, before + 3);
+
+        pb.disable_steady_tick();
+    }
+
+    #[test]
+    fn aligned_ticks_land_on_boundaries() {
+        let interval = Duration::from_millis(100);
+        for now in [0, 1, 99, 100, 150, 1_234_567] {
+            let now = Duration::from_millis(now);
+            let wait = until_boundary(now, interval);
+            assert!(!wait.is_zero() && wait <= interval, "{wait:?}");
+            assert_eq!((now + wait).as_nanos() % interval.as_nanos(), 0);
+        }
+
+        // Sub-millisecond times and intervals that don't divide a second evenly
+        let interval = Duration::from_micros(1_500);
+        let now = Duration::new(1_700_000_000, 123_456_789);
+        let wait = until_boundary(now, interval);
+        assert!(wait < interval);
+        assert_eq!((now + wait).as_nanos() % interval.as_nanos(), 0);
+    }
+
+    #[test]
+    fn aligned_ticker_ticks_and_terminates() {
+        let _guard = TICKER_TEST.lock().unwrap();
+        assert!(!TICKER_RUNNING.load(Ordering::SeqCst));
+
+        let pb = ProgressBar::new_spinner();
+        pb.enable_aligned_steady_tick(Duration::from_millis(20));
+        thread::sleep(Duration::from_millis(250));
+        assert!(TICKER_RUNNING.load(Ordering::SeqCst));
+        assert!(pb.state().state.tick >= 2);
+
+        drop(pb);
+        assert!(!TICKER_RUNNING.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn finish_stops_ticker_promptly() {
+        let _guard = TICKER_TEST.lock().unwrap();
+        assert!(!TICKER_RUNNING.load(Ordering::SeqCst));
+
+        let pb = ProgressBar::new_spinner();
+        pb.enable_steady_tick(Duration::from_secs(3600));
+        // Give the thread time to start up and go to sleep
+        thread::sleep(Duration::from_millis(50));
+        assert!(TICKER_RUNNING.load(Ordering::SeqCst));
+
+        let start = Instant::now();
+        pb.finish_and_clear();
+        assert!(!TICKER_RUNNING.load(Ordering::SeqCst));
+        assert!(start.elapsed() < Duration::from_secs(1));
+
+        // The same goes for finishing through a guard
+        let pb = ProgressBar::new_spinner();
+        pb.enable_steady_tick(Duration::from_secs(3600));
+        thread::sleep(Duration::from_millis(50));
+        drop(pb.finish_on_drop(ProgressFinish::Abandon));
+        assert!(!TICKER_RUNNING.load(Ordering::SeqCst));
+        assert_eq!(pb.status(), ProgressStatus::Abandoned);
+    }
+
+    #[test]
+    fn steady_tick_interval() {
+        let _guard = TICKER_TEST.lock().unwrap();
+
+        let pb = ProgressBar::new_spinner();
+        assert_eq!(pb.steady_tick_interval(), None);
+
+        pb.enable_steady_tick(Duration::from_millis(100));
+        assert_eq!(pb.steady_tick_interval(), Some(Duration::from_millis(100)));
+        pb.enable_aligned_steady_tick(Duration::from_millis(250));
+        assert_eq!(pb.steady_tick_interval(), Some(Duration::from_millis(250)));
+
+        // A zero interval is ignored
+        pb.enable_steady_tick(Duration::ZERO);
+        assert_eq!(pb.steady_tick_interval(), Some(Duration::from_millis(250)));
+
+        pb.disable_steady_tick();
+        assert_eq!(pb.steady_tick_interval(), None);
+
+        pb.enable_steady_tick(Duration::from_millis(100));
+        pb.finish();
+        assert_eq!(pb.steady_tick_interval(), None);
+    }
+
+    #[test]
+    fn hidden_bar_tracks_state() {
+        let time = Arc::new(Mutex::new(Instant::now()));
+        let clock = time.clone();
+        let advance = |secs| *time.lock().unwrap() += Duration::from_secs(secs);
+
+        let pb = ProgressBar::with_draw_target(Some(100), ProgressDrawTarget::hidden())
+            .with_clock(move || *clock.lock().unwrap());
+        for _ in 0..5 {
+            advance(1);
+            pb.inc(10);
+            pb.tick();
+        }
+
+        assert_eq!(pb.position(), 50);
+        assert_eq!(pb.per_sec(), 10.0);
+        assert_eq!(pb.eta(), Duration::from_secs(5));
+        assert_eq!(pb.steady_tick_interval(), None);
+
+        // Nothing is ever drawn, even when forced
+        let mut state = pb.state();
+        let now = state.now();
+        let drawable = state.draw_target.drawable(true, now).is_none();
+        drop(state);
+        assert!(drawable);
+    }
+
+    #[test]
+    fn inc_and_get_returns_new_position() {
+        let pb = ProgressBar::hidden();
+        let mut total = 0;
+        for delta in 1..=20 {
+            total += delta;
+            assert_eq!(pb.inc_and_get(delta), total);
+        }
+        assert_eq!(pb.position(), total);
+        pb.set_position(u64::MAX - 1);
+        assert_eq!(pb.inc_and_get(5), u64::MAX);
+
+        // Concurrent increments each see a distinct position
+        let pb = ProgressBar::hidden();
+        let handles = (0..4)
+            .map(|_| {
+                let pb = pb.clone();
+                thread::spawn(move || (0..100).map(|_| pb.inc_and_get(1)).collect::<Vec<_>>())
+            })
+            .collect::<Vec<_>>();
+        let mut seen = handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect::<Vec<_>>();
+        seen.sort_unstable();
+        assert_eq!(seen, (1..=400).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn render_line() {
+        let pb = ProgressBar::hidden()
+            .with_style(
+                ProgressStyle::with_template("{msg} [{wide_bar}] {pos}/{len}")
+                    .unwrap()
+                    .progress_chars("#>-"),
+            )
+            .with_message("copying");
+        pb.set_length(10);
+        pb.set_position(5);
+
+        assert_eq!(
+            pb.render_line_with_width(30),
+            "copying [#######>-------] 5/10"
+        );
+        // Hidden draw targets don't have a width, so the default of 80 columns is used
+        assert_eq!(console::measure_text_width(&pb.render_line()), 80);
+
+        pb.set_style(ProgressStyle::with_template("{pos}\n{msg}").unwrap());
+        assert_eq!(pb.render_line(), "5\ncopying");
+    }
+
+    #[test]
+    fn reset_keeps_message_and_prefix() {
+        let pb = ProgressBar::hidden()
+            .with_style(ProgressStyle::with_template("{prefix} {msg} {pos}/{len}").unwrap())
+            .with_prefix("[1/2]")
+            .with_message("downloading");
+        pb.set_length(10);
+        pb.set_position(7);
+        pb.abandon();
+
+        pb.reset();
+        assert_eq!(pb.position(), 0);
+        assert_eq!(pb.length(), Some(10));
+        assert!(!pb.is_finished());
+        assert_eq!(pb.prefix(), "[1/2]");
+        assert_eq!(pb.message(), "downloading");
+        assert_eq!(pb.render_line(), "[1/2] downloading 0/10");
+    }
+
+    #[test]
+    fn should_draw_follows_rate_limits() {
+        let start = Instant::now();
+        let clock = move || start;
+        let at = |millis| start + Duration::from_millis(millis);
+
+        let (target, _) = ProgressDrawTarget::string_buffer();
+        let pb = ProgressBar::with_draw_target(Some(10), target).with_clock(clock);
+        pb.set_draw_rate_limit(Duration::from_millis(100));
+        assert!(pb.should_draw(at(0)));
+        pb.tick();
+        assert!(!pb.should_draw(at(0)));
+        assert!(!pb.should_draw(at(99)));
+        assert!(pb.should_draw(at(100)));
+
+        let (target, _) = ProgressDrawTarget::string_buffer();
+        let mp = MultiProgress::with_draw_target(target);
+        mp.set_max_refresh_rate(10);
+        let pb = mp.add(ProgressBar::new(10).with_clock(clock));
+        assert!(pb.should_draw(at(0)));
+        pb.tick();
+        assert!(!pb.should_draw(at(50)));
+        assert!(pb.should_draw(at(100)));
+
+        // Finished progress bars are always drawn, hidden ones never are
+        pb.finish();
+        assert!(pb.should_draw(at(0)));
+        assert!(!ProgressBar::hidden().should_draw(at(100)));
+    }
+
+    #[test]
+    fn set_steady_tick_changes_interval() {
+        let _guard = TICKER_TEST.lock().unwrap();
+
+        let pb = ProgressBar::new_spinner();
+        pb.enable_steady_tick(Duration::from_secs(3600));
+        // Give the thread time to start up and go to sleep
+        thread::sleep(Duration::from_millis(50));
+        let before = pb.state().state.tick;
+
+        // The sleeping thread picks up the shorter interval right away
+        pb.set_steady_tick(Duration::from_millis(10));
+        assert_eq!(pb.steady_tick_interval(), Some(Duration::from_millis(10)));
+        thread::sleep(Duration::from_millis(200));
+        let after = pb.state().state.tick;
+        assert!(after >= before + 5, "{before} -> {after}");
+        assert!(TICKER_RUNNING.load(Ordering::SeqCst));
+
+        // And a longer one slows it down again
+        pb.set_steady_tick(Duration::from_secs(3600));
+        thread::sleep(Duration::from_millis(50));
+        let before = pb.state().state.tick;
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(pb.state().state.tick, before);
+
+        pb.set_steady_tick(Duration::ZERO);
+        assert_eq!(pb.steady_tick_interval(), None);
+        assert!(!TICKER_RUNNING.load(Ordering::SeqCst));
+    }
+
     #[test]
     fn ticker_thread_terminates_on_drop_2() {
         let _guard = TICKER_TEST.lock().unwrap();
@@ -812,4 +2054,79 @@ mod tests {
         drop(pb2);
         assert!(!TICKER_RUNNING.load(Ordering::SeqCst));
     }
+
+    #[test]
+    fn try_println_skips_when_contended() {
+        /// Runs `locked` on another thread, which waits while holding its lock until released
+        fn hold(locked: impl FnOnce(&dyn Fn()) + Send + 'static) -> impl FnOnce() {
+            let (locked_tx, locked_rx) = mpsc::channel();
+            let (release_tx, release_rx) = mpsc::channel::<()>();
+            let handle = thread::spawn(move || {
+                locked(&|| {
+                    locked_tx.send(()).unwrap();
+                    let _ = release_rx.recv();
+                });
+            });
+            locked_rx.recv().unwrap();
+            move || {
+                drop(release_tx);
+                handle.join().unwrap();
+            }
+        }
+
+        let mp = MultiProgress::with_draw_target(ProgressDrawTarget::hidden());
+        let pb = mp.add(ProgressBar::new(10));
+
+        // Another thread draws the `MultiProgress`
+        let multi = mp.state.clone();
+        let release = hold(move |wait| {
+            let _guard = multi.write().unwrap();
+            wait();
+        });
+        assert!(!pb.try_println("dropped"));
+        assert_eq!(pb.try_suspend(|| 42), None);
+        release();
+
+        // Another thread updates the progress bar
+        let bar = pb.clone();
+        let release = hold(move |wait| {
+            let _guard = bar.state();
+            wait();
+        });
+        assert!(!pb.try_println("dropped"));
+        assert_eq!(pb.try_suspend(|| 42), None);
+        release();
+
+        assert!(pb.try_println("printed"));
+        assert_eq!(pb.try_suspend(|| 42), Some(42));
+    }
+
+    #[test]
+    fn finish_on_drop_guard() {
+        fn step(pb: &ProgressBar, fail: bool) -> Result<(), &'static str> {
+            let _guard =
+                pb.finish_on_drop(ProgressFinish::AbandonWithMessage("interrupted".into()));
+            pb.inc(1);
+            if fail {
+                return Err("failed");
+            }
+            pb.finish_with_message("done");
+            Ok(())
+        }
+
+        let pb = ProgressBar::hidden();
+        pb.set_length(10);
+        assert!(step(&pb, true).is_err());
+        assert_eq!(pb.status(), ProgressStatus::Abandoned);
+        assert_eq!(pb.message(), "interrupted");
+        assert_eq!(pb.position(), 1);
+
+        // An explicitly finished progress bar is left alone
+        let pb = ProgressBar::hidden();
+        pb.set_length(10);
+        step(&pb, false).unwrap();
+        assert_eq!(pb.status(), ProgressStatus::Finished);
+        assert_eq!(pb.message(), "done");
+        assert_eq!(pb.position(), 10);
+    }
 }
@@ -0,0 +1,166 @@
+use console::AnsiCodeIterator;
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span, Text};
+
+use crate::ProgressBar;
+
+impl ProgressBar {
+    /// Renders the progress bar as [`Text`] for a [ratatui](https://ratatui.rs) application
+    ///
+    /// Like [`ProgressBar::render_line_with_width()`], this renders the current state for a
+    /// terminal of `width` columns without drawing anything, so the application stays in charge
+    /// of the terminal. Each line of the template becomes a [`Line`], and the colors and
+    /// attributes of the [`ProgressStyle`](crate::ProgressStyle) are carried over as ratatui
+    /// styles. Like when drawing to a terminal, styles are only applied if
+    /// [`console::colors_enabled()`] is true, or if they force styling.
+    #[cfg_attr(docsrs, doc(cfg(feature = "ratatui")))]
+    pub fn render_text(&self, width: u16) -> Text<'static> {
+        self.render_line_with_width(width)
+            .split('\n')
+            .map(ansi_to_line)
+            .collect::<Vec<_>>()
+            .into()
+    }
+}
+
+/// Splits a line with ANSI escape codes into spans styled accordingly
+fn ansi_to_line(line: &str) -> Line<'static> {
+    let mut spans = Vec::new();
+    let mut style = Style::default();
+    for (part, is_ansi) in AnsiCodeIterator::new(line) {
+        match is_ansi {
+            true => style = apply_sgr(style, part),
+            false => spans.push(Span::styled(part.to_string(), style)),
+        }
+    }
+    Line::from(spans)
+}
+
+/// Applies a Select Graphic Rendition escape code (e.g. `\x1b[1;32m`) to `style`, ignoring any
+/// other escape codes
+fn apply_sgr(mut style: Style, code: &str) -> Style {
+    let params = match code
+        .strip_prefix("\x1b[")
+        .and_then(|code| code.strip_suffix('m'))
+    {
+        Some(params) => params,
+        None => return style,
+    };
+
+    let mut params = params.split(';').map(|p| p.parse::<u8>().unwrap_or(0));
+    while let Some(param) = params.next() {
+        style = match param {
+            0 => Style::default(),
+            1 => style.add_modifier(Modifier::BOLD),
+            2 => style.add_modifier(Modifier::DIM),
+            3 => style.add_modifier(Modifier::ITALIC),
+            4 => style.add_modifier(Modifier::UNDERLINED),
+            5 => style.add_modifier(Modifier::SLOW_BLINK),
+            7 => style.add_modifier(Modifier::REVERSED),
+            8 => style.add_modifier(Modifier::HIDDEN),
+            9 => style.add_modifier(Modifier::CROSSED_OUT),
+            30..=37 => style.fg(basic_color(param - 30, false)),
+            90..=97 => style.fg(basic_color(param - 90, true)),
+            40..=47 => style.bg(basic_color(param - 40, false)),
+            100..=107 => style.bg(basic_color(param - 100, true)),
+            38 => match extended_color(&mut params) {
+                Some(color) => style.fg(color),
+                None => style,
+            },
+            48 => match extended_color(&mut params) {
+                Some(color) => style.bg(color),
+                None => style,
+            },
+            39 => style.fg(Color::Reset),
+            49 => style.bg(Color::Reset),
+            _ => style,
+        };
+    }
+    style
+}
+
+/// One of the 8 basic ANSI colors, or its bright variant
+fn basic_color(n: u8, bright: bool) -> Color {
+    match (n, bright) {
+        (0, false) => Color::Black,
+        (1, false) => Color::Red,
+        (2, false) => Color::Green,
+        (3, false) => Color::Yellow,
+        (4, false) => Color::Blue,
+        (5, false) => Color::Magenta,
+        (6, false) => Color::Cyan,
+        (7, false) => Color::Gray,
+        (0, true) => Color::DarkGray,
+        (1, true) => Color::LightRed,
+        (2, true) => Color::LightGreen,
+        (3, true) => Color::LightYellow,
+        (4, true) => Color::LightBlue,
+        (5, true) => Color::LightMagenta,
+        (6, true) => Color::LightCyan,
+        _ => Color::White,
+    }
+}
+
+/// Parses the rest of a 256-color (`5;n`) or RGB (`2;r;g;b`) color parameter
+fn extended_color(params: &mut impl Iterator<Item = u8>) -> Option<Color> {
+    match params.next()? {
+        5 => Some(Color::Indexed(params.next()?)),
+        2 => Some(Color::Rgb(params.next()?, params.next()?, params.next()?)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use console::set_colors_enabled;
+
+    use super::*;
+    use crate::ProgressStyle;
+
+    #[test]
+    fn render_text() {
+        set_colors_enabled(true);
+
+        let pb = ProgressBar::hidden()
+            .with_style(
+                ProgressStyle::with_template("{msg} [{bar:10.green/red}] {pos:.bold}/{len}")
+                    .unwrap()
+                    .progress_chars("#>-"),
+            )
+            .with_message("copying");
+        pb.set_length(10);
+        pb.set_position(5);
+
+        let text = pb.render_text(80);
+        assert_eq!(text.lines.len(), 1);
+        assert_eq!(text.lines[0].to_string(), "copying [#####>----] 5/10");
+
+        let spans = &text.lines[0].spans;
+        let filled = spans.iter().find(|span| span.content == "#####>").unwrap();
+        assert_eq!(filled.style.fg, Some(Color::Green));
+        let empty = spans.iter().find(|span| span.content == "----").unwrap();
+        assert_eq!(empty.style.fg, Some(Color::Red));
+        let pos = spans.iter().find(|span| span.content == "5").unwrap();
+        assert!(pos.style.add_modifier.contains(Modifier::BOLD));
+        let msg = spans
+            .iter()
+            .find(|span| span.content == "copying [")
+            .unwrap();
+        assert_eq!(msg.style, Style::default());
+    }
+
+    #[test]
+    fn sgr_codes() {
+        let style = apply_sgr(Style::default(), "\x1b[1;38;5;208;48;2;1;2;3m");
+        assert_eq!(style.fg, Some(Color::Indexed(208)));
+        assert_eq!(style.bg, Some(Color::Rgb(1, 2, 3)));
+        assert!(style.add_modifier.contains(Modifier::BOLD));
+
+        assert_eq!(apply_sgr(style, "\x1b[0m"), Style::default());
+        assert_eq!(apply_sgr(style, "\x1b[2K"), style);
+        assert_eq!(
+            apply_sgr(Style::default(), "\x1b[94m").fg,
+            Some(Color::LightBlue)
+        );
+    }
+}
@@ -20,12 +20,14 @@ where
         self.progress_with(ProgressBar::new(len))
     }
 
-    fn progress(self) -> ProgressBarIter<Self>
-    where
-        Self: IndexedParallelIterator,
-    {
-        let len = u64::try_from(self.len()).unwrap();
-        self.progress_count(len)
+    /// Wrap an iterator with default styling. Uses [`ParallelIterator::opt_len()`] as length, or
+    /// shows a spinner if the length isn't known up front.
+    fn progress(self) -> ProgressBarIter<Self> {
+        let bar = match self.opt_len() {
+            Some(len) => ProgressBar::new(u64::try_from(len).unwrap()),
+            None => ProgressBar::new_spinner(),
+        };
+        self.progress_with(bar)
     }
 
     /// Wrap an iterator with a progress bar and style it.
@@ -41,7 +43,11 @@ where
 
 impl<S: Send, T: ParallelIterator<Item = S>> ParallelProgressIterator for T {
     fn progress_with(self, progress: ProgressBar) -> ProgressBarIter<Self> {
-        ProgressBarIter { it: self, progress }
+        ProgressBarIter {
+            it: self,
+            progress,
+            size_hint: None,
+        }
     }
 }
 
@@ -99,6 +105,7 @@ impl<T, P: Producer<Item = T>> Producer for ProgressProducer<P> {
         ProgressBarIter {
             it: self.base.into_iter(),
             progress: self.progress,
+            size_hint: None,
         }
     }
 
@@ -208,7 +215,7 @@ impl<S: Send, T: ParallelIterator<Item = S>> ParallelIterator for ProgressBarIte
 
 #[cfg(test)]
 mod test {
-    use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+    use rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
 
     use crate::{ParallelProgressIterator, ProgressBar, ProgressBarIter, ProgressStyle};
 
@@ -232,4 +239,30 @@ mod test {
             v.par_iter().progress_with_style(style)
         });
     }
+
+    #[test]
+    fn it_counts_items_from_all_threads() {
+        let pb = ProgressBar::hidden();
+        let sum: u64 = (0..10_000u64)
+            .into_par_iter()
+            .map(|x| x * 2)
+            .progress_with(pb.clone())
+            .sum();
+        assert_eq!(sum, 99_990_000);
+        assert_eq!(pb.position(), 10_000);
+
+        let it = (0..10_000u64).into_par_iter().progress();
+        assert_eq!(it.progress.length(), Some(10_000));
+        assert_eq!(it.count(), 10_000);
+
+        // Filtering makes the length unknown
+        let it = (0..10_000u64)
+            .into_par_iter()
+            .filter(|x| x % 2 == 0)
+            .progress();
+        let pb = it.progress.clone();
+        assert_eq!(pb.length(), None);
+        assert_eq!(it.count(), 5_000);
+        assert_eq!(pb.position(), 5_000);
+    }
 }
@@ -0,0 +1,328 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+
+#[cfg(target_arch = "wasm32")]
+use instant::Instant;
+
+use crate::draw_target::ProgressDrawTarget;
+use crate::progress_bar::{TickTarget, Ticker};
+use crate::state::{
+    paint, print_above, ProgressFinish, ProgressState, ProgressStatus, TabExpandedString,
+    DEFAULT_TAB_WIDTH,
+};
+use crate::style::ProgressStyle;
+
+/// A spinner for tasks of unknown length
+///
+/// A `Spinner` is drawn like a [`ProgressBar`](crate::ProgressBar) without a length, but it only
+/// keeps what a spinner needs: its frame, message, prefix and start time. There's no position,
+/// length or rate estimate to maintain, so keys like `pos` and `eta` render as for a progress bar
+/// that never advanced. It's styled with the tick strings of its [`ProgressStyle`], which
+/// defaults to [`ProgressStyle::default_spinner()`].
+///
+/// ```rust,no_run
+/// # use std::time::Duration;
+/// # use indicatif::Spinner;
+/// let spinner = Spinner::new().with_message("Connecting");
+/// spinner.enable_steady_tick(Duration::from_millis(100));
+/// // ...
+/// spinner.finish_with_message("Connected");
+/// ```
+///
+/// A `Spinner` can't be added to a [`MultiProgress`](crate::MultiProgress), use
+/// [`ProgressBar::new_spinner()`](crate::ProgressBar::new_spinner) for that.
+#[derive(Clone)]
+pub struct Spinner {
+    state: Arc<Mutex<SpinnerState>>,
+    ticker: Arc<Mutex<Option<Ticker>>>,
+}
+
+impl fmt::Debug for Spinner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Spinner").finish()
+    }
+}
+
+impl Spinner {
+    /// Creates a new spinner drawing to stderr
+    pub fn new() -> Self {
+        Self::with_draw_target(ProgressDrawTarget::stderr())
+    }
+
+    /// Creates a new spinner with a given draw target
+    pub fn with_draw_target(draw_target: ProgressDrawTarget) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(SpinnerState {
+                draw_target,
+                style: ProgressStyle::default_spinner(),
+                tick: 0,
+                started: Instant::now(),
+                status: ProgressStatus::InProgress,
+                message: TabExpandedString::NoTabs("".into()),
+                prefix: TabExpandedString::NoTabs("".into()),
+            })),
+            ticker: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// A convenience builder-like function for a spinner with a given style
+    pub fn with_style(self, style: ProgressStyle) -> Self {
+        self.set_style(style);
+        self
+    }
+
+    /// A convenience builder-like function for a spinner with a given prefix
+    pub fn with_prefix(self, prefix: impl Into<Cow<'static, str>>) -> Self {
+        self.state.lock().unwrap().prefix =
+            TabExpandedString::new(prefix.into(), DEFAULT_TAB_WIDTH);
+        self
+    }
+
+    /// A convenience builder-like function for a spinner with a given message
+    pub fn with_message(self, message: impl Into<Cow<'static, str>>) -> Self {
+        self.state.lock().unwrap().message =
+            TabExpandedString::new(message.into(), DEFAULT_TAB_WIDTH);
+        self
+    }
+
+    /// Overrides the stored style
+    pub fn set_style(&self, style: ProgressStyle) {
+        self.state.lock().unwrap().style = style;
+    }
+
+    /// Sets the current prefix of the spinner and redraws it
+    pub fn set_prefix(&self, prefix: impl Into<Cow<'static, str>>) {
+        let mut state = self.state.lock().unwrap();
+        state.prefix = TabExpandedString::new(prefix.into(), DEFAULT_TAB_WIDTH);
+        state.draw(false, Instant::now());
+    }
+
+    /// Sets the current message of the spinner and redraws it
+    pub fn set_message(&self, msg: impl Into<Cow<'static, str>>) {
+        let mut state = self.state.lock().unwrap();
+        state.message = TabExpandedString::new(msg.into(), DEFAULT_TAB_WIDTH);
+        state.draw(false, Instant::now());
+    }
+
+    /// Returns the current message of the spinner
+    pub fn message(&self) -> String {
+        self.state.lock().unwrap().message.expanded().to_string()
+    }
+
+    /// Returns the current prefix of the spinner
+    pub fn prefix(&self) -> String {
+        self.state.lock().unwrap().prefix.expanded().to_string()
+    }
+
+    /// Advances the spinner to its next frame
+    ///
+    /// This doesn't have any effect while a steady tick is enabled.
+    pub fn tick(&self) {
+        if self.ticker.lock().unwrap().is_none() {
+            self.state.steady_tick();
+        }
+    }
+
+    /// Spawns a background thread to tick the spinner, see
+    /// [`ProgressBar::enable_steady_tick()`](crate::ProgressBar::enable_steady_tick)
+    pub fn enable_steady_tick(&self, interval: Duration) {
+        self.replace_ticker((!interval.is_zero()).then_some(interval));
+    }
+
+    /// Undoes [`Spinner::enable_steady_tick()`]
+    pub fn disable_steady_tick(&self) {
+        self.replace_ticker(None);
+    }
+
+    fn replace_ticker(&self, interval: Option<Duration>) {
+        let mut ticker = self.ticker.lock().unwrap();
+        if let Some(ticker) = ticker.take() {
+            ticker.stop();
+        }
+        *ticker = interval.map(|interval| Ticker::new(interval, false, &self.state));
+    }
+
+    /// Print a log line above the spinner
+    pub fn println<I: AsRef<str>>(&self, msg: I) {
+        self.state
+            .lock()
+            .unwrap()
+            .println(msg.as_ref(), Instant::now());
+    }
+
+    /// Finishes the spinner and leaves the current frame and message on the screen
+    pub fn finish(&self) {
+        self.finish_with(ProgressFinish::AndLeave);
+    }
+
+    /// Finishes the spinner and sets a message
+    pub fn finish_with_message(&self, msg: impl Into<Cow<'static, str>>) {
+        self.finish_with(ProgressFinish::WithMessage(msg.into()));
+    }
+
+    /// Finishes the spinner and completely clears it
+    pub fn finish_and_clear(&self) {
+        self.finish_with(ProgressFinish::AndClear);
+    }
+
+    /// Finishes the spinner and leaves the current frame and message on the screen
+    ///
+    /// Unlike [`finish()`](Spinner::finish), this renders with the abandoned style of the
+    /// message, see [`ProgressStyle::on_abandon_style()`].
+    pub fn abandon(&self) {
+        self.finish_with(ProgressFinish::Abandon);
+    }
+
+    /// Finishes the spinner as abandoned and sets a message
+    pub fn abandon_with_message(&self, msg: impl Into<Cow<'static, str>>) {
+        self.finish_with(ProgressFinish::AbandonWithMessage(msg.into()));
+    }
+
+    fn finish_with(&self, finish: ProgressFinish) {
+        self.state.lock().unwrap().finish(finish, Instant::now());
+        self.replace_ticker(None);
+    }
+
+    /// Indicates that the spinner finished
+    pub fn is_finished(&self) -> bool {
+        self.state.lock().unwrap().status != ProgressStatus::InProgress
+    }
+
+    /// Returns the time since the spinner was created
+    pub fn elapsed(&self) -> Duration {
+        self.state.lock().unwrap().started.elapsed()
+    }
+}
+
+impl Default for Spinner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The state of a [`Spinner`], which is rendered through a [`ProgressState`] built on demand
+struct SpinnerState {
+    draw_target: ProgressDrawTarget,
+    style: ProgressStyle,
+    tick: u64,
+    started: Instant,
+    status: ProgressStatus,
+    message: TabExpandedString,
+    prefix: TabExpandedString,
+}
+
+impl SpinnerState {
+    fn snapshot(&self, now: Instant) -> ProgressState {
+        ProgressState::spinner(
+            now,
+            self.tick,
+            self.started,
+            self.status,
+            self.message.clone(),
+            self.prefix.clone(),
+        )
+    }
+
+    fn println(&mut self, msg: &str, now: Instant) {
+        let state = self.snapshot(now);
+        if let Some(drawable) = self.draw_target.drawable(true, now) {
+            print_above(drawable, msg, &mut self.style, &state);
+        }
+    }
+
+    fn finish(&mut self, finish: ProgressFinish, now: Instant) {
+        self.status = match finish {
+            ProgressFinish::AndLeave => ProgressStatus::Finished,
+            ProgressFinish::WithMessage(msg) => {
+                self.message = TabExpandedString::new(msg, DEFAULT_TAB_WIDTH);
+                ProgressStatus::Finished
+            }
+            ProgressFinish::AndClear => ProgressStatus::Cleared,
+            ProgressFinish::Abandon => ProgressStatus::Abandoned,
+            ProgressFinish::AbandonWithMessage(msg) => {
+                self.message = TabExpandedString::new(msg, DEFAULT_TAB_WIDTH);
+                ProgressStatus::Abandoned
+            }
+        };
+        self.draw(true, now);
+    }
+
+    fn draw(&mut self, force_draw: bool, now: Instant) {
+        let force_draw = force_draw || self.status != ProgressStatus::InProgress;
+        let state = self.snapshot(now);
+        if self.draw_target.emit(&state, force_draw, now).is_some() {
+            return;
+        }
+
+        let width = self.draw_target.width();
+        if let Some(drawable) = self.draw_target.drawable(force_draw, now) {
+            let _ = paint(drawable, &mut self.style, &state, width);
+        }
+    }
+}
+
+impl TickTarget for Mutex<SpinnerState> {
+    fn steady_tick(&self) -> bool {
+        let mut state = self.lock().unwrap();
+        if state.status != ProgressStatus::InProgress {
+            return false;
+        }
+
+        state.tick = state.tick.saturating_add(1);
+        state.draw(false, Instant::now());
+        true
+    }
+}
+
+impl Drop for SpinnerState {
+    fn drop(&mut self) {
+        if self.status == ProgressStatus::InProgress {
+            self.finish(ProgressFinish::AndLeave, Instant::now());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+    use crate::progress_bar::TICKER_TEST;
+
+    #[test]
+    fn steady_tick_advances_frames() {
+        let _guard = TICKER_TEST.lock().unwrap();
+
+        let (target, buffer) = ProgressDrawTarget::string_buffer();
+        let spinner = Spinner::with_draw_target(target)
+            .with_style(
+                ProgressStyle::with_template("{spinner} {msg}")
+                    .unwrap()
+                    .tick_chars("abc."),
+            )
+            .with_message("working");
+
+        let mut frames = Vec::new();
+        spinner.enable_steady_tick(Duration::from_millis(5));
+        for _ in 0..20 {
+            thread::sleep(Duration::from_millis(10));
+            let frame = buffer.lock().unwrap().clone();
+            if frames.last() != Some(&frame) {
+                frames.push(frame);
+            }
+        }
+        assert!(frames.len() >= 3, "{frames:?}");
+        assert!(frames
+            .iter()
+            .all(|frame| ["a working", "b working", "c working"].contains(&frame.as_str())));
+
+        spinner.finish_with_message("done");
+        assert!(spinner.is_finished());
+        assert!(spinner.ticker.lock().unwrap().is_none());
+        assert_eq!(*buffer.lock().unwrap(), ". done");
+    }
+}
@@ -1,23 +1,49 @@
 use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
 use std::io;
-use std::sync::Arc;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, MutexGuard, TryLockError, TryLockResult, Weak};
 use std::time::Duration;
 #[cfg(not(target_arch = "wasm32"))]
 use std::time::Instant;
 
+use console::{measure_text_width, Style};
 #[cfg(target_arch = "wasm32")]
 use instant::Instant;
 use portable_atomic::{AtomicU64, AtomicU8, Ordering};
 
-use crate::draw_target::ProgressDrawTarget;
+use crate::clock::{Clock, SystemClock};
+use crate::draw_target::{visual_line_count, Drawable, ProgressDrawTarget};
 use crate::style::ProgressStyle;
 
 pub(crate) struct BarState {
     pub(crate) draw_target: ProgressDrawTarget,
     pub(crate) on_finish: ProgressFinish,
     pub(crate) style: ProgressStyle,
+    /// Whether `style` was set explicitly rather than being a default
+    style_set: bool,
     pub(crate) state: ProgressState,
     pub(crate) tab_width: usize,
+    /// Minimum time between two non-forced draws
+    pub(crate) draw_rate_limit: Duration,
+    /// How long the progress bar has to run before it's first painted
+    pub(crate) min_display_duration: Duration,
+    last_draw: Option<Instant>,
+    /// The number of terminal rows taken up by the last render
+    pub(crate) line_count: usize,
+    /// The callback set with `ProgressBar::on_update()`
+    pub(crate) on_update: Option<Arc<UpdateHook>>,
+    /// Whether there are updates to deliver once the lock is released, see `BarStateGuard`
+    update_pending: bool,
+    /// Runs once the progress bar is finished, see `ProgressBar::with_finish_callback()`
+    pub(crate) finish_callback: Option<FinishCallback>,
+    /// Whether `set_length` and `inc_length` discard the rate estimate
+    pub(crate) reset_eta_on_length_change: bool,
+    /// The aggregate progress bar this bar contributes to, see `ProgressBar::sum_of()`
+    pub(crate) aggregate: Option<AggregateLink>,
+    /// The last reported `(pos, len)` of each child, if this is an aggregate progress bar
+    pub(crate) aggregate_children: Vec<(u64, u64)>,
 }
 
 impl BarState {
@@ -30,56 +56,79 @@ impl BarState {
             draw_target,
             on_finish: ProgressFinish::default(),
             style: ProgressStyle::default_bar(),
+            style_set: false,
             state: ProgressState::new(len, pos),
             tab_width: DEFAULT_TAB_WIDTH,
+            draw_rate_limit: Duration::ZERO,
+            min_display_duration: Duration::ZERO,
+            last_draw: None,
+            line_count: 0,
+            on_update: None,
+            update_pending: false,
+            finish_callback: None,
+            reset_eta_on_length_change: false,
+            aggregate: None,
+            aggregate_children: Vec::new(),
         }
     }
 
     /// Finishes the progress bar using the [`ProgressFinish`] behavior stored
     /// in the [`ProgressStyle`].
     pub(crate) fn finish_using_style(&mut self, now: Instant, finish: ProgressFinish) {
-        self.state.status = Status::DoneVisible;
         match finish {
             ProgressFinish::AndLeave => {
-                if let Some(len) = self.state.len {
-                    self.state.pos.set(len);
-                }
+                self.state.complete();
+                self.state.status = ProgressStatus::Finished;
             }
             ProgressFinish::WithMessage(msg) => {
-                if let Some(len) = self.state.len {
-                    self.state.pos.set(len);
-                }
+                self.state.complete();
                 self.state.message = TabExpandedString::new(msg, self.tab_width);
+                self.state.status = ProgressStatus::Finished;
             }
             ProgressFinish::AndClear => {
-                if let Some(len) = self.state.len {
-                    self.state.pos.set(len);
-                }
-                self.state.status = Status::DoneHidden;
+                self.state.complete();
+                self.state.status = ProgressStatus::Cleared;
+            }
+            ProgressFinish::Abandon => {
+                self.state.status = ProgressStatus::Abandoned;
             }
-            ProgressFinish::Abandon => {}
             ProgressFinish::AbandonWithMessage(msg) => {
                 self.state.message = TabExpandedString::new(msg, self.tab_width);
+                self.state.status = ProgressStatus::Abandoned;
             }
         }
 
         // There's no need to update the estimate here; once the `status` is no longer
         // `InProgress`, we will use the length and elapsed time to estimate.
         let _ = self.draw(true, now);
+        if let Some(callback) = self.finish_callback.take() {
+            callback(&self.state);
+        }
+        self.notify_update();
     }
 
     pub(crate) fn reset(&mut self, now: Instant, mode: Reset) {
         // Always reset the estimator; this is the only reset that will occur if mode is
         // `Reset::Eta`.
         self.state.est.reset(now);
+        self.state.smoothed_eta.store(u64::MAX, Ordering::Relaxed);
 
         if let Reset::Elapsed | Reset::All = mode {
             self.state.started = now;
         }
 
+        // A paused bar stays paused, but counts from the reset onwards
+        if self.state.paused_at.is_some() {
+            self.state.paused_at = Some(now);
+        }
+
         if let Reset::All = mode {
             self.state.pos.reset(now);
-            self.state.status = Status::InProgress;
+            if let Some(start) = self.state.countdown_start {
+                self.state.pos.set(start);
+            }
+            self.state.last_progress = (self.state.pos(), now);
+            self.state.status = ProgressStatus::InProgress;
 
             for tracker in self.style.format_map.values_mut() {
                 tracker.reset(&self.state, now);
@@ -97,17 +146,81 @@ impl BarState {
     }
 
     pub(crate) fn set_length(&mut self, now: Instant, len: u64) {
-        self.state.len = Some(len);
+        self.length_changed(now, Some(len));
+        if !self.state.allow_overshoot && self.state.pos() > len {
+            self.state.set_pos(len);
+        }
+        self.update_estimate_and_draw(now);
+    }
+
+    pub(crate) fn set_pos_and_len(&mut self, now: Instant, pos: u64, len: u64) {
+        self.length_changed(now, Some(len));
+        match self.state.allow_overshoot {
+            true => self.state.set_pos(pos),
+            false => self.state.set_pos(pos.min(len)),
+        }
         self.update_estimate_and_draw(now);
     }
 
     pub(crate) fn inc_length(&mut self, now: Instant, delta: u64) {
         if let Some(len) = self.state.len {
-            self.state.len = Some(len.saturating_add(delta));
+            self.length_changed(now, Some(len.saturating_add(delta)));
         }
         self.update_estimate_and_draw(now);
     }
 
+    fn length_changed(&mut self, now: Instant, len: Option<u64>) {
+        if self.reset_eta_on_length_change && len != self.state.len {
+            self.state.est.reset(now);
+        }
+        self.state.len = len;
+    }
+
+    /// The current time according to the progress bar's clock
+    pub(crate) fn now(&self) -> Instant {
+        self.state.clock.now()
+    }
+
+    pub(crate) fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        let now = clock.now();
+        self.state.clock = clock;
+        self.state.started = now;
+        self.state.last_progress.1 = now;
+        self.state.est.reset(now);
+        if self.state.paused_at.is_some() {
+            self.state.paused_at = Some(now);
+        }
+        self.last_draw = None;
+    }
+
+    pub(crate) fn pause(&mut self, now: Instant) {
+        if self.state.paused_at.is_none() {
+            self.state.paused_at = Some(now);
+        }
+    }
+
+    pub(crate) fn resume(&mut self, now: Instant) {
+        if let Some(paused_at) = self.state.paused_at.take() {
+            let paused = now.saturating_duration_since(paused_at);
+            self.state.started += paused;
+            self.state.last_progress.1 += paused;
+            self.state.est.shift(paused);
+            self.update_estimate_and_draw(now);
+        }
+    }
+
+    pub(crate) fn resume_from(&mut self, now: Instant, pos: u64, elapsed: Duration) {
+        let started = backdate(now, elapsed);
+        self.state.pos.set(pos);
+        self.state.started = started;
+        self.state.last_progress = (pos, now);
+        self.state.est.restart(self.state.steps_done(), now);
+        if self.state.paused_at.is_some() {
+            self.state.paused_at = Some(now);
+        }
+        let _ = self.draw(false, now);
+    }
+
     pub(crate) fn set_tab_width(&mut self, tab_width: usize) {
         self.tab_width = tab_width;
         self.state.message.set_tab_width(tab_width);
@@ -116,52 +229,138 @@ impl BarState {
     }
 
     pub(crate) fn set_style(&mut self, style: ProgressStyle) {
+        self.use_style(style);
+        self.style_set = true;
+    }
+
+    /// Replaces the style, unless one was set explicitly
+    pub(crate) fn set_default_style(&mut self, style: ProgressStyle) {
+        if !self.style_set {
+            self.use_style(style);
+        }
+    }
+
+    fn use_style(&mut self, style: ProgressStyle) {
         self.style = style;
         self.style.set_tab_width(self.tab_width);
     }
 
     pub(crate) fn tick(&mut self, now: Instant) {
-        self.state.tick = self.state.tick.saturating_add(1);
+        self.advance_frame();
         self.update_estimate_and_draw(now);
     }
 
+    /// Move on to the next spinner frame without repainting
+    pub(crate) fn advance_frame(&mut self) {
+        self.state.tick = self.state.tick.saturating_add(1);
+    }
+
     pub(crate) fn update_estimate_and_draw(&mut self, now: Instant) {
-        let pos = self.state.pos.pos.load(Ordering::Relaxed);
-        self.state.est.record(pos, now);
+        // Progress made while paused is attributed to the time after resuming
+        if self.state.paused_at.is_none() && self.state.estimate {
+            self.state.est.record(self.state.steps_done(), now);
+        }
+
+        let pos = self.state.pos();
+        if pos != self.state.last_progress.0 {
+            self.state.last_progress = (pos, now);
+        }
 
         for tracker in self.style.format_map.values_mut() {
             tracker.tick(&self.state, now);
         }
 
         let _ = self.draw(false, now);
+        self.notify_update();
     }
 
-    pub(crate) fn println(&mut self, now: Instant, msg: &str) {
-        let width = self.draw_target.width();
-        let mut drawable = match self.draw_target.drawable(true, now) {
-            Some(drawable) => drawable,
-            None => return,
-        };
-
-        let mut draw_state = drawable.state();
-        let lines: Vec<String> = msg.lines().map(Into::into).collect();
-        // Empty msg should trigger newline as we are in println
-        if lines.is_empty() {
-            draw_state.lines.push(String::new());
-        } else {
-            draw_state.lines.extend(lines);
+    /// Queues the new state for the [`ProgressBar::on_update()`](crate::ProgressBar::on_update)
+    /// callback, if any, and reports it to the aggregate progress bar this bar contributes to
+    ///
+    /// This is called with the bar's lock held, the callbacks only run once it's released, see
+    /// `BarStateGuard`. The aggregate's lock is always taken after the lock of its child, never
+    /// the other way around.
+    fn notify_update(&mut self) {
+        if let Some(on_update) = &self.on_update {
+            on_update.queue(self.state.detach());
+            self.update_pending = true;
         }
 
-        draw_state.orphan_lines_count = draw_state.lines.len();
-        if let Some(width) = width {
-            if !matches!(self.state.status, Status::DoneHidden) {
-                self.style
-                    .format_state(&self.state, &mut draw_state.lines, width);
+        self.report_to_aggregate();
+    }
+
+    /// Makes this progress bar contribute to the aggregate progress bar of `link`
+    pub(crate) fn set_aggregate(&mut self, link: AggregateLink) {
+        self.aggregate = Some(link);
+        self.report_to_aggregate();
+    }
+
+    fn report_to_aggregate(&mut self) {
+        if let Some(link) = &self.aggregate {
+            if let Some(parent) = link.parent.upgrade() {
+                let contribution = self.state.contribution();
+                parent.lock().unwrap().update_child(link.idx, contribution);
+                self.update_pending = true;
             }
         }
+    }
+
+    /// Takes what's needed to deliver the pending updates once the lock is released
+    fn take_pending_updates(&mut self) -> Option<PendingUpdates> {
+        match mem::take(&mut self.update_pending) {
+            true => Some(PendingUpdates {
+                on_update: self.on_update.clone(),
+                parent: self.aggregate.as_ref().map(|link| link.parent.clone()),
+            }),
+            false => None,
+        }
+    }
+
+    /// Record the `(pos, len)` contribution of the child at `idx` of this aggregate progress bar
+    pub(crate) fn update_child(&mut self, idx: usize, contribution: (u64, u64)) {
+        self.aggregate_children[idx] = contribution;
+        self.update_aggregate();
+    }
+
+    pub(crate) fn set_aggregate_children(&mut self, children: Vec<(u64, u64)>) {
+        self.aggregate_children = children;
+        self.update_aggregate();
+    }
+
+    fn update_aggregate(&mut self) {
+        if self.state.is_finished() {
+            return;
+        }
+
+        let (pos, len) = self.aggregate_children.iter().fold(
+            (0u64, 0u64),
+            |(pos, len), (child_pos, child_len)| {
+                (
+                    pos.saturating_add(*child_pos),
+                    len.saturating_add(*child_len),
+                )
+            },
+        );
+        self.state.len = Some(len);
+        self.state.pos.set(pos);
+        let now = self.now();
+        self.update_estimate_and_draw(now);
+    }
+
+    pub(crate) fn println(&mut self, now: Instant, msg: &str) {
+        if let Some(drawable) = self.draw_target.drawable(true, now) {
+            print_above(drawable, msg, &mut self.style, &self.state);
+        }
+    }
 
-        drop(draw_state);
-        let _ = drawable.draw();
+    /// Returns `false` without printing if the draw target is in use by another thread
+    pub(crate) fn try_println(&mut self, now: Instant, msg: &str) -> bool {
+        match self.draw_target.try_drawable(true, now) {
+            Some(Some(drawable)) => print_above(drawable, msg, &mut self.style, &self.state),
+            Some(None) => {}
+            None => return false,
+        }
+        true
     }
 
     pub(crate) fn suspend<F: FnOnce() -> R, R>(&mut self, now: Instant, f: F) -> R {
@@ -174,32 +373,166 @@ impl BarState {
         }
 
         let ret = f();
-        let _ = self.draw(true, Instant::now());
+        let now = self.now();
+        let _ = self.draw(true, now);
         ret
     }
 
+    pub(crate) fn try_suspend<F: FnOnce() -> R, R>(&mut self, now: Instant, f: F) -> Option<R> {
+        match self.draw_target.remote() {
+            Some((state, _)) => Some(uncontended(state.try_write())?.suspend(f, now)),
+            None => Some(self.suspend(now, f)),
+        }
+    }
+
+    /// Whether a draw at `now` that isn't forced would paint, going by the same checks as `draw()`
+    ///
+    /// The `AtomicPosition` throttle of `inc()` and friends happens before and isn't included.
+    pub(crate) fn should_draw(&self, now: Instant) -> bool {
+        if self.last_draw.is_none() && !self.min_display_duration.is_zero() {
+            let elapsed = now.saturating_duration_since(self.state.started);
+            match self.state.status {
+                ProgressStatus::Cleared => return false,
+                ProgressStatus::InProgress if elapsed < self.min_display_duration => return false,
+                _ => {}
+            }
+        }
+
+        // Finished progress bars are always drawn, see `draw()`
+        if self.state.is_finished() {
+            return !self.draw_target.is_hidden();
+        }
+
+        if let Some(last_draw) = self.last_draw {
+            if now.saturating_duration_since(last_draw) < self.draw_rate_limit {
+                return false;
+            }
+        }
+
+        self.draw_target.would_draw(now)
+    }
+
     pub(crate) fn draw(&mut self, mut force_draw: bool, now: Instant) -> io::Result<()> {
         let width = self.draw_target.width();
 
         // `|= self.is_finished()` should not be needed here, but we used to always draw for
         // finished progress bars, so it's kept as to not cause compatibility issues in weird cases.
         force_draw |= self.state.is_finished();
-        let mut drawable = match self.draw_target.drawable(force_draw, now) {
+
+        // Wait with the first paint until the progress bar ran for the minimum display duration,
+        // unless it finished by then. If it was cleared, there's nothing to paint at all.
+        if self.last_draw.is_none() && !self.min_display_duration.is_zero() {
+            let elapsed = now.saturating_duration_since(self.state.started);
+            match self.state.status {
+                ProgressStatus::Cleared => return Ok(()),
+                ProgressStatus::InProgress if elapsed < self.min_display_duration => return Ok(()),
+                _ => {}
+            }
+        }
+
+        if !force_draw {
+            if let Some(last_draw) = self.last_draw {
+                if now.saturating_duration_since(last_draw) < self.draw_rate_limit {
+                    return Ok(());
+                }
+            }
+        }
+
+        if let Some(result) = self.draw_target.emit(&self.state, force_draw, now) {
+            self.last_draw = Some(now);
+            return result;
+        }
+
+        let drawable = match self.draw_target.drawable(force_draw, now) {
             Some(drawable) => drawable,
             None => return Ok(()),
         };
 
-        let mut draw_state = drawable.state();
+        self.last_draw = Some(now);
+        let (line_count, result) = paint(drawable, &mut self.style, &self.state, width);
+        self.line_count = line_count;
+        result
+    }
+}
 
-        if let Some(width) = width {
-            if !matches!(self.state.status, Status::DoneHidden) {
-                self.style
-                    .format_state(&self.state, &mut draw_state.lines, width);
-            }
+/// Paints `state` to `drawable`, returning the number of terminal rows it takes up
+pub(crate) fn paint(
+    mut drawable: Drawable<'_>,
+    style: &mut ProgressStyle,
+    state: &ProgressState,
+    width: Option<u16>,
+) -> (usize, io::Result<()>) {
+    let prefix_width = drawable.align_prefix(measure_text_width(state.prefix.expanded()));
+    let ascii = drawable.ascii_only();
+    let mut draw_state = drawable.state();
+
+    let start = draw_state.lines.len();
+    let mut line_count = 0;
+    if let Some(width) = width {
+        if state.is_shown() {
+            let lines = &mut draw_state.lines;
+            with_display_style(style, ascii, |style| {
+                style.format_state_aligned(state, lines, width, prefix_width)
+            });
+            line_count = visual_line_count(&draw_state.lines[start..], width as usize).as_usize();
+        }
+    }
+
+    drop(draw_state);
+    (line_count, drawable.draw())
+}
+
+/// Renders with `style`, using ASCII glyphs if the draw target can't display Unicode
+fn with_display_style<R>(
+    style: &mut ProgressStyle,
+    ascii: bool,
+    f: impl FnOnce(&ProgressStyle) -> R,
+) -> R {
+    match ascii && !style.is_ascii() {
+        true => style.with_ascii_glyphs(f),
+        false => f(style),
+    }
+}
+
+/// Prints `msg` above the progress bar, which is drawn again below it
+pub(crate) fn print_above(
+    mut drawable: Drawable<'_>,
+    msg: &str,
+    style: &mut ProgressStyle,
+    state: &ProgressState,
+) {
+    let ascii = drawable.ascii_only();
+    let width = drawable.width();
+    let prefix_width = drawable.align_prefix(measure_text_width(state.prefix.expanded()));
+    let mut draw_state = drawable.state();
+    let lines: Vec<String> = msg.lines().map(Into::into).collect();
+    // Empty msg should trigger newline as we are in println
+    if lines.is_empty() {
+        draw_state.lines.push(String::new());
+    } else {
+        draw_state.lines.extend(lines);
+    }
+
+    draw_state.orphan_lines_count = draw_state.lines.len();
+    if let Some(width) = width {
+        if state.is_shown() {
+            let lines = &mut draw_state.lines;
+            with_display_style(style, ascii, |style| {
+                style.format_state_aligned(state, lines, width, prefix_width)
+            });
         }
+    }
+
+    drop(draw_state);
+    let _ = drawable.draw();
+}
 
-        drop(draw_state);
-        drawable.draw()
+/// Returns `None` if the lock is held by another thread
+pub(crate) fn uncontended<G>(result: TryLockResult<G>) -> Option<G> {
+    match result {
+        Ok(guard) => Some(guard),
+        Err(TryLockError::WouldBlock) => None,
+        Err(err @ TryLockError::Poisoned(_)) => panic!("{err}"),
     }
 }
 
@@ -212,13 +545,144 @@ impl Drop for BarState {
             return;
         }
 
-        self.finish_using_style(Instant::now(), self.on_finish.clone());
+        self.finish_using_style(self.now(), self.on_finish.clone());
 
         // Notify the `MultiProgress` that we're now a zombie.
         self.draw_target.mark_zombie();
+
+        // Nobody holds the lock of a progress bar that's being dropped
+        if let Some(pending) = self.take_pending_updates() {
+            pending.deliver();
+        }
+    }
+}
+
+/// The locked state of a progress bar, which delivers pending updates once it's unlocked
+///
+/// Updates are queued while the lock is held, so the `on_update` callback can run without it
+/// and may use the progress bar itself.
+pub(crate) struct BarStateGuard<'a>(Option<MutexGuard<'a, BarState>>);
+
+impl<'a> BarStateGuard<'a> {
+    pub(crate) fn lock(state: &'a Mutex<BarState>) -> Self {
+        Self::new(state.lock().unwrap())
+    }
+
+    pub(crate) fn new(guard: MutexGuard<'a, BarState>) -> Self {
+        Self(Some(guard))
+    }
+}
+
+impl Deref for BarStateGuard<'_> {
+    type Target = BarState;
+
+    fn deref(&self) -> &BarState {
+        self.0.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for BarStateGuard<'_> {
+    fn deref_mut(&mut self) -> &mut BarState {
+        self.0.as_mut().unwrap()
+    }
+}
+
+impl Drop for BarStateGuard<'_> {
+    fn drop(&mut self) {
+        let pending = self
+            .0
+            .as_mut()
+            .and_then(|state| state.take_pending_updates());
+        drop(self.0.take());
+        if let Some(pending) = pending {
+            pending.deliver();
+        }
+    }
+}
+
+/// Updates to deliver after releasing the lock of a progress bar
+struct PendingUpdates {
+    on_update: Option<Arc<UpdateHook>>,
+    /// The aggregate progress bar, which may have updates of its own
+    parent: Option<Weak<Mutex<BarState>>>,
+}
+
+impl PendingUpdates {
+    fn deliver(self) {
+        if let Some(on_update) = self.on_update {
+            on_update.deliver();
+        }
+
+        // Releasing the aggregate's lock delivers its updates, there's nothing to do if it was
+        // poisoned
+        if let Some(parent) = self.parent.and_then(|parent| parent.upgrade()) {
+            if let Ok(guard) = parent.lock() {
+                drop(BarStateGuard::new(guard));
+            }
+        }
+    }
+}
+
+/// Runs the [`ProgressBar::on_update()`](crate::ProgressBar::on_update) callback
+///
+/// States are queued while the progress bar is locked, so they're queued in order, and passed to
+/// the callback after the lock is released. Only the latest state is kept until the callback
+/// gets to it. The callback runs on one thread at a time, which also delivers the states queued
+/// while it was running, including those queued by the callback itself.
+pub(crate) struct UpdateHook {
+    callback: Mutex<UpdateCallback>,
+    /// The latest state the callback hasn't seen yet
+    pending: Mutex<Option<ProgressState>>,
+}
+
+impl UpdateHook {
+    pub(crate) fn new(callback: UpdateCallback) -> Self {
+        Self {
+            callback: Mutex::new(callback),
+            pending: Mutex::new(None),
+        }
+    }
+
+    fn queue(&self, state: ProgressState) {
+        *self.pending.lock().unwrap() = Some(state);
+    }
+
+    fn deliver(&self) {
+        loop {
+            // If the callback is running (or panicked before), leave the state to that thread
+            let callback = match self.callback.try_lock() {
+                Ok(callback) => callback,
+                Err(_) => return,
+            };
+
+            loop {
+                let state = self.pending.lock().unwrap().take();
+                match state {
+                    Some(state) => callback(&state),
+                    None => break,
+                }
+            }
+
+            // Another thread may have queued a state and given up on delivering it after the
+            // last check, but before the callback was unlocked
+            drop(callback);
+            if self.pending.lock().unwrap().is_none() {
+                return;
+            }
+        }
     }
 }
 
+/// Link from a progress bar to the aggregate progress bar it contributes to
+pub(crate) struct AggregateLink {
+    pub(crate) parent: Weak<Mutex<BarState>>,
+    pub(crate) idx: usize,
+}
+
+pub(crate) type UpdateCallback = Box<dyn Fn(&ProgressState) + Send>;
+
+pub(crate) type FinishCallback = Box<dyn FnOnce(&ProgressState) + Send>;
+
 pub(crate) enum Reset {
     Eta,
     Elapsed,
@@ -232,10 +696,28 @@ pub struct ProgressState {
     len: Option<u64>,
     pub(crate) tick: u64,
     pub(crate) started: Instant,
-    status: Status,
+    paused_at: Option<Instant>,
+    /// The last position seen when updating the progress bar, and when it was first seen
+    last_progress: (u64, Instant),
+    clock: Arc<dyn Clock>,
+    status: ProgressStatus,
     est: Estimator,
+    /// The ETA last shown by `eta_smooth` in milliseconds, or `u64::MAX` if there is none
+    smoothed_eta: AtomicU64,
     pub(crate) message: TabExpandedString,
     pub(crate) prefix: TabExpandedString,
+    pub(crate) metrics: HashMap<String, f64>,
+    /// The name, count and style of each segment of a stacked bar
+    pub(crate) segments: Vec<(String, u64, Style)>,
+    pub(crate) visible: bool,
+    /// Whether `percent` may exceed 100 and `set_length` keeps a position past the new length
+    pub(crate) allow_overshoot: bool,
+    /// A rate set with `ProgressBar::set_fixed_rate()`, used instead of the estimator's
+    pub(crate) fixed_rate: Option<f64>,
+    /// Whether updates are recorded in the estimator, see `ProgressBar::without_eta()`
+    pub(crate) estimate: bool,
+    /// The starting position of a progress bar that counts down to zero
+    pub(crate) countdown_start: Option<u64>,
 }
 
 impl ProgressState {
@@ -245,57 +727,216 @@ impl ProgressState {
             pos,
             len,
             tick: 0,
-            status: Status::InProgress,
+            status: ProgressStatus::InProgress,
             started: now,
+            paused_at: None,
+            last_progress: (0, now),
+            clock: Arc::new(SystemClock),
             est: Estimator::new(now),
+            smoothed_eta: AtomicU64::new(u64::MAX),
             message: TabExpandedString::NoTabs("".into()),
             prefix: TabExpandedString::NoTabs("".into()),
+            metrics: HashMap::new(),
+            segments: Vec::new(),
+            visible: true,
+            allow_overshoot: false,
+            fixed_rate: None,
+            estimate: true,
+            countdown_start: None,
+        }
+    }
+
+    /// The `(pos, len)` counted by an aggregate progress bar, see `ProgressBar::sum_of()`
+    pub(crate) fn contribution(&self) -> (u64, u64) {
+        let pos = self.pos();
+        (pos, self.len.unwrap_or(pos))
+    }
+
+    /// A copy of the state as it is now, which doesn't follow the progress bar anymore
+    fn detach(&self) -> Self {
+        let now = self.now();
+        let pos = AtomicPosition::new();
+        pos.set(self.pos());
+        Self {
+            pos: Arc::new(pos),
+            len: self.len,
+            tick: self.tick,
+            started: self.started,
+            paused_at: self.paused_at,
+            last_progress: self.last_progress,
+            clock: Arc::new(move || now),
+            status: self.status,
+            est: self.est.clone(),
+            smoothed_eta: AtomicU64::new(self.smoothed_eta.load(Ordering::Relaxed)),
+            message: self.message.clone(),
+            prefix: self.prefix.clone(),
+            metrics: self.metrics.clone(),
+            segments: self.segments.clone(),
+            visible: self.visible,
+            allow_overshoot: self.allow_overshoot,
+            fixed_rate: self.fixed_rate,
+            estimate: self.estimate,
+            countdown_start: self.countdown_start,
+        }
+    }
+
+    /// The state of a [`Spinner`](crate::Spinner) as of `now`, which is built for each render
+    ///
+    /// It has no length, and as nothing is recorded, a zero position and rate.
+    pub(crate) fn spinner(
+        now: Instant,
+        tick: u64,
+        started: Instant,
+        status: ProgressStatus,
+        message: TabExpandedString,
+        prefix: TabExpandedString,
+    ) -> Self {
+        Self {
+            tick,
+            started,
+            clock: Arc::new(move || now),
+            status,
+            estimate: false,
+            message,
+            prefix,
+            ..Self::new(None, Arc::new(AtomicPosition::new()))
+        }
+    }
+
+    /// The value of a metric set with [`ProgressBar::set_metric()`](crate::ProgressBar::set_metric)
+    pub fn metric(&self, name: &str) -> Option<f64> {
+        self.metrics.get(name).copied()
+    }
+
+    /// The count of a segment of a stacked bar, see
+    /// [`ProgressBar::set_segment()`](crate::ProgressBar::set_segment)
+    pub fn segment(&self, name: &str) -> Option<u64> {
+        self.segments
+            .iter()
+            .find(|(segment, ..)| segment == name)
+            .map(|&(_, count, _)| count)
+    }
+
+    pub(crate) fn set_segment(&mut self, name: &str, count: u64) {
+        match self
+            .segments
+            .iter_mut()
+            .find(|(segment, ..)| segment == name)
+        {
+            Some(segment) => segment.1 = count,
+            None => self.segments.push((name.to_owned(), count, Style::new())),
         }
     }
 
+    /// Whether the progress bar should be drawn, i.e. it's visible and wasn't cleared
+    pub(crate) fn is_shown(&self) -> bool {
+        self.visible && !matches!(self.status, ProgressStatus::Cleared)
+    }
+
     /// Indicates that the progress bar finished.
     pub fn is_finished(&self) -> bool {
-        match self.status {
-            Status::InProgress => false,
-            Status::DoneVisible => true,
-            Status::DoneHidden => true,
-        }
+        !matches!(self.status, ProgressStatus::InProgress)
+    }
+
+    /// How the progress bar was finished, if at all
+    pub fn status(&self) -> ProgressStatus {
+        self.status
     }
 
     /// Returns the completion as a floating-point number between 0 and 1
+    ///
+    /// A position past the length counts as complete. For a progress bar that counts down, this
+    /// is the part of the length that has been counted down.
+    ///
+    /// This is [`fraction_f64()`](Self::fraction_f64) rounded to an `f32`, which can't tell apart
+    /// positions that are close to each other relative to a very large length.
     pub fn fraction(&self) -> f32 {
-        let pos = self.pos.pos.load(Ordering::Relaxed);
+        self.fraction_f64() as f32
+    }
+
+    /// Returns the completion as a double-precision floating-point number between 0 and 1
+    ///
+    /// See [`fraction()`](Self::fraction). The bar is drawn using this value, so that it keeps
+    /// advancing for lengths in the billions.
+    pub fn fraction_f64(&self) -> f64 {
+        let mut pos = self.pos.pos.load(Ordering::Relaxed);
+        if let (Some(_), Some(len)) = (self.countdown_start, self.len) {
+            pos = len.saturating_sub(pos);
+        }
         let pct = match (pos, self.len) {
             (_, None) => 0.0,
             (_, Some(0)) => 1.0,
             (0, _) => 0.0,
-            (pos, Some(len)) => pos as f32 / len as f32,
+            (pos, Some(len)) => pos as f64 / len as f64,
         };
         pct.clamp(0.0, 1.0)
     }
 
+    /// The completion in percent as shown by the `percent` keys
+    ///
+    /// Like [`fraction()`](Self::fraction), but exceeds 100 for a position past the length if
+    /// overshoot is allowed.
+    pub(crate) fn percent(&self) -> f32 {
+        match (self.allow_overshoot, self.len) {
+            (true, Some(len)) if len > 0 && self.pos() > len && self.countdown_start.is_none() => {
+                self.pos() as f32 / len as f32 * 100.0
+            }
+            _ => self.fraction() * 100.0,
+        }
+    }
+
     /// The expected ETA
+    ///
+    /// This is zero once the position reaches the length, or once it reaches zero for a progress
+    /// bar that [counts down](crate::ProgressBar::new_countdown).
+    ///
+    /// This is also zero when the ETA is unknown, see [`ProgressState::try_eta()`] to tell the
+    /// two apart.
     pub fn eta(&self) -> Duration {
-        if self.is_finished() {
-            return Duration::new(0, 0);
-        }
+        self.try_eta().unwrap_or_default()
+    }
 
-        let len = match self.len {
-            Some(len) => len,
-            None => return Duration::new(0, 0),
+    /// The ETA shown by the `eta_smooth` key
+    ///
+    /// This is [`eta()`](Self::eta) rounded to a granularity depending on its magnitude. Small
+    /// increases (up to one step of that granularity) since the ETA was last shown are ignored,
+    /// so that estimator noise doesn't make it flicker back and forth.
+    pub(crate) fn smoothed_eta(&self) -> Duration {
+        let shown = match self.smoothed_eta.load(Ordering::Relaxed) {
+            u64::MAX => None,
+            millis => Some(Duration::from_millis(millis)),
         };
+        let eta = smooth_eta(shown, self.eta());
+        self.smoothed_eta
+            .store(eta.as_millis() as u64, Ordering::Relaxed);
+        eta
+    }
 
-        let pos = self.pos.pos.load(Ordering::Relaxed);
+    /// The expected ETA, or `None` if it's unknown
+    ///
+    /// The ETA is unknown for a progress bar without a length (unless it counts down to zero),
+    /// and until there's a rate of progress to estimate it from. Once the progress bar is
+    /// finished, this is `Some` zero.
+    pub fn try_eta(&self) -> Option<Duration> {
+        if self.is_finished() {
+            return Some(Duration::new(0, 0));
+        }
 
-        let sps = self.est.steps_per_second(Instant::now());
+        let pos = self.pos.pos.load(Ordering::Relaxed);
+        let remaining = match (self.countdown_start, self.len) {
+            (Some(_), _) => pos,
+            (None, Some(len)) => len.saturating_sub(pos),
+            (None, None) => return None,
+        };
 
-        // Infinite duration should only ever happen at the beginning, so in this case it's okay to
-        // just show an ETA of 0 until progress starts to occur.
-        if sps == 0.0 {
-            return Duration::new(0, 0);
+        // A rate of zero (or one that can't be computed) should only ever happen at the
+        // beginning, before progress starts to occur
+        let sps = self.steps_per_second();
+        if sps == 0.0 || !sps.is_finite() {
+            return None;
         }
 
-        secs_to_duration(len.saturating_sub(pos) as f64 / sps)
+        Some(secs_to_duration(remaining as f64 / sps))
     }
 
     /// The expected total duration (that is, elapsed time + expected ETA)
@@ -303,20 +944,106 @@ impl ProgressState {
         if self.len.is_none() || self.is_finished() {
             return Duration::new(0, 0);
         }
-        self.started.elapsed().saturating_add(self.eta())
+        self.elapsed().saturating_add(self.eta())
     }
 
     /// The number of steps per second
     pub fn per_sec(&self) -> f64 {
-        if let Status::InProgress = self.status {
-            self.est.steps_per_second(Instant::now())
+        if let ProgressStatus::InProgress = self.status {
+            self.steps_per_second()
+        } else if self.estimate && !self.elapsed().is_zero() {
+            self.steps_done() as f64 / self.elapsed().as_secs_f64()
         } else {
-            self.pos() as f64 / self.started.elapsed().as_secs_f64()
+            0.0
         }
     }
 
+    /// The number of steps made so far, which counts down from the start for a countdown
+    fn steps_done(&self) -> u64 {
+        match self.countdown_start {
+            Some(start) => start.saturating_sub(self.pos()),
+            None => self.pos(),
+        }
+    }
+
+    /// Moves the position to where the progress bar is complete, if that's known
+    fn complete(&mut self) {
+        match (self.countdown_start, self.len) {
+            (Some(_), _) => self.pos.set(0),
+            (None, Some(len)) => self.pos.set(len),
+            (None, None) => {}
+        }
+    }
+
+    /// The fixed rate if there is one, or the estimated rate otherwise
+    fn steps_per_second(&self) -> f64 {
+        match self.fixed_rate {
+            Some(rate) => rate,
+            None if !self.estimate => 0.0,
+            None => self.est.steps_per_second(self.now()),
+        }
+    }
+
+    /// The rates of the most recent updates in steps per second, oldest first
+    ///
+    /// Holds the rate between each of the last 16 updates that advanced the position and the
+    /// update before it, or fewer until there have been that many. Like the ETA estimate, the
+    /// history starts over when the position moves backwards or the estimate is reset.
+    pub fn rate_history(&self) -> Vec<f64> {
+        self.est.history.iter().copied().collect()
+    }
+
+    /// The raw number of steps per second reported by the rate estimator
+    ///
+    /// Unlike [`ProgressState::per_sec()`], this does not switch to the overall average once the
+    /// progress bar is finished. Returns `0.0` if no steps have been recorded yet.
+    pub fn rate(&self) -> f64 {
+        let sps = self.est.steps_per_second(self.now());
+        if sps.is_finite() {
+            sps
+        } else {
+            0.0
+        }
+    }
+
+    /// A plain-data copy of the current progress, suitable for sending to other threads
+    pub fn snapshot(&self) -> ProgressSnapshot {
+        ProgressSnapshot {
+            pos: self.pos(),
+            len: self.len(),
+            elapsed: self.elapsed(),
+            eta: self.eta(),
+            per_sec: self.per_sec(),
+            fraction: self.fraction(),
+            is_finished: self.is_finished(),
+        }
+    }
+
+    /// The time spent on this progress bar, not counting any time it was paused
     pub fn elapsed(&self) -> Duration {
-        self.started.elapsed()
+        self.now().saturating_duration_since(self.started)
+    }
+
+    /// The time since the position last changed, not counting any time the progress bar was
+    /// paused
+    ///
+    /// Position changes are noticed when the progress bar is updated, so this is zero if the
+    /// position changed since the last update.
+    pub fn time_since_progress(&self) -> Duration {
+        match self.last_progress {
+            (pos, since) if pos == self.pos() => self.now().saturating_duration_since(since),
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// Indicates that the progress bar is paused
+    pub fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+
+    /// The instant timing information is evaluated at, which is frozen while paused
+    fn now(&self) -> Instant {
+        self.paused_at.unwrap_or_else(|| self.clock.now())
     }
 
     pub fn pos(&self) -> u64 {
@@ -335,10 +1062,38 @@ impl ProgressState {
     pub fn set_len(&mut self, len: u64) {
         self.len = Some(len);
     }
+
+    pub(crate) fn set_estimator(&mut self, kind: EstimatorKind, now: Instant) {
+        self.est.set_kind(kind, now);
+    }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
-pub(crate) enum TabExpandedString {
+/// A point-in-time copy of a progress bar's [`ProgressState`]
+///
+/// Unlike [`ProgressState`] this holds no references into the progress bar, so it can be cloned
+/// cheaply, sent across threads and logged. With the `serde` feature enabled it can also be
+/// serialized and deserialized.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProgressSnapshot {
+    /// See [`ProgressState::pos()`]
+    pub pos: u64,
+    /// See [`ProgressState::len()`]
+    pub len: Option<u64>,
+    /// See [`ProgressState::elapsed()`]
+    pub elapsed: Duration,
+    /// See [`ProgressState::eta()`]
+    pub eta: Duration,
+    /// See [`ProgressState::per_sec()`]
+    pub per_sec: f64,
+    /// See [`ProgressState::fraction()`]
+    pub fraction: f32,
+    /// See [`ProgressState::is_finished()`]
+    pub is_finished: bool,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub(crate) enum TabExpandedString {
     NoTabs(Cow<'static, str>),
     WithTabs {
         original: Cow<'static, str>,
@@ -402,10 +1157,15 @@ impl TabExpandedString {
 /// The purpose of this extra smoothing step is to reduce instantaneous fluctations in the estimate
 /// when large updates are received. Without this, estimates might have a large spike followed by a
 /// slow asymptotic approach to zero (until the next spike).
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub(crate) struct Estimator {
+    kind: EstimatorKind,
     smoothed_steps_per_sec: f64,
     double_smoothed_steps_per_sec: f64,
+    /// Most recent updates, used by [`EstimatorKind::Samples`] and [`EstimatorKind::TimeWindow`]
+    samples: VecDeque<Sample>,
+    /// The rates of the most recent updates, see [`ProgressState::rate_history()`]
+    history: VecDeque<f64>,
     prev_steps: u64,
     prev_time: Instant,
     start_time: Instant,
@@ -414,8 +1174,11 @@ pub(crate) struct Estimator {
 impl Estimator {
     fn new(now: Instant) -> Self {
         Self {
+            kind: EstimatorKind::default(),
             smoothed_steps_per_sec: 0.0,
             double_smoothed_steps_per_sec: 0.0,
+            samples: VecDeque::new(),
+            history: VecDeque::with_capacity(RATE_HISTORY_LEN),
             prev_steps: 0,
             prev_time: now,
             start_time: now,
@@ -436,12 +1199,44 @@ impl Estimator {
 
         let delta_steps = new_steps - self.prev_steps;
         let delta_t = duration_to_secs(now - self.prev_time);
+        self.prev_steps = new_steps;
+        self.prev_time = now;
+
+        if self.history.len() == RATE_HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back(delta_steps as f64 / delta_t);
+
+        let sample = Sample {
+            steps: delta_steps,
+            secs: delta_t,
+            end: now,
+        };
+        match self.kind {
+            EstimatorKind::Samples(capacity) => {
+                if self.samples.len() == capacity {
+                    self.samples.pop_front();
+                }
+                self.samples.push_back(sample);
+                return;
+            }
+            EstimatorKind::TimeWindow(window) => {
+                if let Some(window_start) = now.checked_sub(window) {
+                    while matches!(self.samples.front(), Some(s) if s.end <= window_start) {
+                        self.samples.pop_front();
+                    }
+                }
+                self.samples.push_back(sample);
+                return;
+            }
+            EstimatorKind::DoubleSmoothed | EstimatorKind::Ewma { .. } => {}
+        }
 
         // the rate of steps we saw in this update
         let new_steps_per_second = delta_steps as f64 / delta_t;
 
         // update the estimate: a weighted average of the old estimate and new data
-        let weight = estimator_weight(delta_t);
+        let weight = self.weight(delta_t);
         self.smoothed_steps_per_sec =
             self.smoothed_steps_per_sec * weight + new_steps_per_second * (1.0 - weight);
 
@@ -452,15 +1247,12 @@ impl Estimator {
         // a source for the double smoothed estimate. See comment on normalization in
         // `steps_per_second` for details.
         let delta_t_start = duration_to_secs(now - self.start_time);
-        let total_weight = 1.0 - estimator_weight(delta_t_start);
+        let total_weight = 1.0 - self.weight(delta_t_start);
         let normalized_smoothed_steps_per_sec = self.smoothed_steps_per_sec / total_weight;
 
         // determine the double smoothed value (EWA smoothing of the single EWA)
         self.double_smoothed_steps_per_sec = self.double_smoothed_steps_per_sec * weight
             + normalized_smoothed_steps_per_sec * (1.0 - weight);
-
-        self.prev_steps = new_steps;
-        self.prev_time = now;
     }
 
     /// Reset the state of the estimator. Once reset, estimates will not depend on any data prior
@@ -468,20 +1260,114 @@ impl Estimator {
     pub(crate) fn reset(&mut self, now: Instant) {
         self.smoothed_steps_per_sec = 0.0;
         self.double_smoothed_steps_per_sec = 0.0;
+        self.samples.clear();
+        self.history.clear();
 
         // only reset prev_time, not prev_steps
         self.prev_time = now;
         self.start_time = now;
     }
 
+    /// Start over at `steps`, so that the rate only depends on the steps made after `now`
+    fn restart(&mut self, steps: u64, now: Instant) {
+        self.reset(now);
+        self.prev_steps = steps;
+    }
+
+    /// Move all recorded timestamps forward, as if the time spent paused never happened
+    fn shift(&mut self, paused: Duration) {
+        self.prev_time += paused;
+        self.start_time += paused;
+        for sample in &mut self.samples {
+            sample.end += paused;
+        }
+    }
+
+    /// Switch to a different estimation algorithm, discarding any data recorded so far
+    pub(crate) fn set_kind(&mut self, kind: EstimatorKind, now: Instant) {
+        match kind {
+            EstimatorKind::Ewma { half_life } => {
+                assert!(!half_life.is_zero(), "EWMA half-life must be non-zero");
+            }
+            EstimatorKind::Samples(capacity) => {
+                assert!(
+                    capacity >= 1,
+                    "estimator window must hold at least one sample"
+                );
+                self.samples = VecDeque::with_capacity(capacity);
+            }
+            EstimatorKind::TimeWindow(window) => {
+                assert!(!window.is_zero(), "estimator time window must be non-zero");
+                self.samples = VecDeque::new();
+            }
+            EstimatorKind::DoubleSmoothed => {}
+        }
+        self.kind = kind;
+        self.reset(now);
+    }
+
+    /// Dilution weight for data of the given age (in seconds) under the selected algorithm
+    fn weight(&self, age: f64) -> f64 {
+        match self.kind {
+            // windowed estimators average evenly and never ask for a weight
+            EstimatorKind::DoubleSmoothed
+            | EstimatorKind::Samples(_)
+            | EstimatorKind::TimeWindow(_) => estimator_weight(age),
+            EstimatorKind::Ewma { half_life } => 0.5_f64.powf(age / duration_to_secs(half_life)),
+        }
+    }
+
     /// Average time per step in seconds, using double exponential smoothing
     fn steps_per_second(&self, now: Instant) -> f64 {
+        // No rate can be measured before any time has passed, e.g. when many updates happen at
+        // the same instant the estimator was started, or when the clock goes backwards
+        if now <= self.start_time {
+            return 0.0;
+        }
+
+        match self.kind {
+            EstimatorKind::Samples(_) => {
+                // Time since the last update counts as a sample without any steps, so that the
+                // estimate decays while progress is stalled.
+                // Steps are summed as floats so that huge deltas can't overflow
+                let (steps, secs) = self.samples.iter().fold(
+                    (
+                        0.0,
+                        duration_to_secs(now.saturating_duration_since(self.prev_time)),
+                    ),
+                    |(steps, secs), s| (steps + s.steps as f64, secs + s.secs),
+                );
+                // Without samples, no time may have passed since the last update either
+                return match secs == 0.0 {
+                    true => 0.0,
+                    false => steps / secs,
+                };
+            }
+            EstimatorKind::TimeWindow(window) => {
+                // Only the part of each sample that overlaps the window is taken into account,
+                // assuming the steps of a sample were spread evenly over its duration.
+                let window_secs = duration_to_secs(window);
+                let since_start = duration_to_secs(now - self.start_time);
+                let steps = self.samples.iter().fold(0.0, |steps, s| {
+                    let age = duration_to_secs(now - s.end);
+                    let overlap = (window_secs - age).min(s.secs);
+                    if overlap > 0.0 {
+                        steps + s.steps as f64 * overlap / s.secs
+                    } else {
+                        steps
+                    }
+                });
+                return steps / window_secs.min(since_start);
+            }
+            EstimatorKind::DoubleSmoothed | EstimatorKind::Ewma { .. } => {}
+        }
+
         // Because the value stored in the Estimator is only updated when the Estimator receives an
         // update, this value will become stuck if progress stalls. To return an accurate estimate,
         // we determine how much time has passed since the last update, and treat this as a
         // pseudo-update with 0 steps.
         let delta_t = duration_to_secs(now - self.prev_time);
-        let reweight = estimator_weight(delta_t);
+        let reweight = self.weight(delta_t);
 
         // Normalization of estimates:
         //
@@ -503,17 +1389,63 @@ impl Estimator {
         // in the weighted average. This sum is just W(0) - W(t_f), where t_f is the time since the
         // first sample, and W(0) = 1.
         let delta_t_start = duration_to_secs(now - self.start_time);
-        let total_weight = 1.0 - estimator_weight(delta_t_start);
+        let total_weight = 1.0 - self.weight(delta_t_start);
 
         // Generate updated values for `smoothed_steps_per_sec` and `double_smoothed_steps_per_sec`
         // (sps and dsps) without storing them. Note that we normalize sps when using it as a
         // source to update dsps, and then normalize dsps itself before returning it.
         let sps = self.smoothed_steps_per_sec * reweight / total_weight;
+        if let EstimatorKind::Ewma { .. } = self.kind {
+            return sps;
+        }
+
         let dsps = self.double_smoothed_steps_per_sec * reweight + sps * (1.0 - reweight);
         dsps / total_weight
     }
 }
 
+/// Algorithm used to estimate the rate of progress
+///
+/// The estimated rate drives the `{eta}`, `{duration}` and `{per_sec}` template keys. Select one
+/// with [`ProgressBar::with_estimator()`](crate::ProgressBar::with_estimator).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EstimatorKind {
+    /// Double-smoothed exponentially weighted average (this is the default)
+    ///
+    /// All data older than 15 seconds has a collective weight of 0.1 in the estimate. This gives
+    /// a very steady estimate, but it takes a while to catch up with changes in throughput.
+    #[default]
+    DoubleSmoothed,
+    /// Single exponentially weighted moving average
+    ///
+    /// The weight of a sample halves every `half_life`, so the estimate converges to a new
+    /// throughput much faster than [`EstimatorKind::DoubleSmoothed`] when `half_life` is short.
+    /// Panics when selected with a zero `half_life`.
+    Ewma { half_life: Duration },
+    /// Plain average over the given number of most recent updates
+    ///
+    /// Every update in the window counts equally, regardless of how long ago it happened. Panics
+    /// when selected with a window of zero updates.
+    Samples(usize),
+    /// Plain average over the updates recorded within the given wall-clock window
+    ///
+    /// Updates older than the window are discarded, so the estimate decays to zero if no progress
+    /// is made for the length of the window. Panics when selected with a zero window.
+    TimeWindow(Duration),
+}
+
+/// The number of rates kept by [`ProgressState::rate_history()`]
+pub(crate) const RATE_HISTORY_LEN: usize = 16;
+
+/// A single update recorded by the windowed estimators
+#[derive(Clone, Debug)]
+struct Sample {
+    steps: u64,
+    secs: f64,
+    end: Instant,
+}
+
 pub(crate) struct AtomicPosition {
     pub(crate) pos: AtomicU64,
     capacity: AtomicU8,
@@ -572,8 +1504,24 @@ impl AtomicPosition {
         self.prev.store(elapsed, Ordering::Release);
     }
 
-    pub(crate) fn inc(&self, delta: u64) {
-        self.pos.fetch_add(delta, Ordering::SeqCst);
+    /// Advance the position by `delta`, saturating at `u64::MAX`, and return the new position
+    pub(crate) fn inc(&self, delta: u64) -> u64 {
+        let prev = self
+            .pos
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |pos| {
+                Some(pos.saturating_add(delta))
+            })
+            .unwrap_or_else(|pos| pos);
+        prev.saturating_add(delta)
+    }
+
+    /// Move the position back by `delta`, saturating at zero
+    pub(crate) fn dec(&self, delta: u64) {
+        let _ = self
+            .pos
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |pos| {
+                Some(pos.saturating_sub(delta))
+            });
     }
 
     pub(crate) fn set(&self, pos: u64) {
@@ -592,7 +1540,7 @@ const MAX_BURST: u8 = 10;
 /// [`ProgressBar`]: crate::ProgressBar
 /// [`ProgressBarIter`]: crate::ProgressBarIter
 /// [`ProgressBar::is_finished`]: crate::ProgressBar::is_finished
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub enum ProgressFinish {
     /// Finishes the progress bar and leaves the current message
     ///
@@ -605,6 +1553,7 @@ pub enum ProgressFinish {
     /// Finishes the progress bar and completely clears it (this is the default)
     ///
     /// Same behavior as calling [`ProgressBar::finish_and_clear()`](crate::ProgressBar::finish_and_clear).
+    #[default]
     AndClear,
     /// Finishes the progress bar and leaves the current message and progress
     ///
@@ -616,12 +1565,6 @@ pub enum ProgressFinish {
     AbandonWithMessage(Cow<'static, str>),
 }
 
-impl Default for ProgressFinish {
-    fn default() -> Self {
-        Self::AndClear
-    }
-}
-
 /// Get the appropriate dilution weight for Estimator data given the data's age (in seconds)
 ///
 /// Whenever an update occurs, we will create a new estimate using a weight `w_i` like so:
@@ -651,6 +1594,51 @@ fn estimator_weight(age: f64) -> f64 {
     0.1_f64.powf(age / EXPONENTIAL_WEIGHTING_SECONDS)
 }
 
+/// Rounds `eta` for display, keeping the previously `shown` ETA if `eta` only rounds up to at
+/// most one step more than it
+fn smooth_eta(shown: Option<Duration>, eta: Duration) -> Duration {
+    let rounded = round_eta(eta);
+    match shown {
+        Some(shown) if rounded > shown && rounded - shown <= eta_step(eta) => shown,
+        _ => rounded,
+    }
+}
+
+/// The granularity at which an ETA is displayed: seconds under a minute, 10 seconds under
+/// 10 minutes and minutes above that
+fn eta_step(eta: Duration) -> Duration {
+    match eta.as_secs() {
+        0..=59 => Duration::from_secs(1),
+        60..=599 => Duration::from_secs(10),
+        _ => Duration::from_secs(60),
+    }
+}
+
+fn round_eta(eta: Duration) -> Duration {
+    let step = eta_step(eta).as_millis();
+    let rounded = (eta.as_millis() + step / 2) / step * step;
+    Duration::from_millis(rounded as u64)
+}
+
+/// Subtract `elapsed` from `now`, clamping to the earliest instant the platform can represent
+pub(crate) fn backdate(now: Instant, elapsed: Duration) -> Instant {
+    if let Some(earlier) = now.checked_sub(elapsed) {
+        return earlier;
+    }
+
+    // `Instant` has a platform-specific lower bound, so search for the longest duration that
+    // can still be subtracted, to the nanosecond
+    let (mut fits, mut too_long) = (Duration::ZERO, elapsed);
+    while too_long - fits > Duration::from_nanos(1) {
+        let mid = fits + (too_long - fits) / 2;
+        match now.checked_sub(mid) {
+            Some(_) => fits = mid,
+            None => too_long = mid,
+        }
+    }
+    now - fits
+}
+
 fn duration_to_secs(d: Duration) -> f64 {
     d.as_secs() as f64 + f64::from(d.subsec_nanos()) / 1_000_000_000f64
 }
@@ -661,19 +1649,33 @@ fn secs_to_duration(s: f64) -> Duration {
     Duration::new(secs, nanos)
 }
 
-#[derive(Debug)]
-pub(crate) enum Status {
+/// Whether and how a progress bar was finished
+///
+/// See [`ProgressState::status()`] and [`ProgressBar::status()`](crate::ProgressBar::status).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ProgressStatus {
+    /// The progress bar has not been finished yet
     InProgress,
-    DoneVisible,
-    DoneHidden,
+    /// The progress bar was finished and left on screen, see [`ProgressFinish::AndLeave`] and
+    /// [`ProgressFinish::WithMessage`]
+    Finished,
+    /// The progress bar was abandoned at its current position and left on screen, see
+    /// [`ProgressFinish::Abandon`] and [`ProgressFinish::AbandonWithMessage`]
+    Abandoned,
+    /// The progress bar was finished and cleared from the screen, see
+    /// [`ProgressFinish::AndClear`]
+    Cleared,
 }
 
 pub(crate) const DEFAULT_TAB_WIDTH: usize = 8;
 
 #[cfg(test)]
 mod tests {
+    use std::thread;
+
     use super::*;
-    use crate::ProgressBar;
+    use crate::{FormattedDuration, ProgressBar, TermLike};
 
     // https://github.com/rust-lang/rust-clippy/issues/10281
     #[allow(clippy::uninlined_format_args)]
@@ -785,6 +1787,994 @@ mod tests {
         assert_eq!(est.steps_per_second(now), 1.0);
     }
 
+    #[test]
+    fn test_rate_before_and_after_progress() {
+        let pb = ProgressBar::hidden();
+        pb.set_length(100);
+        // Nothing recorded yet
+        assert_eq!(pb.state().state.rate(), 0.0);
+
+        thread::sleep(Duration::from_millis(10));
+        pb.set_position(10);
+        let rate = pb.state().state.rate();
+        assert!(rate > 0.0 && rate.is_finite());
+
+        // The raw rate does not switch to the overall average when finished
+        pb.finish();
+        let rate = pb.state().state.rate();
+        assert!(rate > 0.0 && rate.is_finite());
+
+        pb.reset_eta();
+        assert_eq!(pb.state().state.rate(), 0.0);
+    }
+
+    #[test]
+    fn test_pause_freezes_elapsed_across_tick() {
+        let time = Arc::new(Mutex::new(Instant::now()));
+        let clock = time.clone();
+        let advance = |secs| *time.lock().unwrap() += Duration::from_secs(secs);
+        let pb = ProgressBar::hidden().with_clock(move || *clock.lock().unwrap());
+        pb.set_length(100);
+        advance(10);
+        pb.set_position(10);
+        pb.tick();
+
+        pb.pause();
+        assert!(pb.is_paused());
+        assert_eq!(pb.elapsed(), Duration::from_secs(10));
+        let eta = pb.eta();
+        assert!(!eta.is_zero());
+
+        advance(50);
+        pb.tick();
+        assert_eq!(pb.elapsed(), Duration::from_secs(10));
+        assert_eq!(pb.eta(), eta);
+
+        // the position can still change while paused
+        pb.inc(10);
+        assert_eq!(pb.position(), 20);
+        assert_eq!(pb.elapsed(), Duration::from_secs(10));
+
+        pb.resume();
+        assert!(!pb.is_paused());
+        assert_eq!(pb.elapsed(), Duration::from_secs(10));
+        advance(5);
+        assert_eq!(pb.elapsed(), Duration::from_secs(15));
+    }
+
+    #[test]
+    fn test_eta_unchanged_across_pause() {
+        let start = Instant::now();
+        let mut est = Estimator::new(start);
+        let mut now = start;
+        for pos in 1..=10 {
+            now += Duration::from_secs(1);
+            est.record(pos * 10, now);
+        }
+        let before = est.steps_per_second(now);
+
+        // a five minute pause, after which the rate picks up where it left off
+        est.shift(Duration::from_secs(300));
+        now += Duration::from_secs(300);
+        assert_eq!(est.steps_per_second(now), before);
+
+        now += Duration::from_secs(1);
+        est.record(110, now);
+        assert!((est.steps_per_second(now) - 10.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_per_sec_excludes_paused_time_when_finished() {
+        let time = Arc::new(Mutex::new(Instant::now()));
+        let clock = time.clone();
+        let advance = |secs| *time.lock().unwrap() += Duration::from_secs(secs);
+        let pb = ProgressBar::hidden().with_clock(move || *clock.lock().unwrap());
+        pb.set_length(10);
+        pb.pause();
+        advance(50);
+        pb.resume();
+        advance(2);
+        pb.finish();
+        // finishing sets the position to 10 after 2 seconds of unpaused time
+        assert_eq!(pb.per_sec(), 5.0);
+    }
+
+    #[test]
+    fn test_snapshot_matches_state() {
+        let pb = ProgressBar::hidden();
+        pb.set_length(100);
+        thread::sleep(Duration::from_millis(10));
+        pb.set_position(25);
+        // pausing freezes the clock, so all accessors agree on the instant
+        pb.pause();
+
+        let state = pb.state();
+        let snapshot = state.state.snapshot();
+        assert_eq!(snapshot.pos, state.state.pos());
+        assert_eq!(snapshot.len, state.state.len());
+        assert_eq!(snapshot.elapsed, state.state.elapsed());
+        assert_eq!(snapshot.eta, state.state.eta());
+        assert_eq!(snapshot.per_sec, state.state.per_sec());
+        assert_eq!(snapshot.fraction, 0.25);
+        assert!(!snapshot.is_finished);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_snapshot_serde_round_trip() {
+        let snapshot = ProgressSnapshot {
+            pos: 3,
+            len: Some(4),
+            elapsed: Duration::from_millis(1500),
+            eta: Duration::from_millis(500),
+            per_sec: 2.0,
+            fraction: 0.75,
+            is_finished: false,
+        };
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert_eq!(
+            serde_json::from_str::<ProgressSnapshot>(&json).unwrap(),
+            snapshot
+        );
+    }
+
+    #[test]
+    fn test_resume_from_checkpoint() {
+        let time = Arc::new(std::sync::Mutex::new(Instant::now()));
+        let clock = time.clone();
+        let pb = ProgressBar::hidden()
+            .with_clock(move || *clock.lock().unwrap())
+            .with_eta_samples(16);
+        pb.set_length(100);
+        pb.resume_from(40, Duration::from_secs(40));
+        assert_eq!(pb.position(), 40);
+        assert_eq!(pb.elapsed(), Duration::from_secs(40));
+
+        // The rate of the earlier run isn't carried over
+        assert_eq!(pb.state().state.try_eta(), None);
+
+        // It's rebuilt from the progress made after resuming
+        *time.lock().unwrap() += Duration::from_secs(1);
+        pb.set_position(45);
+        pb.tick();
+        assert_eq!(pb.per_sec(), 5.0);
+        assert_eq!(pb.eta(), Duration::from_secs(11));
+
+        // Once finished, the rate covers the earlier run as well
+        pb.finish();
+        assert_eq!(pb.elapsed(), Duration::from_secs(41));
+        assert_eq!(pb.per_sec(), 100.0 / 41.0);
+    }
+
+    #[test]
+    fn test_resume_from_discards_rate() {
+        let mut now = Instant::now();
+        let mut est = Estimator::new(now);
+        for pos in 1..=10 {
+            now += Duration::from_secs(1);
+            est.record(pos * 100, now);
+        }
+
+        // The jump to the checkpoint isn't counted as progress
+        est.restart(40, now);
+        assert_eq!(est.steps_per_second(now), 0.0);
+        now += Duration::from_secs(1);
+        est.record(45, now);
+        assert!((est.steps_per_second(now) - 5.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_huge_elapsed_does_not_panic() {
+        let pb = ProgressBar::hidden().with_elapsed(Duration::MAX);
+        pb.resume_from(1, Duration::MAX);
+        let _ = pb.eta();
+        let _ = pb.elapsed();
+    }
+
+    #[test]
+    fn test_backdate_clamps_to_earliest_instant() {
+        let now = Instant::now();
+        assert_eq!(
+            backdate(now, Duration::from_secs(5)),
+            now - Duration::from_secs(5)
+        );
+
+        // Nothing before the clamped start can be represented
+        let earliest = backdate(now, Duration::MAX);
+        assert!(earliest.checked_sub(Duration::from_nanos(1)).is_none());
+    }
+
+    #[derive(Debug)]
+    struct FlushCounter(Arc<portable_atomic::AtomicUsize>);
+
+    impl TermLike for FlushCounter {
+        fn width(&self) -> u16 {
+            80
+        }
+        fn move_cursor_up(&self, _: usize) -> io::Result<()> {
+            Ok(())
+        }
+        fn move_cursor_down(&self, _: usize) -> io::Result<()> {
+            Ok(())
+        }
+        fn move_cursor_right(&self, _: usize) -> io::Result<()> {
+            Ok(())
+        }
+        fn move_cursor_left(&self, _: usize) -> io::Result<()> {
+            Ok(())
+        }
+        fn write_line(&self, _: &str) -> io::Result<()> {
+            Ok(())
+        }
+        fn write_str(&self, _: &str) -> io::Result<()> {
+            Ok(())
+        }
+        fn clear_line(&self) -> io::Result<()> {
+            Ok(())
+        }
+        fn flush(&self) -> io::Result<()> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_draw_rate_limit() {
+        let paints = Arc::new(portable_atomic::AtomicUsize::new(0));
+        let target = ProgressDrawTarget::term_like(Box::new(FlushCounter(paints.clone())));
+        let pos = Arc::new(AtomicPosition::new());
+        let mut state = BarState::new(Some(100), target, pos.clone());
+        state.draw_rate_limit = Duration::from_millis(100);
+
+        let start = Instant::now();
+        for i in 0..50 {
+            pos.inc(1);
+            state.tick(start + Duration::from_millis(i * 10));
+        }
+        // one paint every 100ms: at 0, 100, 200, 300 and 400ms
+        assert_eq!(paints.load(Ordering::SeqCst), 5);
+        assert_eq!(state.state.pos(), 50);
+
+        // forced draws bypass the limit
+        state
+            .draw(true, start + Duration::from_millis(495))
+            .unwrap();
+        assert_eq!(paints.load(Ordering::SeqCst), 6);
+        state.finish_using_style(start + Duration::from_millis(496), ProgressFinish::AndLeave);
+        assert_eq!(paints.load(Ordering::SeqCst), 7);
+    }
+
+    #[test]
+    fn test_min_display_duration() {
+        let paints = Arc::new(portable_atomic::AtomicUsize::new(0));
+        let target = ProgressDrawTarget::term_like(Box::new(FlushCounter(paints.clone())));
+        let pos = Arc::new(AtomicPosition::new());
+        let mut state = BarState::new(Some(10), target, pos.clone());
+        state.min_display_duration = Duration::from_millis(100);
+
+        // A fast task that's cleared when done is never painted
+        let start = state.state.started;
+        for i in 0..10 {
+            pos.inc(1);
+            state.tick(start + Duration::from_millis(i * 5));
+        }
+        state.draw(true, start + Duration::from_millis(50)).unwrap();
+        state.finish_using_style(start + Duration::from_millis(60), ProgressFinish::AndClear);
+        assert_eq!(paints.load(Ordering::SeqCst), 0);
+
+        // Finishing early without clearing only paints the final state
+        let target = ProgressDrawTarget::term_like(Box::new(FlushCounter(paints.clone())));
+        let mut state = BarState::new(Some(10), target, pos.clone());
+        state.min_display_duration = Duration::from_millis(100);
+        let start = state.state.started;
+        state.tick(start + Duration::from_millis(10));
+        assert_eq!(paints.load(Ordering::SeqCst), 0);
+        state.finish_using_style(start + Duration::from_millis(20), ProgressFinish::AndLeave);
+        assert_eq!(paints.load(Ordering::SeqCst), 1);
+
+        // A slower task is painted once it ran for the minimum display duration
+        paints.store(0, Ordering::SeqCst);
+        let target = ProgressDrawTarget::term_like(Box::new(FlushCounter(paints.clone())));
+        let mut state = BarState::new(Some(10), target, pos);
+        state.min_display_duration = Duration::from_millis(100);
+        let start = state.state.started;
+        state.tick(start + Duration::from_millis(99));
+        assert_eq!(paints.load(Ordering::SeqCst), 0);
+        state.tick(start + Duration::from_millis(100));
+        assert_eq!(paints.load(Ordering::SeqCst), 1);
+        state.finish_using_style(start + Duration::from_millis(150), ProgressFinish::AndClear);
+        assert_eq!(paints.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_status_for_each_finish_variant() {
+        let cases = [
+            (ProgressFinish::AndLeave, ProgressStatus::Finished, 10),
+            (
+                ProgressFinish::WithMessage("done".into()),
+                ProgressStatus::Finished,
+                10,
+            ),
+            (ProgressFinish::AndClear, ProgressStatus::Cleared, 10),
+            (ProgressFinish::Abandon, ProgressStatus::Abandoned, 3),
+            (
+                ProgressFinish::AbandonWithMessage("oops".into()),
+                ProgressStatus::Abandoned,
+                3,
+            ),
+        ];
+
+        for (finish, status, pos) in cases {
+            let pb = ProgressBar::hidden().with_finish(finish);
+            pb.set_length(10);
+            pb.set_position(3);
+            assert_eq!(pb.status(), ProgressStatus::InProgress);
+
+            pb.finish_using_style();
+            assert_eq!(pb.status(), status);
+            assert_eq!(pb.position(), pos);
+            assert!(pb.is_finished());
+
+            pb.reset();
+            assert_eq!(pb.status(), ProgressStatus::InProgress);
+        }
+    }
+
+    #[test]
+    fn test_reset_eta_on_length_change() {
+        let rate_after_discovery = |reset| {
+            let pos = Arc::new(AtomicPosition::new());
+            let mut state = BarState::new(Some(100), ProgressDrawTarget::hidden(), pos.clone());
+            state.reset_eta_on_length_change = reset;
+
+            // 10 steps per second for 10 seconds
+            let mut now = Instant::now();
+            for _ in 0..10 {
+                now += Duration::from_secs(1);
+                pos.inc(10);
+                state.tick(now);
+            }
+
+            // lots more work is discovered, which turns out to go much faster
+            state.inc_length(now, 10_000);
+            for _ in 0..2 {
+                now += Duration::from_secs(1);
+                pos.inc(100);
+                state.tick(now);
+            }
+            state.state.est.steps_per_second(now)
+        };
+
+        assert!((rate_after_discovery(true) - 100.0).abs() < 1e-9);
+        assert!(rate_after_discovery(false) < 50.0);
+    }
+
+    #[test]
+    fn test_fraction_of_large_length() {
+        const LEN: u64 = 10_000_000_000;
+        let pos = Arc::new(AtomicPosition::new());
+        let state = ProgressState::new(Some(LEN), pos.clone());
+
+        pos.set(LEN / 2);
+        let half = state.fraction_f64();
+        pos.set(LEN / 2 + 1);
+        assert!(state.fraction_f64() > half);
+        assert_eq!(state.fraction(), 0.5);
+
+        // Just short of the length, the bar isn't drawn full yet
+        pos.set(LEN - 1);
+        assert!(state.fraction_f64() < 1.0);
+        let style = ProgressStyle::with_template("{bar:10}")
+            .unwrap()
+            .progress_chars("#>-");
+        let mut lines = Vec::new();
+        style.format_state(&state, &mut lines, 80);
+        assert_eq!(lines, ["#########>"]);
+    }
+
+    #[test]
+    fn test_countdown() {
+        let pos = Arc::new(AtomicPosition::new());
+        let mut state = BarState::new(Some(100), ProgressDrawTarget::hidden(), pos.clone());
+        state.state.countdown_start = Some(100);
+        pos.set(100);
+        assert_eq!(state.state.fraction(), 0.0);
+
+        // 10 steps per second toward zero
+        let mut now = Instant::now();
+        for _ in 0..4 {
+            now += Duration::from_secs(1);
+            pos.dec(10);
+            state.tick(now);
+        }
+        state.pause(now);
+        assert_eq!(state.state.pos(), 60);
+        assert_eq!(state.state.fraction(), 0.4);
+        let rate = state.state.per_sec();
+        assert!((rate - 10.0).abs() < 1.0, "{rate}");
+        let eta = state.state.eta().as_secs_f64();
+        assert!((eta - 60.0 / rate).abs() < 0.01, "{eta}");
+
+        state.state.fixed_rate = Some(20.0);
+        assert_eq!(state.state.eta(), Duration::from_secs(3));
+        state.state.fixed_rate = None;
+
+        state.finish_using_style(now, ProgressFinish::AndLeave);
+        assert_eq!(state.state.pos(), 0);
+        assert_eq!(state.state.fraction(), 1.0);
+        assert_eq!(state.state.eta(), Duration::ZERO);
+
+        state.reset(now, Reset::All);
+        assert_eq!(state.state.pos(), 100);
+
+        let pb = ProgressBar::new_countdown(10);
+        pb.set_draw_target(ProgressDrawTarget::hidden());
+        pb.dec(3);
+        assert_eq!(pb.position(), 7);
+        pb.dec(20);
+        assert_eq!(pb.position(), 0);
+    }
+
+    #[test]
+    fn test_fixed_rate() {
+        let pos = Arc::new(AtomicPosition::new());
+        let mut state = BarState::new(Some(100), ProgressDrawTarget::hidden(), pos.clone());
+        let mut now = Instant::now();
+        for _ in 0..4 {
+            now += Duration::from_secs(1);
+            pos.inc(10);
+            state.tick(now);
+        }
+        state.pause(now);
+        let estimated = state.state.eta();
+
+        state.state.fixed_rate = Some(12.0);
+        assert_eq!(state.state.eta(), Duration::from_secs(5));
+        assert_eq!(state.state.per_sec(), 12.0);
+        assert_eq!(
+            state.state.duration(),
+            state.state.elapsed() + Duration::from_secs(5)
+        );
+
+        state.state.fixed_rate = None;
+        assert_eq!(state.state.eta(), estimated);
+    }
+
+    #[test]
+    #[should_panic(expected = "fixed rate must be finite and non-negative")]
+    fn test_fixed_rate_nan() {
+        ProgressBar::hidden().set_fixed_rate(Some(f64::NAN));
+    }
+
+    #[test]
+    fn test_position_past_length() {
+        for allow_overshoot in [false, true] {
+            let pos = Arc::new(AtomicPosition::new());
+            let mut state = BarState::new(Some(100), ProgressDrawTarget::hidden(), pos.clone());
+            state.state.allow_overshoot = allow_overshoot;
+
+            let mut now = Instant::now();
+            for _ in 0..10 {
+                now += Duration::from_secs(1);
+                pos.inc(8);
+                state.tick(now);
+            }
+
+            // Freeze the clock for the duration checks
+            state.pause(now);
+
+            // The work shrinks below what was already done
+            state.set_length(now, 40);
+            let expected = if allow_overshoot { 80 } else { 40 };
+            assert_eq!(state.state.pos(), expected);
+            assert_eq!(state.state.fraction(), 1.0);
+            assert_eq!(state.state.eta(), Duration::ZERO);
+            assert_eq!(state.state.duration(), state.state.elapsed());
+            assert!(state.state.per_sec() > 0.0);
+            let percent = if allow_overshoot { 200.0 } else { 100.0 };
+            assert_eq!(state.state.percent(), percent);
+
+            // Moving past the length is kept either way
+            pos.set(60);
+            assert_eq!(state.state.fraction(), 1.0);
+            assert_eq!(state.state.eta(), Duration::ZERO);
+            let percent = if allow_overshoot { 150.0 } else { 100.0 };
+            assert_eq!(state.state.percent(), percent);
+
+            state.finish_using_style(now, ProgressFinish::AndLeave);
+            assert_eq!(state.state.duration(), Duration::ZERO);
+            assert!(state.state.per_sec().is_finite());
+        }
+
+        let pb = ProgressBar::hidden().with_style(
+            ProgressStyle::with_template("{bar:10} {pos}/{len} {percent}%")
+                .unwrap()
+                .progress_chars("#>-"),
+        );
+        pb.set_allow_overshoot(true);
+        pb.set_length(10);
+        pb.set_position(15);
+        let mut lines = Vec::new();
+        let state = pb.state();
+        state.style.format_state(&state.state, &mut lines, 80);
+        drop(state);
+        assert_eq!(lines[0], "########## 15/10 150%");
+    }
+
+    #[test]
+    fn test_positions_near_u64_max() {
+        let pb = ProgressBar::hidden().with_style(
+            ProgressStyle::with_template("{bytes}/{total_bytes} {bytes_per_sec} {per_sec} {eta}")
+                .unwrap(),
+        );
+        pb.set_length(u64::MAX);
+        pb.set_position(u64::MAX - 10);
+        thread::sleep(Duration::from_millis(5));
+        pb.inc(5);
+        pb.inc(u64::MAX);
+        assert_eq!(pb.position(), u64::MAX);
+        pb.inc_length(1);
+        assert_eq!(pb.length(), Some(u64::MAX));
+
+        let rate = pb.per_sec();
+        assert!(rate >= 0.0 && !rate.is_nan());
+        let _ = pb.eta();
+
+        let mut lines = Vec::new();
+        let state = pb.state();
+        state.style.format_state(&state.state, &mut lines, 80);
+        assert!(lines[0].starts_with("16.00 EiB/16.00 EiB"));
+    }
+
+    #[test]
+    fn test_estimator_huge_deltas() {
+        for kind in [
+            EstimatorKind::DoubleSmoothed,
+            EstimatorKind::Ewma {
+                half_life: Duration::from_secs(1),
+            },
+            EstimatorKind::Samples(4),
+            EstimatorKind::TimeWindow(Duration::from_secs(10)),
+        ] {
+            let mut now = Instant::now();
+            let mut est = Estimator::new(now);
+            est.set_kind(kind, now);
+            for pos in [u64::MAX / 4, u64::MAX / 2, u64::MAX - 1, u64::MAX] {
+                now += Duration::from_millis(1);
+                est.record(pos, now);
+            }
+            let sps = est.steps_per_second(now);
+            assert!(sps.is_finite() && sps > 1e18, "{kind:?}: {sps}");
+        }
+    }
+
+    #[test]
+    fn test_estimator_same_instant_records() {
+        for kind in [
+            EstimatorKind::DoubleSmoothed,
+            EstimatorKind::Ewma {
+                half_life: Duration::from_secs(1),
+            },
+            EstimatorKind::Samples(4),
+            EstimatorKind::TimeWindow(Duration::from_secs(10)),
+        ] {
+            let start = Instant::now();
+            let mut now = start;
+            let mut est = Estimator::new(now);
+            est.set_kind(kind, now);
+
+            // Records at the instant the estimator started don't make it divide by zero
+            for pos in 1..=1000 {
+                est.record(pos, now);
+            }
+            let sps = est.steps_per_second(now);
+            assert!(sps.is_finite(), "{kind:?}: {sps}");
+
+            // Steps recorded without time passing count towards the next measurable interval
+            now += Duration::from_secs(1);
+            est.record(1000, now);
+            for pos in 1001..=2000 {
+                est.record(pos, now);
+            }
+            now += Duration::from_secs(1);
+            est.record(2000, now);
+            let sps = est.steps_per_second(now);
+            assert!((500.0..=2000.0).contains(&sps), "{kind:?}: {sps}");
+
+            // A clock that goes backwards doesn't panic or skew the estimate
+            est.record(2100, start);
+            let sps = est.steps_per_second(start);
+            assert!(sps.is_finite(), "{kind:?}: {sps}");
+            now += Duration::from_secs(1);
+            est.record(3000, now);
+            let sps = est.steps_per_second(now);
+            assert!((500.0..=2000.0).contains(&sps), "{kind:?}: {sps}");
+        }
+    }
+
+    #[test]
+    fn test_per_sec_without_time() {
+        let time = Arc::new(std::sync::Mutex::new(Instant::now()));
+        let clock = time.clone();
+        let pb = ProgressBar::hidden()
+            .with_clock(move || *clock.lock().unwrap())
+            .with_eta_samples(16);
+
+        // Many updates at the instant the progress bar started
+        for pos in 1..=1000 {
+            pb.set_position(pos);
+            pb.tick();
+        }
+        assert_eq!(pb.per_sec(), 0.0);
+
+        // They count towards the first measurable interval
+        *time.lock().unwrap() += Duration::from_secs(1);
+        pb.set_position(2000);
+        pb.tick();
+        assert_eq!(pb.per_sec(), 2000.0);
+
+        // A clock that goes backwards
+        *time.lock().unwrap() -= Duration::from_secs(2);
+        pb.set_position(2100);
+        pb.tick();
+        let rate = pb.per_sec();
+        assert!(rate.is_finite() && rate >= 0.0, "{rate}");
+
+        // Finishing at the instant the progress bar started, with and without progress
+        for len in [10, 0] {
+            let start = Instant::now();
+            let pb = ProgressBar::hidden().with_clock(move || start);
+            pb.set_length(len);
+            pb.finish();
+            assert_eq!(pb.per_sec(), 0.0);
+        }
+    }
+
+    #[test]
+    fn test_fake_clock_exact_eta() {
+        let time = Arc::new(std::sync::Mutex::new(Instant::now()));
+        let clock = time.clone();
+        let advance = |secs| *time.lock().unwrap() += Duration::from_secs(secs);
+
+        let pb = ProgressBar::hidden()
+            .with_clock(move || *clock.lock().unwrap())
+            .with_eta_samples(16);
+        pb.set_length(100);
+
+        for _ in 0..4 {
+            advance(1);
+            pb.inc(10);
+            pb.tick();
+        }
+        assert_eq!(pb.elapsed(), Duration::from_secs(4));
+        assert_eq!(pb.per_sec(), 10.0);
+        assert_eq!(pb.eta(), Duration::from_secs(6));
+        assert_eq!(pb.duration(), Duration::from_secs(10));
+
+        // stalling for four seconds halves the rate estimated over the sample window
+        advance(4);
+        assert_eq!(pb.per_sec(), 5.0);
+        assert_eq!(pb.eta(), Duration::from_secs(12));
+
+        pb.pause();
+        advance(100);
+        pb.resume();
+        assert_eq!(pb.elapsed(), Duration::from_secs(8));
+
+        pb.finish();
+        assert_eq!(pb.per_sec(), 12.5);
+    }
+
+    #[test]
+    fn test_without_eta() {
+        let time = Arc::new(std::sync::Mutex::new(Instant::now()));
+        let clock = time.clone();
+
+        let pb = ProgressBar::hidden()
+            .with_clock(move || *clock.lock().unwrap())
+            .without_eta();
+        pb.set_length(100);
+        for _ in 0..10 {
+            *time.lock().unwrap() += Duration::from_secs(1);
+            pb.inc(5);
+            pb.tick();
+        }
+
+        let state = pb.state();
+        let recorded = (state.state.est.prev_steps, state.state.est.samples.len());
+        drop(state);
+        assert_eq!(recorded, (0, 0));
+        assert_eq!(pb.position(), 50);
+        assert_eq!(pb.eta(), Duration::ZERO);
+        assert_eq!(pb.per_sec(), 0.0);
+        assert_eq!(pb.state().state.rate(), 0.0);
+
+        // A fixed rate still works
+        pb.set_fixed_rate(Some(10.0));
+        assert_eq!(pb.eta(), Duration::from_secs(5));
+        pb.set_fixed_rate(None);
+
+        pb.finish();
+        assert_eq!(pb.per_sec(), 0.0);
+    }
+
+    #[test]
+    fn test_set_pos_and_len() {
+        let pos = Arc::new(AtomicPosition::new());
+        let mut state = BarState::new(Some(10), ProgressDrawTarget::hidden(), pos);
+        let now = Instant::now();
+        state.set_pos_and_len(now, 50, 100);
+        assert_eq!((state.state.pos(), state.state.len()), (50, Some(100)));
+        assert_eq!(state.state.est.prev_steps, 50);
+
+        // The position is capped to the length unless overshooting is allowed
+        state.set_pos_and_len(now, 150, 120);
+        assert_eq!((state.state.pos(), state.state.len()), (120, Some(120)));
+        state.state.allow_overshoot = true;
+        state.set_pos_and_len(now, 150, 120);
+        assert_eq!(state.state.pos(), 150);
+    }
+
+    #[test]
+    fn test_try_eta() {
+        let time = Arc::new(std::sync::Mutex::new(Instant::now()));
+        let clock = time.clone();
+
+        let pb = ProgressBar::hidden().with_clock(move || *clock.lock().unwrap());
+        for _ in 0..5 {
+            *time.lock().unwrap() += Duration::from_secs(1);
+            pb.inc(5);
+            pb.tick();
+        }
+        // Without a length, the ETA is unknown rather than zero
+        assert_eq!(pb.try_eta(), None);
+        assert_eq!(pb.eta(), Duration::ZERO);
+
+        let pb = ProgressBar::hidden().with_clock(move || *time.lock().unwrap());
+        pb.set_length(100);
+        // No progress has been made to estimate the ETA from yet
+        assert_eq!(pb.try_eta(), None);
+
+        pb.set_fixed_rate(Some(10.0));
+        pb.set_position(50);
+        assert_eq!(pb.try_eta(), Some(Duration::from_secs(5)));
+        assert_eq!(pb.eta(), Duration::from_secs(5));
+
+        pb.finish();
+        assert_eq!(pb.try_eta(), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn test_stall_indicator() {
+        let time = Arc::new(std::sync::Mutex::new(Instant::now()));
+        let clock = time.clone();
+        let advance = |secs| *time.lock().unwrap() += Duration::from_secs(secs);
+
+        let (target, buffer) = ProgressDrawTarget::string_buffer();
+        let pb = ProgressBar::with_draw_target(Some(100), target)
+            .with_clock(move || *clock.lock().unwrap())
+            .with_style(
+                ProgressStyle::with_template("{pos}|{stall}")
+                    .unwrap()
+                    .stall_threshold(Duration::from_secs(10)),
+            );
+        pb.inc(1);
+        pb.tick();
+        assert_eq!(*buffer.lock().unwrap(), "1|");
+
+        advance(5);
+        pb.tick();
+        assert_eq!(pb.time_since_progress(), Duration::from_secs(5));
+        assert_eq!(*buffer.lock().unwrap(), "1|");
+
+        advance(7);
+        pb.tick();
+        assert_eq!(*buffer.lock().unwrap(), "1|stalled 12s");
+
+        pb.inc(1);
+        assert_eq!(pb.time_since_progress(), Duration::ZERO);
+        pb.tick();
+        assert_eq!(*buffer.lock().unwrap(), "2|");
+
+        // Finished progress bars are never stalled
+        advance(20);
+        pb.abandon();
+        assert_eq!(*buffer.lock().unwrap(), "2|");
+    }
+
+    #[test]
+    fn test_ewma_tracks_step_change() {
+        let mut now = Instant::now();
+        let mut ewma = Estimator::new(now);
+        ewma.set_kind(
+            EstimatorKind::Ewma {
+                half_life: Duration::from_secs(1),
+            },
+            now,
+        );
+        let mut double = Estimator::new(now);
+        let mut pos = 0;
+
+        // 10 steps per second for a while, then jump to 100 steps per second
+        for rate in [10; 30].iter().chain(&[100; 5]) {
+            pos += rate;
+            now += Duration::from_secs(1);
+            ewma.record(pos, now);
+            double.record(pos, now);
+        }
+
+        let remaining = 1_000.0;
+        let ewma_eta = remaining / ewma.steps_per_second(now);
+        let double_eta = remaining / double.steps_per_second(now);
+        let expected_eta = remaining / 100.0;
+        assert!((ewma_eta - expected_eta).abs() / expected_eta < 0.05);
+        assert!((double_eta - expected_eta).abs() / expected_eta > 0.5);
+    }
+
+    #[test]
+    fn test_ewma_steady_rate() {
+        let mut now = Instant::now();
+        let mut est = Estimator::new(now);
+        est.set_kind(
+            EstimatorKind::Ewma {
+                half_life: Duration::from_millis(500),
+            },
+            now,
+        );
+
+        for pos in 1..=10 {
+            now += Duration::from_millis(250);
+            est.record(pos * 5, now);
+        }
+        assert!((est.steps_per_second(now) - 20.0).abs() < 1e-9);
+    }
+
+    #[test]
+    #[should_panic(expected = "EWMA half-life must be non-zero")]
+    fn test_ewma_zero_half_life() {
+        let now = Instant::now();
+        let mut est = Estimator::new(now);
+        est.set_kind(
+            EstimatorKind::Ewma {
+                half_life: Duration::ZERO,
+            },
+            now,
+        );
+    }
+
+    #[test]
+    fn smoothed_eta_rounding() {
+        let smoothed = |secs: f64| {
+            let eta = smooth_eta(None, Duration::from_secs_f64(secs));
+            FormattedDuration(eta).to_string()
+        };
+
+        assert_eq!(smoothed(0.4), "00:00:00");
+        assert_eq!(smoothed(12.6), "00:00:13");
+        assert_eq!(smoothed(59.4), "00:00:59");
+        assert_eq!(smoothed(64.0), "00:01:00");
+        assert_eq!(smoothed(125.0), "00:02:10");
+        assert_eq!(smoothed(599.0), "00:10:00");
+        assert_eq!(smoothed(629.0), "00:10:00");
+        assert_eq!(smoothed(3_690.0), "01:02:00");
+    }
+
+    #[test]
+    fn smoothed_eta_ignores_small_increases() {
+        let mut eta = None;
+        let mut shown = |secs| {
+            let shown = smooth_eta(eta, Duration::from_secs(secs));
+            eta = Some(shown);
+            shown.as_secs()
+        };
+
+        assert_eq!(shown(131), 130);
+        assert_eq!(shown(138), 130);
+        assert_eq!(shown(124), 120);
+        assert_eq!(shown(155), 160);
+        assert_eq!(shown(42), 42);
+        assert_eq!(shown(43), 42);
+        assert_eq!(shown(45), 45);
+    }
+
+    #[test]
+    fn test_sample_window_smooths_noise() {
+        let spread = |capacity| {
+            let mut now = Instant::now();
+            let mut est = Estimator::new(now);
+            est.set_kind(EstimatorKind::Samples(capacity), now);
+
+            // deterministic noise: rates uniformly spread over 50..150 steps per second
+            let mut seed = 42u64;
+            let mut pos = 0;
+            let (mut min, mut max) = (f64::INFINITY, 0.0_f64);
+            for i in 0..1_000 {
+                seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+                pos += 50 + (seed >> 33) % 100;
+                now += Duration::from_secs(1);
+                est.record(pos, now);
+                if i >= 500 {
+                    let sps = est.steps_per_second(now);
+                    min = min.min(sps);
+                    max = max.max(sps);
+                }
+            }
+            max - min
+        };
+
+        assert!(spread(256) * 2.0 < spread(16));
+    }
+
+    #[test]
+    fn test_sample_window_capacity() {
+        let mut now = Instant::now();
+        let mut est = Estimator::new(now);
+        est.set_kind(EstimatorKind::Samples(2), now);
+
+        for (pos, secs) in [(100, 1), (101, 1), (103, 1)] {
+            now += Duration::from_secs(secs);
+            est.record(pos, now);
+        }
+        // only the last two updates (1 + 2 steps over 2 seconds) are taken into account
+        assert_eq!(est.steps_per_second(now), 1.5);
+
+        est.reset(now);
+        now += Duration::from_secs(1);
+        est.record(107, now);
+        assert_eq!(est.steps_per_second(now), 4.0);
+    }
+
+    #[test]
+    fn test_sample_window_without_time() {
+        let start = Instant::now();
+        let mut est = Estimator::new(start);
+        est.set_kind(EstimatorKind::Samples(4), start);
+
+        // An empty window right at the last update spans no time at all
+        let now = start + Duration::from_secs(1);
+        est.prev_time = now;
+        assert_eq!(est.steps_per_second(now), 0.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "estimator window must hold at least one sample")]
+    fn test_sample_window_empty() {
+        let _ = ProgressBar::hidden().with_eta_samples(0);
+    }
+
+    #[test]
+    fn test_time_window_decays_after_burst() {
+        let mut now = Instant::now();
+        let mut est = Estimator::new(now);
+        est.set_kind(EstimatorKind::TimeWindow(Duration::from_secs(5)), now);
+
+        // a burst of 1000 steps per second for one second
+        for pos in 1..=10 {
+            now += Duration::from_millis(100);
+            est.record(pos * 100, now);
+        }
+        assert!((est.steps_per_second(now) - 1000.0).abs() < 1e-6);
+
+        // followed by a long pause
+        let mut prev = f64::INFINITY;
+        for _ in 0..8 {
+            now += Duration::from_millis(500);
+            let sps = est.steps_per_second(now);
+            assert!(sps < prev);
+            prev = sps;
+        }
+        assert!((est.steps_per_second(now) - 200.0).abs() < 1e-6);
+
+        now += Duration::from_secs(1);
+        assert_eq!(est.steps_per_second(now), 0.0);
+
+        // old samples are dropped once progress resumes
+        now += Duration::from_secs(1);
+        est.record(1_010, now);
+        assert_eq!(est.samples.len(), 1);
+        assert!((est.steps_per_second(now) - 10.0 / 6.0).abs() < 1e-6);
+    }
+
     #[test]
     fn test_duration_stuff() {
         let duration = Duration::new(42, 100_000_000);
@@ -1,10 +1,12 @@
 use std::collections::HashMap;
 use std::fmt::{self, Write};
 use std::mem;
+use std::ops::Range;
+use std::time::Duration;
 #[cfg(not(target_arch = "wasm32"))]
 use std::time::Instant;
 
-use console::{measure_text_width, Style};
+use console::{measure_text_width, truncate_str, AnsiCodeIterator, Color, Style};
 #[cfg(target_arch = "wasm32")]
 use instant::Instant;
 #[cfg(feature = "unicode-segmentation")]
@@ -12,9 +14,11 @@ use unicode_segmentation::UnicodeSegmentation;
 
 use crate::format::{
     BinaryBytes, DecimalBytes, FormattedDuration, HumanBytes, HumanCount, HumanDuration,
-    HumanFloatCount,
+    HumanFloatCount, SiCount,
+};
+use crate::state::{
+    ProgressState, ProgressStatus, TabExpandedString, DEFAULT_TAB_WIDTH, RATE_HISTORY_LEN,
 };
-use crate::state::{ProgressState, TabExpandedString, DEFAULT_TAB_WIDTH};
 
 #[derive(Clone)]
 pub struct ProgressStyle {
@@ -23,10 +27,37 @@ pub struct ProgressStyle {
     template: Template,
     // how unicode-big each char in progress_chars is
     char_width: usize,
+    bar_delimiters: [Box<str>; 2],
+    /// The fractions of the bar at which marks are drawn
+    bar_marks: Vec<f32>,
+    /// The marks drawn over filled and empty cells
+    mark_chars: [Box<str>; 2],
     tab_width: usize,
+    reverse: bool,
+    gradient: Vec<Color>,
+    indeterminate: bool,
+    tick_frame_duration: Option<Duration>,
+    stall_threshold: Duration,
+    /// The granularity the shown position is rounded down to
+    position_step: u64,
+    /// The longest ETA that's shown, and what's shown instead of longer ones
+    max_eta: Option<(Duration, Box<str>)>,
+    wrap_message: Option<WrapMode>,
+    /// The styles of the message once the progress bar is finished or abandoned
+    finish_style: Option<Style>,
+    abandon_style: Option<Style>,
+    thousands_separator: char,
+    colors: ColorScheme,
     pub(crate) format_map: HashMap<&'static str, Box<dyn ProgressTracker>>,
 }
 
+/// Glyphs replaced by [`ProgressStyle::swap_ascii_glyphs()`], if they weren't ASCII already
+struct Glyphs {
+    progress_chars: Option<(Vec<Box<str>>, usize)>,
+    tick_strings: Option<Vec<Box<str>>>,
+    mark_chars: Option<[Box<str>; 2]>,
+}
+
 #[cfg(feature = "unicode-segmentation")]
 fn segment(s: &str) -> Vec<Box<str>> {
     UnicodeSegmentation::graphemes(s, true)
@@ -36,7 +67,16 @@ fn segment(s: &str) -> Vec<Box<str>> {
 
 #[cfg(not(feature = "unicode-segmentation"))]
 fn segment(s: &str) -> Vec<Box<str>> {
-    s.chars().map(|x| x.to_string().into()).collect()
+    // Approximate grapheme clusters by keeping zero-width characters (e.g. combining accents)
+    // with the preceding character
+    let mut clusters: Vec<String> = Vec::new();
+    for c in s.chars() {
+        match clusters.last_mut() {
+            Some(last) if measure(c.encode_utf8(&mut [0; 4])) == 0 => last.push(c),
+            _ => clusters.push(c.to_string()),
+        }
+    }
+    clusters.into_iter().map(Into::into).collect()
 }
 
 #[cfg(feature = "unicode-width")]
@@ -97,9 +137,24 @@ impl ProgressStyle {
                 .collect(),
             progress_chars,
             char_width,
+            bar_delimiters: [Box::from(""), Box::from("")],
+            bar_marks: Vec::new(),
+            mark_chars: [Box::from("|"), Box::from("|")],
             template,
             format_map: HashMap::default(),
             tab_width: DEFAULT_TAB_WIDTH,
+            reverse: false,
+            gradient: Vec::new(),
+            indeterminate: false,
+            tick_frame_duration: None,
+            stall_threshold: Duration::from_secs(5),
+            position_step: 1,
+            max_eta: None,
+            wrap_message: None,
+            finish_style: None,
+            abandon_style: None,
+            thousands_separator: ',',
+            colors: ColorScheme::default(),
         }
     }
 
@@ -107,8 +162,12 @@ impl ProgressStyle {
     ///
     /// Note that the last character is used as the [final tick string][Self::get_final_tick_str()].
     /// At least two characters are required to provide a non-final and final state.
+    ///
+    /// With the `improved_unicode` feature, each grapheme cluster (such as an emoji with a
+    /// modifier) is a single frame. Otherwise use [`tick_strings()`](Self::tick_strings) for
+    /// frames made of multiple code points.
     pub fn tick_chars(mut self, s: &str) -> Self {
-        self.tick_strings = s.chars().map(|c| c.to_string().into()).collect();
+        self.tick_strings = segment(s);
         // Format bar will panic with some potentially confusing message, better to panic here
         // with a message explicitly informing of the problem
         assert!(
@@ -127,15 +186,84 @@ impl ProgressStyle {
         // Format bar will panic with some potentially confusing message, better to panic here
         // with a message explicitly informing of the problem
         assert!(
-            self.progress_chars.len() >= 2,
+            self.tick_strings.len() >= 2,
             "at least 2 tick strings required"
         );
         self
     }
 
+    /// Replaces non-ASCII progress characters and tick strings with ASCII ones
+    ///
+    /// The progress characters become `#>-` and the tick strings `-\|/`. This is applied
+    /// automatically when drawing to a terminal that doesn't support Unicode, i.e. the Linux
+    /// console or one whose locale is set to something other than UTF-8 (Windows consoles, and
+    /// terminals without a locale, are assumed to support it).
+    pub fn ascii(mut self) -> Self {
+        self.swap_ascii_glyphs();
+        self
+    }
+
+    /// Renders with `f` using ASCII glyphs like [`ascii()`](Self::ascii) without cloning the
+    /// style, so its trackers stay the ones that are ticked
+    pub(crate) fn with_ascii_glyphs<R>(&mut self, f: impl FnOnce(&Self) -> R) -> R {
+        let glyphs = self.swap_ascii_glyphs();
+        let result = f(self);
+        let Glyphs {
+            progress_chars,
+            tick_strings,
+            mark_chars,
+        } = glyphs;
+        if let Some((chars, char_width)) = progress_chars {
+            self.progress_chars = chars;
+            self.char_width = char_width;
+        }
+        if let Some(tick_strings) = tick_strings {
+            self.tick_strings = tick_strings;
+        }
+        if let Some(mark_chars) = mark_chars {
+            self.mark_chars = mark_chars;
+        }
+        result
+    }
+
+    /// Replaces the non-ASCII glyphs with ASCII ones, returning the replaced ones
+    fn swap_ascii_glyphs(&mut self) -> Glyphs {
+        let progress_chars = match self.progress_chars.iter().all(|c| c.is_ascii()) {
+            true => None,
+            false => Some((
+                mem::replace(&mut self.progress_chars, segment("#>-")),
+                mem::replace(&mut self.char_width, 1),
+            )),
+        };
+        let tick_strings = match self.tick_strings.iter().all(|s| s.is_ascii()) {
+            true => None,
+            false => Some(mem::replace(&mut self.tick_strings, segment("-\\|/ "))),
+        };
+        let mark_chars = match self.mark_chars.iter().all(|s| s.is_ascii()) {
+            true => None,
+            false => Some(mem::replace(
+                &mut self.mark_chars,
+                [Box::from("|"), Box::from("|")],
+            )),
+        };
+        Glyphs {
+            progress_chars,
+            tick_strings,
+            mark_chars,
+        }
+    }
+
+    pub(crate) fn is_ascii(&self) -> bool {
+        self.progress_chars.iter().all(|c| c.is_ascii())
+            && self.tick_strings.iter().all(|s| s.is_ascii())
+            && self.mark_chars.iter().all(|s| s.is_ascii())
+    }
+
     /// Sets the progress characters `(filled, current, to do)`
     ///
-    /// You can pass more than three for a more detailed display.
+    /// You can pass more than three for a more detailed display: all characters between the first
+    /// and the last one are used for the partially filled cell, ordered from the fullest to the
+    /// emptiest. For example, `"█▉▊▋▌▍▎▏ "` draws a half-filled cell as `▌`.
     /// All passed grapheme clusters need to be of equal width.
     pub fn progress_chars(mut self, s: &str) -> Self {
         self.progress_chars = segment(s);
@@ -146,6 +274,175 @@ impl ProgressStyle {
             "at least 2 progress chars required"
         );
         self.char_width = width(&self.progress_chars);
+        self.check_mark_width();
+        self
+    }
+
+    /// Sets the strings drawn before and after the bar
+    ///
+    /// The delimiters count toward the width of `bar` and `wide_bar`, so the bar itself gets
+    /// narrower. Both are empty by default.
+    pub fn bar_delimiters(mut self, left: &str, right: &str) -> Self {
+        self.bar_delimiters = [left.into(), right.into()];
+        self
+    }
+
+    /// Draws marks inside the bar at the given fractions, e.g. `&[0.25, 0.5, 0.75]`
+    ///
+    /// The mark for a fraction replaces the cell the bar reaches at that fraction (counted in the
+    /// direction the bar fills), drawn as `filled` once that cell is filled and as `empty` before.
+    /// The cell currently being filled is never replaced. Like the progress characters, both
+    /// marks need to be of the same width as them. An empty slice removes the marks.
+    pub fn bar_marks(mut self, fractions: &[f32], filled: &str, empty: &str) -> Self {
+        self.bar_marks = fractions.to_vec();
+        self.mark_chars = [filled.into(), empty.into()];
+        self.check_mark_width();
+        self
+    }
+
+    /// Panics if the bar has marks that aren't as wide as the progress characters
+    fn check_mark_width(&self) {
+        if self.bar_marks.is_empty() {
+            return;
+        }
+
+        for mark in &self.mark_chars {
+            assert_eq!(
+                measure(mark),
+                self.char_width,
+                "got passed bar marks of a different width than the progress characters"
+            );
+        }
+    }
+
+    /// Sets whether the bar fills from the right edge toward the left
+    ///
+    /// The progress characters are used as-is, so for a partially filled cell you may want to pass
+    /// characters that look right when the bar grows to the left.
+    pub fn reverse(mut self, reverse: bool) -> Self {
+        self.reverse = reverse;
+        self
+    }
+
+    /// Colors the filled part of the bar with a gradient across the given color stops
+    ///
+    /// The first color is used at the start of the bar and the last one at its end, so the color
+    /// of the leading edge changes as the bar fills up. Between stops, colors from the 256-color
+    /// palette's color cube (and the basic colors, which are mapped onto it) are interpolated;
+    /// other colors switch over halfway. An empty slice disables the gradient.
+    ///
+    /// Like other styling, the gradient is not rendered if colors are disabled.
+    pub fn bar_gradient(mut self, colors: &[Color]) -> Self {
+        self.gradient = colors.to_vec();
+        self
+    }
+
+    /// Sets whether the bar pulses while the length of the progress bar is unknown
+    ///
+    /// Instead of staying empty, the `bar` and `wide_bar` keys then render a short segment that
+    /// bounces back and forth across the bar, moving one cell per tick. Once a length is set, the
+    /// bar shows the progress as usual.
+    pub fn indeterminate(mut self, indeterminate: bool) -> Self {
+        self.indeterminate = indeterminate;
+        self
+    }
+
+    /// Selects the spinner frame from the elapsed time instead of the number of ticks
+    ///
+    /// With a frame duration, the `spinner` key shows the frame for
+    /// `elapsed / frame_duration` (modulo the number of frames), so the spinner turns at the same
+    /// speed no matter how often the progress bar is updated. The frame still only changes when
+    /// the progress bar is redrawn, e.g. by a [steady tick](crate::ProgressBar::enable_steady_tick).
+    /// A zero duration restores the default of advancing one frame per tick.
+    pub fn tick_frame_duration(mut self, frame_duration: Duration) -> Self {
+        self.tick_frame_duration = Some(frame_duration).filter(|d| !d.is_zero());
+        self
+    }
+
+    /// Sets how long the position has to stay unchanged before the `stall` key is rendered
+    ///
+    /// Defaults to 5 seconds.
+    pub fn stall_threshold(mut self, threshold: Duration) -> Self {
+        self.stall_threshold = threshold;
+        self
+    }
+
+    /// Rounds the shown position down to a multiple of `step`
+    ///
+    /// This applies to the `pos`, `human_pos`, `pos_si`, `bytes`, `decimal_bytes` and
+    /// `binary_bytes` keys, so that e.g. `{bytes}` only advances in whole steps instead of
+    /// flickering digit by digit. It only changes what's shown: the bar, the percentage, the rate
+    /// and the ETA still use the exact position. Once the position reaches the length or the
+    /// progress bar is finished, the exact position is shown. Without a length, the `len`,
+    /// `human_len`, `len_si`, `total_bytes`, `decimal_total_bytes` and `binary_total_bytes` keys
+    /// show the rounded position as well. A step of zero or one shows the exact position
+    /// throughout, which is the default.
+    pub fn position_step(mut self, step: u64) -> Self {
+        self.position_step = step.max(1);
+        self
+    }
+
+    /// Caps the ETA shown by the `eta`, `eta_precise` and `eta_smooth` keys
+    ///
+    /// An ETA longer than `max` is shown as `indicator` instead, e.g. `>99h`, as it's usually the
+    /// result of barely any progress having been made yet rather than a useful estimate. By
+    /// default, and with a zero duration, the ETA is shown however long it is. This only changes
+    /// what's shown, [`ProgressState::eta()`] is left as is.
+    pub fn max_eta(mut self, max: Duration, indicator: &str) -> Self {
+        self.max_eta = Some((max, indicator.into())).filter(|(max, _)| !max.is_zero());
+        self
+    }
+
+    /// Sets the style of the message once the progress bar is finished
+    ///
+    /// This applies to the `msg` and `wide_msg` keys after [`ProgressBar::finish()`] and its
+    /// variants that leave the progress bar on screen, and takes precedence over the style given
+    /// in the template.
+    ///
+    /// [`ProgressBar::finish()`]: crate::ProgressBar::finish
+    pub fn on_finish_style(mut self, style: Style) -> Self {
+        self.finish_style = Some(style);
+        self
+    }
+
+    /// Sets the style of the message once the progress bar is abandoned
+    ///
+    /// Like [`on_finish_style()`](Self::on_finish_style), but after [`ProgressBar::abandon()`]
+    /// or [`ProgressBar::abandon_with_message()`].
+    ///
+    /// [`ProgressBar::abandon()`]: crate::ProgressBar::abandon
+    /// [`ProgressBar::abandon_with_message()`]: crate::ProgressBar::abandon_with_message
+    pub fn on_abandon_style(mut self, style: Style) -> Self {
+        self.abandon_style = Some(style);
+        self
+    }
+
+    /// Sets how the message is fitted to the terminal width
+    ///
+    /// This applies to the `msg` and `wide_msg` keys. Without a [`WrapMode`], `msg` is rendered
+    /// as-is, overflowing the line if it's too long, while `wide_msg` is truncated with `…`. A
+    /// line can only contain a single element that adapts to the terminal width, so `msg` is
+    /// rendered as-is on a line that also contains `wide_bar` or `wide_msg`, or when it has a
+    /// fixed width.
+    pub fn wrap_message(mut self, mode: WrapMode) -> Self {
+        self.wrap_message = Some(mode);
+        self
+    }
+
+    /// Sets the separator between groups of thousands in `human_pos` and `human_len`
+    ///
+    /// Defaults to `,`.
+    pub fn thousands_separator(mut self, separator: char) -> Self {
+        self.thousands_separator = separator;
+        self
+    }
+
+    /// Sets the styles applied to parts of the bar that don't have a style in the template
+    ///
+    /// Styles set inline in the template (like `{msg:.red}` or `{bar:.cyan/blue}`) take
+    /// precedence. Like those, the styles only apply when colors are enabled for the output.
+    pub fn color_scheme(mut self, colors: ColorScheme) -> Self {
+        self.colors = colors;
         self
     }
 
@@ -163,10 +460,56 @@ impl ProgressStyle {
         Ok(self)
     }
 
+    /// Sets the template string for the progress bar, rejecting unknown keys
+    ///
+    /// Unlike [`template()`](Self::template), every placeholder must name a built-in key or a
+    /// key previously registered with [`with_key()`](Self::with_key), so register custom keys
+    /// before calling this. The returned [`TemplateError`] reports the offending byte range.
+    pub fn try_template(mut self, s: &str) -> Result<Self, TemplateError> {
+        let template = Template::from_str_with_tab_width(s, self.tab_width)?;
+        for (part, span) in template.placeholders() {
+            if let TemplatePart::Placeholder { key, .. } = part {
+                if !self.is_known_key(key) {
+                    return Err(TemplateError {
+                        kind: TemplateErrorKind::UnknownKey(key.clone()),
+                        span: span.clone(),
+                    });
+                }
+            }
+        }
+
+        self.template = template;
+        Ok(self)
+    }
+
+    fn is_known_key(&self, key: &str) -> bool {
+        BUILTIN_KEYS.contains(&key)
+            || self.format_map.contains_key(key)
+            || key
+                .strip_prefix("metric:")
+                .map_or(false, |name| !name.is_empty())
+    }
+
+    fn push_grouped(&self, buf: &mut String, n: u64) {
+        let grouped = HumanCount(n).to_string();
+        match self.thousands_separator {
+            ',' => buf.push_str(&grouped),
+            sep => buf.extend(grouped.chars().map(|c| if c == ',' { sep } else { c })),
+        }
+    }
+
     fn current_tick_str(&self, state: &ProgressState) -> &str {
         match state.is_finished() {
             true => self.get_final_tick_str(),
-            false => self.get_tick_str(state.tick),
+            false => self.get_tick_str(self.current_frame(state)),
+        }
+    }
+
+    /// The index of the spinner frame to show, based on the elapsed time if configured
+    fn current_frame(&self, state: &ProgressState) -> u64 {
+        match self.tick_frame_duration {
+            Some(frame) => (state.elapsed().as_nanos() / frame.as_nanos()) as u64,
+            None => state.tick,
         }
     }
 
@@ -180,11 +523,115 @@ impl ProgressStyle {
         &self.tick_strings[self.tick_strings.len() - 1]
     }
 
-    fn format_bar(&self, fract: f32, width: usize, alt_style: Option<&Style>) -> BarDisplay<'_> {
+    fn format_state_bar<'a>(
+        &'a self,
+        state: &'a ProgressState,
+        width: usize,
+        alt_style: Option<&Style>,
+    ) -> BarDisplay<'a> {
+        match self.indeterminate && state.len().is_none() && !state.is_finished() {
+            true => self.format_pulse(state.tick, width, alt_style),
+            false if !state.segments.is_empty() => {
+                self.format_segments(&state.segments, state.len(), width, alt_style)
+            }
+            false => self.format_bar(state.fraction_f64(), width, alt_style),
+        }
+    }
+
+    /// Lays out the segments of a stacked bar by their cumulative fraction of the total
+    fn format_segments<'a>(
+        &'a self,
+        segments: &'a [(String, u64, Style)],
+        len: Option<u64>,
+        width: usize,
+        alt_style: Option<&Style>,
+    ) -> BarDisplay<'a> {
+        let width = self.inner_bar_width(width);
+        let sum = segments
+            .iter()
+            .fold(0u64, |sum, &(_, count, _)| sum.saturating_add(count));
+        let total = len.map_or(sum, |len| len.max(sum));
+
+        // Rounding the end of each region rather than its width makes the regions add up to the
+        // full width if the counts add up to the total
+        let (mut done, mut end) = (0u64, 0usize);
+        let regions = segments
+            .iter()
+            .map(|(_, count, style)| {
+                done = done.saturating_add(*count);
+                let start = end;
+                end = match total {
+                    0 => 0,
+                    total => (done as f64 / total as f64 * width as f64).round() as usize,
+                };
+                (end - start, style)
+            })
+            .collect();
+
+        BarDisplay {
+            chars: &self.progress_chars,
+            lead: 0,
+            filled: 0,
+            cur: None,
+            rest: width - end,
+            delimiters: &self.bar_delimiters,
+            marks: Vec::new(),
+            mark_chars: &self.mark_chars,
+            segments: regions,
+            alt_style: self.empty_style(alt_style),
+            filled_style: self.colors.bar_filled.clone(),
+            reverse: self.reverse,
+            gradient: &self.gradient,
+            width,
+        }
+    }
+
+    /// The width left for the bar's clusters once the delimiters are drawn
+    fn inner_bar_width(&self, width: usize) -> usize {
+        let [left, right] = &self.bar_delimiters;
+        width.saturating_sub(measure_text_width(left) + measure_text_width(right)) / self.char_width
+    }
+
+    fn format_pulse(&self, tick: u64, width: usize, alt_style: Option<&Style>) -> BarDisplay<'_> {
+        let width = self.inner_bar_width(width);
+        let lit = Ord::max(width / 5, 1).min(width);
+        // The segment moves back and forth between the first and the last possible position
+        let travel = (width - lit) as u64;
+        let lead = match travel {
+            0 => 0,
+            _ => match tick % (2 * travel) {
+                phase if phase <= travel => phase,
+                phase => 2 * travel - phase,
+            },
+        } as usize;
+
+        BarDisplay {
+            chars: &self.progress_chars,
+            lead,
+            filled: lit,
+            cur: None,
+            rest: width - lead - lit,
+            delimiters: &self.bar_delimiters,
+            alt_style: self.empty_style(alt_style),
+            filled_style: self.colors.bar_filled.clone(),
+            marks: Vec::new(),
+            mark_chars: &self.mark_chars,
+            segments: Vec::new(),
+            reverse: self.reverse,
+            gradient: &self.gradient,
+            width,
+        }
+    }
+
+    fn empty_style(&self, alt_style: Option<&Style>) -> Style {
+        alt_style.unwrap_or(&self.colors.bar_empty).clone()
+    }
+
+    fn format_bar(&self, fract: f64, width: usize, alt_style: Option<&Style>) -> BarDisplay<'_> {
         // The number of clusters from progress_chars to write (rounding down).
-        let width = width / self.char_width;
+        let width = self.inner_bar_width(width);
         // The number of full clusters (including a fractional component for a partially-full one).
-        let fill = fract * width as f32;
+        let fill = fract * width as f64;
         // The number of entirely full clusters (by truncating `fill`).
         let entirely_filled = fill as usize;
         // 1 if the bar is not entirely empty or full (meaning we need to draw the "current"
@@ -201,7 +648,7 @@ impl ProgressStyle {
             } else {
                 // Pick a fine-grained entry, ranging from the last one (n) if the fractional part
                 // of fill is 0 to the first one (1) if the fractional part of fill is almost 1.
-                n.saturating_sub((fill.fract() * n as f32) as usize)
+                n.saturating_sub((fill.fract() * n as f64) as usize)
             };
             Some(cur_char)
         } else {
@@ -210,16 +657,32 @@ impl ProgressStyle {
 
         // Number of entirely empty clusters needed to fill the bar up to `width`.
         let bg = width.saturating_sub(entirely_filled).saturating_sub(head);
-        let rest = RepeatedStringDisplay {
-            str: &self.progress_chars[self.progress_chars.len() - 1],
-            num: bg,
+
+        // The cells replaced by marks
+        let marks = match width {
+            0 => Vec::new(),
+            _ => self
+                .bar_marks
+                .iter()
+                .map(|&mark| ((mark.clamp(0.0, 1.0) * width as f32) as usize).min(width - 1))
+                .collect(),
         };
 
         BarDisplay {
             chars: &self.progress_chars,
+            lead: 0,
             filled: entirely_filled,
             cur,
-            rest: alt_style.unwrap_or(&Style::new()).apply_to(rest),
+            rest: bg,
+            delimiters: &self.bar_delimiters,
+            alt_style: self.empty_style(alt_style),
+            filled_style: self.colors.bar_filled.clone(),
+            marks,
+            mark_chars: &self.mark_chars,
+            segments: Vec::new(),
+            reverse: self.reverse,
+            gradient: &self.gradient,
+            width,
         }
     }
 
@@ -228,12 +691,28 @@ impl ProgressStyle {
         state: &ProgressState,
         lines: &mut Vec<String>,
         target_width: u16,
+    ) {
+        self.format_state_aligned(state, lines, target_width, None);
+    }
+
+    /// Like `format_state()`, but pads the prefix to at least `prefix_width` columns
+    pub(crate) fn format_state_aligned(
+        &self,
+        state: &ProgressState,
+        lines: &mut Vec<String>,
+        target_width: u16,
+        prefix_width: Option<usize>,
     ) {
         let mut cur = String::new();
         let mut buf = String::new();
         let mut wide = None;
 
         let pos = state.pos();
+        let pos = match state.len() {
+            Some(len) if pos >= len => pos,
+            _ if state.is_finished() => pos,
+            _ => pos - pos % self.position_step,
+        };
         let len = state.len().unwrap_or(pos);
         for part in &self.template.parts {
             match part {
@@ -257,33 +736,71 @@ impl ProgressStyle {
                             "bar" => buf
                                 .write_fmt(format_args!(
                                     "{}",
-                                    self.format_bar(
-                                        state.fraction(),
+                                    self.format_state_bar(
+                                        state,
                                         width.unwrap_or(20) as usize,
                                         alt_style.as_ref(),
                                     )
                                 ))
                                 .unwrap(),
-                            "spinner" => buf.push_str(self.current_tick_str(state)),
+                            "spinner" => {
+                                let tick = self.current_tick_str(state);
+                                buf.push_str(tick);
+                                // Pad narrower frames so the rest of the line doesn't shift
+                                if !state.is_finished() {
+                                    let frames = &self.tick_strings[..self.tick_strings.len() - 1];
+                                    let max = frames
+                                        .iter()
+                                        .map(|frame| measure_text_width(frame))
+                                        .max()
+                                        .unwrap_or(0);
+                                    let pad = max.saturating_sub(measure_text_width(tick));
+                                    buf.extend(std::iter::repeat(' ').take(pad));
+                                }
+                            }
                             "wide_msg" => {
                                 wide = Some(WideElement::Message { align });
                                 buf.push('\x00');
                             }
+                            "msg"
+                                if self.wrap_message.is_some()
+                                    && wide.is_none()
+                                    && width.is_none() =>
+                            {
+                                wide = Some(WideElement::FittedMessage);
+                                buf.push('\x00');
+                            }
                             "msg" => buf.push_str(state.message.expanded()),
-                            "prefix" => buf.push_str(state.prefix.expanded()),
+                            "prefix" => match prefix_width {
+                                Some(prefix_width) => buf
+                                    .write_fmt(format_args!(
+                                        "{}",
+                                        PaddedStringDisplay {
+                                            str: state.prefix.expanded(),
+                                            width: prefix_width,
+                                            align: Alignment::Left,
+                                            truncate: false,
+                                            ellipsis: false,
+                                        }
+                                    ))
+                                    .unwrap(),
+                                None => buf.push_str(state.prefix.expanded()),
+                            },
                             "pos" => buf.write_fmt(format_args!("{pos}")).unwrap(),
-                            "human_pos" => {
-                                buf.write_fmt(format_args!("{}", HumanCount(pos))).unwrap();
-                            }
+                            "human_pos" => self.push_grouped(&mut buf, pos),
                             "len" => buf.write_fmt(format_args!("{len}")).unwrap(),
-                            "human_len" => {
-                                buf.write_fmt(format_args!("{}", HumanCount(len))).unwrap();
-                            }
+                            "human_len" => self.push_grouped(&mut buf, len),
+                            "pos_si" => buf
+                                .write_fmt(format_args!("{}", SiCount(pos as f64)))
+                                .unwrap(),
+                            "len_si" => buf
+                                .write_fmt(format_args!("{}", SiCount(len as f64)))
+                                .unwrap(),
                             "percent" => buf
-                                .write_fmt(format_args!("{:.*}", 0, state.fraction() * 100f32))
+                                .write_fmt(format_args!("{:.*}", 0, state.percent()))
                                 .unwrap(),
                             "percent_precise" => buf
-                                .write_fmt(format_args!("{:.*}", 3, state.fraction() * 100f32))
+                                .write_fmt(format_args!("{:.*}", 3, state.percent()))
                                 .unwrap(),
                             "bytes" => buf.write_fmt(format_args!("{}", HumanBytes(pos))).unwrap(),
                             "total_bytes" => {
@@ -307,9 +824,20 @@ impl ProgressStyle {
                             "elapsed" => buf
                                 .write_fmt(format_args!("{:#}", HumanDuration(state.elapsed())))
                                 .unwrap(),
+                            "stall" => {
+                                let stalled = state.time_since_progress();
+                                if !state.is_finished() && stalled > self.stall_threshold {
+                                    let stalled = HumanDuration(stalled);
+                                    buf.write_fmt(format_args!("stalled {stalled:#}")).unwrap();
+                                }
+                            }
+                            "sparkline" => push_sparkline(&mut buf, &state.rate_history()),
                             "per_sec" => buf
                                 .write_fmt(format_args!("{}/s", HumanFloatCount(state.per_sec())))
                                 .unwrap(),
+                            "per_sec_si" => buf
+                                .write_fmt(format_args!("{}/s", SiCount(state.per_sec())))
+                                .unwrap(),
                             "bytes_per_sec" => buf
                                 .write_fmt(format_args!("{}/s", HumanBytes(state.per_sec() as u64)))
                                 .unwrap(),
@@ -325,22 +853,71 @@ impl ProgressStyle {
                                     BinaryBytes(state.per_sec() as u64)
                                 ))
                                 .unwrap(),
-                            "eta_precise" => buf
-                                .write_fmt(format_args!("{}", FormattedDuration(state.eta())))
-                                .unwrap(),
-                            "eta" => buf
-                                .write_fmt(format_args!("{:#}", HumanDuration(state.eta())))
-                                .unwrap(),
+                            "eta_precise" | "eta" => match (state.eta(), &self.max_eta) {
+                                (eta, Some((max, indicator))) if eta > *max => {
+                                    buf.push_str(indicator);
+                                }
+                                (eta, _) if key == "eta" => {
+                                    buf.write_fmt(format_args!("{:#}", HumanDuration(eta)))
+                                        .unwrap();
+                                }
+                                (eta, _) => {
+                                    buf.write_fmt(format_args!("{}", FormattedDuration(eta)))
+                                        .unwrap();
+                                }
+                            },
+                            "eta_smooth" => match (state.smoothed_eta(), &self.max_eta) {
+                                (eta, Some((max, indicator))) if eta > *max => {
+                                    buf.push_str(indicator);
+                                }
+                                (eta, _) => {
+                                    buf.write_fmt(format_args!("{}", FormattedDuration(eta)))
+                                        .unwrap();
+                                }
+                            },
+                            "elapsed_of_total" => {
+                                let elapsed = state.elapsed();
+                                buf.write_fmt(format_args!("{} / ", FormattedDuration(elapsed)))
+                                    .unwrap();
+                                match state.try_eta() {
+                                    Some(eta) => buf
+                                        .write_fmt(format_args!(
+                                            "{}",
+                                            FormattedDuration(elapsed.saturating_add(eta))
+                                        ))
+                                        .unwrap(),
+                                    None => buf.push('?'),
+                                }
+                            }
                             "duration_precise" => buf
                                 .write_fmt(format_args!("{}", FormattedDuration(state.duration())))
                                 .unwrap(),
                             "duration" => buf
                                 .write_fmt(format_args!("{:#}", HumanDuration(state.duration())))
                                 .unwrap(),
-                            _ => (),
+                            key => {
+                                if let Some(value) = key
+                                    .strip_prefix("metric:")
+                                    .and_then(|name| state.metric(name))
+                                {
+                                    buf.write_fmt(format_args!("{value}")).unwrap();
+                                }
+                            }
                         }
                     };
 
+                    let status_style = match (key.as_str(), state.status()) {
+                        ("msg" | "wide_msg", ProgressStatus::Finished) => {
+                            self.finish_style.as_ref()
+                        }
+                        ("msg" | "wide_msg", ProgressStatus::Abandoned) => {
+                            self.abandon_style.as_ref()
+                        }
+                        _ => None,
+                    };
+                    let style = status_style
+                        .or(style.as_ref())
+                        .or_else(|| self.colors.get(key));
                     match width {
                         Some(width) => {
                             let padded = PaddedStringDisplay {
@@ -348,6 +925,7 @@ impl ProgressStyle {
                                 width: *width as usize,
                                 align: *align,
                                 truncate: *truncate,
+                                ellipsis: false,
                             };
                             match style {
                                 Some(s) => cur
@@ -401,6 +979,54 @@ impl ProgressStyle {
             lines.push(line.to_string());
         }
     }
+
+    /// Fits each line of the message into `width` columns according to the [`WrapMode`]
+    ///
+    /// Lines wrapped by [`WrapMode::Wrap`] after the first one may use the whole `target_width`.
+    fn fit_message(&self, msg: &str, width: usize, target_width: u16) -> String {
+        let mut fitted = String::with_capacity(msg.len());
+        for (i, line) in msg.split('\n').enumerate() {
+            if i > 0 {
+                fitted.push('\n');
+            }
+
+            let width = match i {
+                0 => width,
+                _ => target_width as usize,
+            };
+            match self.wrap_message {
+                Some(WrapMode::Truncate) => fitted.push_str(&truncate_str(line, width, "")),
+                Some(WrapMode::Ellipsis) => fitted.push_str(&truncate_str(line, width, "…")),
+                Some(WrapMode::Wrap) => wrap_line(line, width, target_width as usize, &mut fitted),
+                None => fitted.push_str(line),
+            }
+        }
+        fitted
+    }
+}
+
+/// Hard-wraps `line` into lines of at most `first` columns for the first line and `rest` columns
+/// for the others, keeping escape codes intact
+fn wrap_line(line: &str, first: usize, rest: usize, out: &mut String) {
+    let mut limit = first.max(1);
+    let mut cols = 0;
+    for (part, is_ansi) in AnsiCodeIterator::new(line) {
+        if is_ansi {
+            out.push_str(part);
+            continue;
+        }
+
+        for cluster in segment(part) {
+            let w = measure(&cluster);
+            if cols > 0 && cols + w > limit {
+                out.push('\n');
+                cols = 0;
+                limit = rest.max(1);
+            }
+            out.push_str(&cluster);
+            cols += w;
+        }
+    }
 }
 
 struct TabRewriter<'a>(&'a mut dyn fmt::Write, usize);
@@ -414,8 +1040,14 @@ impl Write for TabRewriter<'_> {
 
 #[derive(Clone, Copy)]
 enum WideElement<'a> {
-    Bar { alt_style: &'a Option<Style> },
-    Message { align: &'a Alignment },
+    Bar {
+        alt_style: &'a Option<Style>,
+    },
+    Message {
+        align: &'a Alignment,
+    },
+    /// The `msg` key with a [`WrapMode`] set, which isn't padded to fill the line
+    FittedMessage,
 }
 
 impl<'a> WideElement<'a> {
@@ -433,9 +1065,43 @@ impl<'a> WideElement<'a> {
                 '\x00',
                 &format!(
                     "{}",
-                    style.format_bar(state.fraction(), left, alt_style.as_ref())
+                    style.format_state_bar(state, left, alt_style.as_ref())
                 ),
             ),
+            WideElement::Message { align } if style.wrap_message.is_some() => {
+                let fitted = style.fit_message(state.message.expanded(), left, width);
+                // Only the first line shares its space with the rest of the template
+                let (first, rest) = match fitted.split_once('\n') {
+                    Some((first, rest)) => (first, Some(rest)),
+                    None => (fitted.as_str(), None),
+                };
+
+                buf.clear();
+                buf.write_fmt(format_args!(
+                    "{}",
+                    PaddedStringDisplay {
+                        str: first,
+                        width: left,
+                        align: *align,
+                        truncate: false,
+                        ellipsis: false,
+                    }
+                ))
+                .unwrap();
+                if cur.as_bytes().last() == Some(&b'\x00') {
+                    buf.truncate(buf.trim_end().len());
+                }
+                if let Some(rest) = rest {
+                    buf.push('\n');
+                    buf.push_str(rest);
+                }
+
+                cur.replace('\x00', buf)
+            }
+            WideElement::FittedMessage => cur.replace(
+                '\x00',
+                &style.fit_message(state.message.expanded(), left, width),
+            ),
             WideElement::Message { align } => {
                 buf.clear();
                 buf.write_fmt(format_args!(
@@ -445,6 +1111,7 @@ impl<'a> WideElement<'a> {
                         width: left,
                         align: *align,
                         truncate: true,
+                        ellipsis: true,
                     }
                 ))
                 .unwrap();
@@ -460,18 +1127,83 @@ impl<'a> WideElement<'a> {
     }
 }
 
+/// Draws one block per rate, scaled to the highest rate
+///
+/// The blocks are right-aligned to the capacity of the rate history, so the sparkline keeps its
+/// width while the history fills up.
+fn push_sparkline(buf: &mut String, rates: &[f64]) {
+    const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let max = rates.iter().copied().fold(0.0, f64::max);
+    let pad = RATE_HISTORY_LEN.saturating_sub(rates.len());
+    buf.extend(std::iter::repeat(' ').take(pad));
+    for &rate in rates {
+        let level = match max > 0.0 {
+            true => (rate / max * (BLOCKS.len() - 1) as f64).round() as usize,
+            false => 0,
+        };
+        buf.push(BLOCKS[level.min(BLOCKS.len() - 1)]);
+    }
+}
+
+/// Keys rendered by [`ProgressStyle::format_state()`] without a registered tracker
+const BUILTIN_KEYS: &[&str] = &[
+    "wide_bar",
+    "bar",
+    "spinner",
+    "wide_msg",
+    "msg",
+    "prefix",
+    "pos",
+    "human_pos",
+    "len",
+    "human_len",
+    "percent",
+    "percent_precise",
+    "bytes",
+    "total_bytes",
+    "decimal_bytes",
+    "decimal_total_bytes",
+    "binary_bytes",
+    "binary_total_bytes",
+    "elapsed_precise",
+    "elapsed",
+    "per_sec",
+    "bytes_per_sec",
+    "decimal_bytes_per_sec",
+    "binary_bytes_per_sec",
+    "eta_precise",
+    "eta",
+    "eta_smooth",
+    "duration_precise",
+    "duration",
+    "elapsed_of_total",
+    "pos_si",
+    "len_si",
+    "per_sec_si",
+    "stall",
+    "sparkline",
+];
+
+/// A template string parsed into its parts, once when the style is created, so drawing only has
+/// to walk the parts
 #[derive(Clone, Debug)]
 struct Template {
     parts: Vec<TemplatePart>,
+    /// Byte ranges of the keys of the placeholders in `parts`, in order
+    key_spans: Vec<Range<usize>>,
 }
 
 impl Template {
     fn from_str_with_tab_width(s: &str, tab_width: usize) -> Result<Self, TemplateError> {
         use State::*;
         let (mut state, mut parts, mut buf) = (Literal, vec![], String::new());
-        for c in s.chars() {
+        let (mut key_spans, mut open, mut key_start) = (vec![], 0, 0);
+        for (i, c) in s.char_indices() {
             let new = match (state, c) {
-                (Literal, '{') => (MaybeOpen, None),
+                (Literal, '{') => {
+                    open = i;
+                    (MaybeOpen, None)
+                }
                 (Literal, '\n') => {
                     if !buf.is_empty() {
                         parts.push(TemplatePart::Literal(TabExpandedString::new(
@@ -500,6 +1232,11 @@ impl Template {
                     (Literal, None)
                 }
                 (MaybeOpen, c) if c != '}' && c != ':' => (Key, Some(c)),
+                // Keys that take an argument, which is kept as part of the key
+                (Key, ':') if buf == "metric" => (Argument, Some(':')),
+                (Argument, c) if c != '}' && c != ':' => (Argument, Some(c)),
+                (Argument, ':') => (Align, None),
+                (Argument, '}') => (Literal, None),
                 (Key, c) if c != '}' && c != ':' => (Key, Some(c)),
                 (Key, ':') => (Align, None),
                 (Key, '}') => (Literal, None),
@@ -512,6 +1249,7 @@ impl Template {
                         style: None,
                         alt_style: None,
                     });
+                    key_spans.push(key_start..i);
                     (Width, None)
                 }
                 (Align, c) if c == '<' || c == '^' || c == '>' => {
@@ -543,14 +1281,25 @@ impl Template {
                 (FirstStyle, c) => (FirstStyle, Some(c)),
                 (AltStyle, '}') => (Literal, None),
                 (AltStyle, c) => (AltStyle, Some(c)),
-                (st, c) => return Err(TemplateError { next: c, state: st }),
+                (st, c) => {
+                    return Err(TemplateError {
+                        kind: TemplateErrorKind::Unexpected { state: st, next: c },
+                        span: i..i + c.len_utf8(),
+                    })
+                }
             };
 
             match (state, new.0) {
-                (MaybeOpen, Key) if !buf.is_empty() => parts.push(TemplatePart::Literal(
-                    TabExpandedString::new(mem::take(&mut buf).into(), tab_width),
-                )),
-                (Key, Align | Literal) if !buf.is_empty() => {
+                (MaybeOpen, Key) => {
+                    key_start = i;
+                    if !buf.is_empty() {
+                        parts.push(TemplatePart::Literal(TabExpandedString::new(
+                            mem::take(&mut buf).into(),
+                            tab_width,
+                        )));
+                    }
+                }
+                (Key | Argument, Align | Literal) if !buf.is_empty() => {
                     parts.push(TemplatePart::Placeholder {
                         key: mem::take(&mut buf),
                         align: Alignment::Left,
@@ -559,6 +1308,7 @@ impl Template {
                         style: None,
                         alt_style: None,
                     });
+                    key_spans.push(key_start..i);
                 }
                 (Width, FirstStyle | Literal) if !buf.is_empty() => {
                     if let Some(TemplatePart::Placeholder { width, .. }) = parts.last_mut() {
@@ -587,14 +1337,55 @@ impl Template {
             }
         }
 
-        if matches!(state, Literal | DoubleClose) && !buf.is_empty() {
+        if !matches!(state, Literal | DoubleClose) {
+            return Err(TemplateError {
+                kind: TemplateErrorKind::Unclosed,
+                span: open..s.len(),
+            });
+        }
+
+        if !buf.is_empty() {
             parts.push(TemplatePart::Literal(TabExpandedString::new(
                 buf.into(),
                 tab_width,
             )));
         }
 
-        Ok(Self { parts })
+        let template = Self { parts, key_spans };
+        template.check_wide_elements()?;
+        Ok(template)
+    }
+
+    /// Only one element per line can fill the remaining space
+    fn check_wide_elements(&self) -> Result<(), TemplateError> {
+        let mut spans = self.key_spans.iter();
+        let mut wide_on_line = false;
+        for part in &self.parts {
+            match part {
+                TemplatePart::NewLine => wide_on_line = false,
+                TemplatePart::Placeholder { key, .. } => {
+                    let span = spans.next();
+                    if !matches!(key.as_str(), "wide_bar" | "wide_msg") {
+                        continue;
+                    } else if wide_on_line {
+                        return Err(TemplateError {
+                            kind: TemplateErrorKind::MultipleWideElements,
+                            span: span.cloned().unwrap_or_default(),
+                        });
+                    }
+                    wide_on_line = true;
+                }
+                TemplatePart::Literal(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn placeholders(&self) -> impl Iterator<Item = (&TemplatePart, &Range<usize>)> {
+        self.parts
+            .iter()
+            .filter(|part| matches!(part, TemplatePart::Placeholder { .. }))
+            .zip(&self.key_spans)
     }
 
     fn from_str(s: &str) -> Result<Self, TemplateError> {
@@ -610,67 +1401,388 @@ impl Template {
     }
 }
 
-#[derive(Debug)]
-pub struct TemplateError {
-    state: State,
-    next: char,
+/// Builds a [`ProgressStyle`], reporting invalid settings as errors
+///
+/// The builder methods on [`ProgressStyle`] itself panic on invalid progress characters or tick
+/// strings, and [`ProgressStyle::template()`] accepts placeholders with unknown keys, which
+/// render as nothing. Here, all of these are checked by [`build()`](Self::build) instead.
+///
+/// ```rust
+/// use indicatif::ProgressStyleBuilder;
+///
+/// let style = ProgressStyleBuilder::new()
+///     .template("{spinner} [{bar:40}] {pos}/{len}")
+///     .progress_chars("=> ")
+///     .build()
+///     .unwrap();
+///
+/// assert!(ProgressStyleBuilder::new().template("{bar} {nope}").build().is_err());
+/// assert!(ProgressStyleBuilder::new().progress_chars("#").build().is_err());
+/// ```
+#[derive(Default)]
+pub struct ProgressStyleBuilder {
+    template: Option<String>,
+    progress_chars: Option<String>,
+    tick_strings: Option<Vec<Box<str>>>,
+    keys: Vec<(&'static str, Box<dyn ProgressTracker>)>,
 }
 
-impl fmt::Display for TemplateError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "TemplateError: unexpected character {:?} in state {:?}",
-            self.next, self.state
-        )
+impl ProgressStyleBuilder {
+    /// Creates a builder that starts out like [`ProgressStyle::default_bar()`]
+    pub fn new() -> Self {
+        Self::default()
     }
-}
 
-impl std::error::Error for TemplateError {}
+    /// Sets the template string, see [`ProgressStyle::try_template()`]
+    pub fn template(mut self, s: &str) -> Self {
+        self.template = Some(s.to_owned());
+        self
+    }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
-enum TemplatePart {
-    Literal(TabExpandedString),
-    Placeholder {
-        key: String,
-        align: Alignment,
-        width: Option<u16>,
-        truncate: bool,
-        style: Option<Style>,
-        alt_style: Option<Style>,
-    },
-    NewLine,
-}
+    /// Sets the progress characters, see [`ProgressStyle::progress_chars()`]
+    pub fn progress_chars(mut self, s: &str) -> Self {
+        self.progress_chars = Some(s.to_owned());
+        self
+    }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
-enum State {
-    Literal,
-    MaybeOpen,
-    DoubleClose,
-    Key,
-    Align,
-    Width,
-    FirstStyle,
-    AltStyle,
-}
+    /// Sets the tick characters, see [`ProgressStyle::tick_chars()`]
+    pub fn tick_chars(mut self, s: &str) -> Self {
+        self.tick_strings = Some(segment(s));
+        self
+    }
+
+    /// Sets the tick strings, see [`ProgressStyle::tick_strings()`]
+    pub fn tick_strings(mut self, s: &[&str]) -> Self {
+        self.tick_strings = Some(s.iter().map(|&s| s.into()).collect());
+        self
+    }
+
+    /// Adds a custom key, see [`ProgressStyle::with_key()`]
+    ///
+    /// Keys added here are known when the template is checked.
+    pub fn with_key<S: ProgressTracker + 'static>(mut self, key: &'static str, f: S) -> Self {
+        self.keys.push((key, Box::new(f)));
+        self
+    }
+
+    /// Checks the settings and builds the style
+    ///
+    /// Fails if the template is malformed or uses an unknown key, if there are fewer than two
+    /// progress characters or tick strings, or if the progress characters differ in width.
+    pub fn build(self) -> Result<ProgressStyle, TemplateError> {
+        let mut style = ProgressStyle::default_bar();
+        for (key, tracker) in self.keys {
+            style.format_map.insert(key, tracker);
+        }
+        if let Some(template) = &self.template {
+            style = style.try_template(template)?;
+        }
+
+        if let Some(chars) = &self.progress_chars {
+            let chars = segment(chars);
+            if chars.len() < 2 {
+                return Err(TemplateError::settings(
+                    TemplateErrorKind::TooFewProgressChars,
+                ));
+            }
+            if chars.iter().any(|c| measure(c) != measure(&chars[0])) {
+                return Err(TemplateError::settings(
+                    TemplateErrorKind::UnequalProgressChars,
+                ));
+            }
+            style.char_width = measure(&chars[0]);
+            style.progress_chars = chars;
+        }
+
+        if let Some(tick_strings) = self.tick_strings {
+            if tick_strings.len() < 2 {
+                return Err(TemplateError::settings(
+                    TemplateErrorKind::TooFewTickStrings,
+                ));
+            }
+            style.tick_strings = tick_strings;
+        }
+
+        Ok(style)
+    }
+}
+
+#[derive(Debug)]
+pub struct TemplateError {
+    kind: TemplateErrorKind,
+    span: Range<usize>,
+}
+
+impl TemplateError {
+    /// Returns the byte range of the template string that caused the error
+    ///
+    /// This is empty for errors in the progress characters or tick strings reported by
+    /// [`ProgressStyleBuilder::build()`].
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    /// An error in a setting other than the template
+    fn settings(kind: TemplateErrorKind) -> Self {
+        Self { kind, span: 0..0 }
+    }
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            TemplateErrorKind::Unexpected { state, next } => write!(
+                f,
+                "TemplateError: unexpected character {:?} in state {:?}",
+                next, state
+            )?,
+            TemplateErrorKind::Unclosed => write!(f, "TemplateError: unclosed placeholder")?,
+            TemplateErrorKind::MultipleWideElements => write!(
+                f,
+                "TemplateError: only one of wide_bar and wide_msg can be used per line"
+            )?,
+            TemplateErrorKind::UnknownKey(key) => {
+                write!(f, "TemplateError: unknown key {:?}", key)?
+            }
+            TemplateErrorKind::TooFewProgressChars => {
+                return write!(f, "TemplateError: at least 2 progress chars required")
+            }
+            TemplateErrorKind::UnequalProgressChars => {
+                return write!(f, "TemplateError: progress chars must have equal widths")
+            }
+            TemplateErrorKind::TooFewTickStrings => {
+                return write!(f, "TemplateError: at least 2 tick strings required")
+            }
+        }
+        write!(f, " at {}..{}", self.span.start, self.span.end)
+    }
+}
+
+#[derive(Debug)]
+enum TemplateErrorKind {
+    Unexpected { state: State, next: char },
+    Unclosed,
+    MultipleWideElements,
+    UnknownKey(String),
+    TooFewProgressChars,
+    UnequalProgressChars,
+    TooFewTickStrings,
+}
+
+impl std::error::Error for TemplateError {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum TemplatePart {
+    Literal(TabExpandedString),
+    Placeholder {
+        key: String,
+        align: Alignment,
+        width: Option<u16>,
+        truncate: bool,
+        style: Option<Style>,
+        alt_style: Option<Style>,
+    },
+    NewLine,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum State {
+    Literal,
+    MaybeOpen,
+    DoubleClose,
+    Key,
+    Argument,
+    Align,
+    Width,
+    FirstStyle,
+    AltStyle,
+}
 
 struct BarDisplay<'a> {
     chars: &'a [Box<str>],
+    /// The number of empty clusters before the filled part
+    lead: usize,
     filled: usize,
     cur: Option<usize>,
-    rest: console::StyledObject<RepeatedStringDisplay<'a>>,
+    /// The number of empty clusters after the filled part
+    rest: usize,
+    delimiters: &'a [Box<str>; 2],
+    /// The indices of the cells replaced by marks, counted in the direction the bar fills
+    marks: Vec<usize>,
+    mark_chars: &'a [Box<str>; 2],
+    /// The width and style of each region of a stacked bar, which replace the filled part
+    segments: Vec<(usize, &'a Style)>,
+    alt_style: Style,
+    filled_style: Style,
+    reverse: bool,
+    gradient: &'a [Color],
+    /// The width of the bar in clusters
+    width: usize,
+}
+
+impl<'a> BarDisplay<'a> {
+    fn fmt_empty(&self, f: &mut fmt::Formatter<'_>, num: usize) -> fmt::Result {
+        let empty = RepeatedStringDisplay {
+            str: &self.chars[self.chars.len() - 1],
+            num,
+        };
+        write!(f, "{}", self.alt_style.apply_to(empty))
+    }
+
+    fn fmt_lead(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.lead {
+            0 => Ok(()),
+            lead => self.fmt_empty(f, lead),
+        }
+    }
+
+    fn fmt_filled(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.gradient.is_empty() {
+            let filled = RepeatedStringDisplay {
+                str: &self.chars[0],
+                num: self.filled,
+            };
+            return write!(f, "{}", self.filled_style.apply_to(filled));
+        }
+
+        for i in 0..self.filled {
+            let idx = self.lead
+                + match self.reverse {
+                    false => i,
+                    true => self.filled - 1 - i,
+                };
+            self.fmt_cell(f, &self.chars[0], idx)?;
+        }
+        Ok(())
+    }
+
+    fn fmt_cur(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.cur {
+            Some(cur) => self.fmt_cell(f, &self.chars[cur], self.lead + self.filled),
+            None => Ok(()),
+        }
+    }
+
+    fn fmt_segments(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.reverse {
+            false => {
+                for &(num, style) in &self.segments {
+                    self.fmt_region(f, num, style)?;
+                }
+                self.fmt_empty(f, self.rest)
+            }
+            true => {
+                self.fmt_empty(f, self.rest)?;
+                for &(num, style) in self.segments.iter().rev() {
+                    self.fmt_region(f, num, style)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn fmt_region(&self, f: &mut fmt::Formatter<'_>, num: usize, style: &Style) -> fmt::Result {
+        let region = RepeatedStringDisplay {
+            str: &self.chars[0],
+            num,
+        };
+        write!(f, "{}", style.apply_to(region))
+    }
+
+    /// Writes the bar cell by cell, replacing the marked cells
+    fn fmt_marked(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let head = self.lead + self.filled;
+        for col in 0..self.width {
+            let idx = match self.reverse {
+                false => col,
+                true => self.width - 1 - col,
+            };
+            let marked = self.marks.contains(&idx);
+            match self.cur {
+                Some(cur) if idx == head => self.fmt_cell(f, &self.chars[cur], idx)?,
+                _ if idx >= self.lead && idx < head => match marked {
+                    true => self.fmt_cell(f, &self.mark_chars[0], idx)?,
+                    false => self.fmt_cell(f, &self.chars[0], idx)?,
+                },
+                _ => {
+                    let empty = match marked {
+                        true => &self.mark_chars[1],
+                        false => &self.chars[self.chars.len() - 1],
+                    };
+                    write!(f, "{}", self.alt_style.apply_to(empty))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn fmt_cell(&self, f: &mut fmt::Formatter<'_>, s: &str, idx: usize) -> fmt::Result {
+        match gradient_color(self.gradient, idx, self.width) {
+            Some(color) => write!(f, "{}", Style::new().fg(color).apply_to(s)),
+            None => write!(f, "{}", self.filled_style.apply_to(s)),
+        }
+    }
 }
 
 impl<'a> fmt::Display for BarDisplay<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        for _ in 0..self.filled {
-            f.write_str(&self.chars[0])?;
-        }
-        if let Some(cur) = self.cur {
-            f.write_str(&self.chars[cur])?;
+        f.write_str(&self.delimiters[0])?;
+        match self.reverse {
+            _ if !self.segments.is_empty() => self.fmt_segments(f)?,
+            _ if !self.marks.is_empty() => self.fmt_marked(f)?,
+            false => {
+                self.fmt_lead(f)?;
+                self.fmt_filled(f)?;
+                self.fmt_cur(f)?;
+                self.fmt_empty(f, self.rest)?;
+            }
+            true => {
+                self.fmt_empty(f, self.rest)?;
+                self.fmt_cur(f)?;
+                self.fmt_filled(f)?;
+                self.fmt_lead(f)?;
+            }
         }
-        self.rest.fmt(f)
+        f.write_str(&self.delimiters[1])
+    }
+}
+
+/// The color of the cell at `idx` of a bar `width` clusters wide with the given gradient
+fn gradient_color(stops: &[Color], idx: usize, width: usize) -> Option<Color> {
+    let (&first, rest) = stops.split_first()?;
+    if rest.is_empty() || width < 2 {
+        return Some(first);
     }
+
+    let t = idx.min(width - 1) as f32 / (width - 1) as f32 * rest.len() as f32;
+    let i = (t as usize).min(rest.len() - 1);
+    let (from, to) = (stops[i], stops[i + 1]);
+    let frac = t - i as f32;
+
+    let lerp = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * frac).round() as u8;
+    Some(match (color_cube(from), color_cube(to)) {
+        (Some((r1, g1, b1)), Some((r2, g2, b2))) => {
+            Color::Color256(16 + 36 * lerp(r1, r2) + 6 * lerp(g1, g2) + lerp(b1, b2))
+        }
+        _ if frac < 0.5 => from,
+        _ => to,
+    })
+}
+
+/// The coordinates of a color in the 6x6x6 color cube of the 256-color palette
+fn color_cube(color: Color) -> Option<(u8, u8, u8)> {
+    Some(match color {
+        Color::Black => (0, 0, 0),
+        Color::Red => (5, 0, 0),
+        Color::Green => (0, 5, 0),
+        Color::Yellow => (5, 5, 0),
+        Color::Blue => (0, 0, 5),
+        Color::Magenta => (5, 0, 5),
+        Color::Cyan => (0, 5, 5),
+        Color::White => (5, 5, 5),
+        Color::Color256(n @ 16..=231) => ((n - 16) / 36, (n - 16) / 6 % 6, (n - 16) % 6),
+        Color::Color256(_) => return None,
+    })
 }
 
 struct RepeatedStringDisplay<'a> {
@@ -692,6 +1804,8 @@ struct PaddedStringDisplay<'a> {
     width: usize,
     align: Alignment,
     truncate: bool,
+    /// Marks the truncated side(s) with `…`
+    ellipsis: bool,
 }
 
 impl<'a> fmt::Display for PaddedStringDisplay<'a> {
@@ -701,16 +1815,7 @@ impl<'a> fmt::Display for PaddedStringDisplay<'a> {
         if excess > 0 && !self.truncate {
             return f.write_str(self.str);
         } else if excess > 0 {
-            let (start, end) = match self.align {
-                Alignment::Left => (0, self.str.len() - excess),
-                Alignment::Right => (excess, self.str.len()),
-                Alignment::Center => (
-                    excess / 2,
-                    self.str.len() - excess.saturating_sub(excess / 2),
-                ),
-            };
-
-            return f.write_str(self.str.get(start..end).unwrap_or(self.str));
+            return self.fmt_truncated(f, cols);
         }
 
         let diff = self.width.saturating_sub(cols);
@@ -731,6 +1836,130 @@ impl<'a> fmt::Display for PaddedStringDisplay<'a> {
     }
 }
 
+impl PaddedStringDisplay<'_> {
+    /// Drops whole grapheme clusters until the string fits in `width` columns
+    ///
+    /// ANSI escape codes are kept. Wide clusters can leave a column free, which is padded so the
+    /// result is exactly `width` columns wide. With `ellipsis`, each side that lost clusters ends
+    /// in `…` instead.
+    fn fmt_truncated(&self, f: &mut fmt::Formatter<'_>, mut cols: usize) -> fmt::Result {
+        // Centered strings lose clusters on both sides, so they need room for two ellipses
+        let marks = match (self.ellipsis, self.align) {
+            (false, _) => 0,
+            (true, Alignment::Center) => 2,
+            (true, _) => 1,
+        };
+        let marks = if marks <= self.width { marks } else { 0 };
+        let width = self.width - marks;
+
+        // Clusters with their width, or `None` for escape codes
+        let mut clusters = Vec::new();
+        for (part, is_ansi) in AnsiCodeIterator::new(self.str) {
+            match is_ansi {
+                true => clusters.push((Box::from(part), None)),
+                false => clusters.extend(segment(part).into_iter().map(|c| {
+                    let w = measure(&c);
+                    (c, Some(w))
+                })),
+            }
+        }
+
+        let text = (0..clusters.len())
+            .filter(|&i| clusters[i].1.is_some())
+            .collect::<Vec<_>>();
+        let mut keep = vec![true; clusters.len()];
+        let (mut start, mut end) = (0, text.len());
+        let (mut dropped_start, mut dropped_end) = (0, 0);
+        while cols > width && start < end {
+            let from_start = match self.align {
+                Alignment::Left => false,
+                Alignment::Right => true,
+                Alignment::Center => dropped_start < dropped_end,
+            };
+
+            let idx = match from_start {
+                true => {
+                    start += 1;
+                    text[start - 1]
+                }
+                false => {
+                    end -= 1;
+                    text[end]
+                }
+            };
+            let w = clusters[idx].1.unwrap_or(0);
+            match from_start {
+                true => dropped_start += w,
+                false => dropped_end += w,
+            }
+            keep[idx] = false;
+            cols = cols.saturating_sub(w);
+        }
+
+        let ellipsis = |dropped: usize| match marks > 0 && dropped > 0 {
+            true => "…",
+            false => "",
+        };
+        let (before, after) = (ellipsis(dropped_start), ellipsis(dropped_end));
+        let marked = usize::from(!before.is_empty()) + usize::from(!after.is_empty());
+        let pad = self.width.saturating_sub(cols + marked);
+        if let Alignment::Right = self.align {
+            for _ in 0..pad {
+                f.write_char(' ')?;
+            }
+        }
+        f.write_str(before)?;
+        for ((cluster, _), _) in clusters.iter().zip(&keep).filter(|(_, &keep)| keep) {
+            f.write_str(cluster)?;
+        }
+        f.write_str(after)?;
+        if !matches!(self.align, Alignment::Right) {
+            for _ in 0..pad {
+                f.write_char(' ')?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Styles for parts of a progress bar, set with [`ProgressStyle::color_scheme()`]
+///
+/// All styles default to plain text.
+#[derive(Clone, Debug, Default)]
+pub struct ColorScheme {
+    /// The filled part of `bar` and `wide_bar`, including the current character
+    pub bar_filled: Style,
+    /// The empty part of `bar` and `wide_bar`
+    pub bar_empty: Style,
+    /// The `msg` and `wide_msg` keys
+    pub message: Style,
+    /// The `prefix` key
+    pub prefix: Style,
+}
+
+impl ColorScheme {
+    fn get(&self, key: &str) -> Option<&Style> {
+        match key {
+            "msg" | "wide_msg" => Some(&self.message),
+            "prefix" => Some(&self.prefix),
+            _ => None,
+        }
+    }
+}
+
+/// How a message that doesn't fit the terminal width is rendered
+///
+/// See [`ProgressStyle::wrap_message()`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WrapMode {
+    /// Cut off the message at the terminal width
+    Truncate,
+    /// Cut off the message and end it with `…`
+    Ellipsis,
+    /// Continue the message on the following lines
+    Wrap,
+}
+
 #[derive(PartialEq, Eq, Debug, Copy, Clone)]
 enum Alignment {
     Left,
@@ -775,6 +2004,7 @@ where
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashSet;
     use std::sync::Arc;
 
     use super::*;
@@ -909,19 +2139,19 @@ mod tests {
         let style = ProgressStyle::with_template("{wide_msg}").unwrap();
         state.message = TabExpandedString::NoTabs("abcdefghijklmnopqrst".into());
         style.format_state(&state, &mut buf, WIDTH);
-        assert_eq!(&buf[0], "abcdefghij");
+        assert_eq!(&buf[0], "abcdefghi…");
 
         buf.clear();
         let style = ProgressStyle::with_template("{wide_msg:>}").unwrap();
         state.message = TabExpandedString::NoTabs("abcdefghijklmnopqrst".into());
         style.format_state(&state, &mut buf, WIDTH);
-        assert_eq!(&buf[0], "klmnopqrst");
+        assert_eq!(&buf[0], "…lmnopqrst");
 
         buf.clear();
         let style = ProgressStyle::with_template("{wide_msg:^}").unwrap();
         state.message = TabExpandedString::NoTabs("abcdefghijklmnopqrst".into());
         style.format_state(&state, &mut buf, WIDTH);
-        assert_eq!(&buf[0], "fghijklmno");
+        assert_eq!(&buf[0], "…ghijklmn…");
     }
 
     #[test]
@@ -996,4 +2226,985 @@ mod tests {
         assert_eq!(&buf[2], "bar");
         assert_eq!(&buf[3], "baz");
     }
+
+    #[test]
+    fn eta_smooth_key() {
+        const WIDTH: u16 = 80;
+        let pos = Arc::new(AtomicPosition::new());
+        let state = ProgressState::new(Some(10), pos);
+        let mut buf = Vec::new();
+
+        let style = ProgressStyle::with_template("{eta_smooth}").unwrap();
+        style.format_state(&state, &mut buf, WIDTH);
+        assert_eq!(&buf[0], "00:00:00");
+    }
+
+    #[test]
+    fn reversed_bar() {
+        let style = ProgressStyle::default_bar().progress_chars("#>-");
+        assert_eq!(style.format_bar(0.25, 8, None).to_string(), "##>-----");
+        assert_eq!(style.format_bar(1.0, 8, None).to_string(), "########");
+
+        let style = style.reverse(true);
+        assert_eq!(style.format_bar(0.25, 8, None).to_string(), "----->##");
+        assert_eq!(style.format_bar(0.0, 8, None).to_string(), "--------");
+        assert_eq!(style.format_bar(1.0, 8, None).to_string(), "########");
+
+        // Partially filled cells are still rendered next to the filled part
+        let style = ProgressStyle::default_bar()
+            .progress_chars("#21-")
+            .reverse(true);
+        assert_eq!(style.format_bar(0.3, 5, None).to_string(), "---2#");
+    }
+
+    #[test]
+    fn fine_grained_progress_chars() {
+        let style = ProgressStyle::default_bar().progress_chars("█▉▊▋▌▍▎▏ ");
+        assert_eq!(style.format_bar(0.55, 10, None).to_string(), "█████▌    ");
+        assert_eq!(style.format_bar(0.5125, 10, None).to_string(), "█████▏    ");
+        assert_eq!(style.format_bar(0.5875, 10, None).to_string(), "█████▉    ");
+        // The emptiest partial character marks where the fill ends, even on a cell boundary
+        assert_eq!(style.format_bar(0.5, 10, None).to_string(), "█████▏    ");
+        assert_eq!(style.format_bar(1.0, 10, None).to_string(), "██████████");
+    }
+
+    #[test]
+    fn bar_gradient() {
+        let style = ProgressStyle::default_bar()
+            .progress_chars("#>-")
+            .bar_gradient(&[Color::Red, Color::Green]);
+
+        set_colors_enabled(true);
+        let rendered = style.format_bar(1.0, 6, None).to_string();
+        let colors = rendered
+            .split('#')
+            .filter(|s| s.contains("\x1b[38;5;"))
+            .collect::<HashSet<_>>();
+        assert!(colors.len() > 1, "{rendered:?}");
+        assert!(rendered.starts_with("\x1b[38;5;196m#"));
+        assert!(rendered.contains("\x1b[38;5;46m#"));
+
+        // Without styling, the plain characters are left
+        let plain = |style: &ProgressStyle| {
+            console::strip_ansi_codes(&style.format_bar(0.5, 6, None).to_string()).into_owned()
+        };
+        assert_eq!(plain(&style), "###>--");
+        assert_eq!(plain(&style.clone().reverse(true)), "-->###");
+    }
+
+    #[test]
+    fn gradient_color_stops() {
+        let stops = [Color::Red, Color::Green];
+        assert_eq!(gradient_color(&stops, 0, 11), Some(Color::Color256(196)));
+        assert_eq!(
+            gradient_color(&stops, 5, 11),
+            Some(Color::Color256(16 + 36 * 3 + 6 * 3))
+        );
+        assert_eq!(gradient_color(&stops, 10, 11), Some(Color::Color256(46)));
+        assert_eq!(gradient_color(&[], 0, 11), None);
+        assert_eq!(gradient_color(&[Color::Blue], 7, 11), Some(Color::Blue));
+        assert_eq!(
+            gradient_color(&[Color::Color256(0), Color::Red], 4, 11),
+            Some(Color::Color256(0))
+        );
+    }
+
+    #[test]
+    fn indeterminate_pulse() {
+        const WIDTH: u16 = 80;
+        let pos = Arc::new(AtomicPosition::new());
+        let mut state = ProgressState::new(None, pos.clone());
+        let style = ProgressStyle::with_template("{bar:10}")
+            .unwrap()
+            .progress_chars("#>-")
+            .indeterminate(true);
+
+        let render = |state: &ProgressState| {
+            let mut buf = Vec::new();
+            style.format_state(state, &mut buf, WIDTH);
+            buf.remove(0)
+        };
+
+        let frames = (0..10)
+            .map(|tick| {
+                state.tick = tick;
+                render(&state)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(frames[0], "##--------");
+        assert_eq!(frames[1], "-##-------");
+        assert_ne!(frames[0], frames[1]);
+        assert_eq!(frames[8], "--------##");
+        assert_eq!(frames[9], "-------##-");
+
+        // With a known length, the bar shows the actual progress
+        pos.set(5);
+        assert_eq!(render(&ProgressState::new(Some(10), pos)), "#####>----");
+    }
+
+    #[test]
+    fn wrap_message_modes() {
+        const WIDTH: u16 = 10;
+        let pos = Arc::new(AtomicPosition::new());
+        let mut state = ProgressState::new(Some(10), pos);
+        state.message = TabExpandedString::new("abcdefghijklmnopqrst".into(), 2);
+
+        let render = |state: &ProgressState, template: &str, mode: Option<WrapMode>| {
+            let mut style = ProgressStyle::with_template(template).unwrap();
+            if let Some(mode) = mode {
+                style = style.wrap_message(mode);
+            }
+            let mut buf = Vec::new();
+            style.format_state(state, &mut buf, WIDTH);
+            buf
+        };
+
+        assert_eq!(render(&state, "> {msg}", None), ["> abcdefghijklmnopqrst"]);
+        assert_eq!(
+            render(&state, "> {msg}", Some(WrapMode::Truncate)),
+            ["> abcdefgh"]
+        );
+        assert_eq!(
+            render(&state, "> {msg} <", Some(WrapMode::Ellipsis)),
+            ["> abcde… <"]
+        );
+        assert_eq!(
+            render(&state, "> {msg}", Some(WrapMode::Wrap)),
+            ["> abcdefgh", "ijklmnopqr", "st"]
+        );
+        assert_eq!(
+            render(&state, "> {wide_msg:>}", Some(WrapMode::Wrap)),
+            ["> abcdefgh", "ijklmnopqr", "st"]
+        );
+        assert_eq!(
+            render(&state, "> {wide_msg}", Some(WrapMode::Ellipsis)),
+            ["> abcdefg…"]
+        );
+
+        state.message = TabExpandedString::new("abc".into(), 2);
+        assert_eq!(
+            render(&state, "> {wide_msg:>} <", Some(WrapMode::Wrap)),
+            [">    abc <"]
+        );
+
+        // Wide characters take up two columns
+        state.message = TabExpandedString::new("ａｂｃｄｅ".into(), 2);
+        assert_eq!(
+            render(&state, "{msg}", Some(WrapMode::Wrap)),
+            ["ａｂｃｄｅ"]
+        );
+    }
+
+    #[test]
+    fn metric_keys() {
+        const WIDTH: u16 = 80;
+        let pos = Arc::new(AtomicPosition::new());
+        let mut state = ProgressState::new(Some(10), pos);
+        state.metrics.insert("cap".into(), 1000.0);
+        state.metrics.insert("used".into(), 12.5);
+
+        let style =
+            ProgressStyle::with_template("{metric:cap} {metric:used:>6} [{metric:unknown}]")
+                .unwrap();
+        let mut buf = Vec::new();
+        style.format_state(&state, &mut buf, WIDTH);
+        assert_eq!(buf, ["1000   12.5 []"]);
+
+        // Derived values can be computed by a custom key
+        let style = ProgressStyle::with_template("{utilization}%")
+            .unwrap()
+            .with_key("utilization", |state: &ProgressState, w: &mut dyn Write| {
+                if let (Some(used), Some(cap)) = (state.metric("used"), state.metric("cap")) {
+                    let _ = write!(w, "{:.2}", used / cap * 100.0);
+                }
+            });
+        buf.clear();
+        style.format_state(&state, &mut buf, WIDTH);
+        assert_eq!(buf, ["1.25%"]);
+    }
+
+    #[test]
+    fn closure_key() {
+        const WIDTH: u16 = 80;
+        let pos = Arc::new(AtomicPosition::new());
+        pos.set(21);
+        let state = ProgressState::new(Some(100), pos);
+
+        let style = ProgressStyle::with_template("{pos} {double} {unknown}")
+            .unwrap()
+            .with_key("double", |state: &ProgressState, w: &mut dyn Write| {
+                let _ = write!(w, "{}", state.pos() * 2);
+            });
+        let mut buf = Vec::new();
+        style.format_state(&state, &mut buf, WIDTH);
+        assert_eq!(buf, ["21 42 "]);
+    }
+
+    #[test]
+    fn width_and_alignment_specs() {
+        const WIDTH: u16 = 80;
+        let pos = Arc::new(AtomicPosition::new());
+        pos.set(9);
+        let mut state = ProgressState::new(Some(100), pos.clone());
+        state.message = TabExpandedString::NoTabs("abcdefghij".into());
+        let mut buf = Vec::new();
+
+        let style = ProgressStyle::with_template("[{pos:>6}] [{percent:3}] [{msg:5!}]").unwrap();
+        style.format_state(&state, &mut buf, WIDTH);
+        assert_eq!(buf, ["[     9] [9  ] [abcde]"]);
+
+        // The columns don't move as the values grow
+        pos.set(10);
+        buf.clear();
+        style.format_state(&state, &mut buf, WIDTH);
+        assert_eq!(buf, ["[    10] [10 ] [abcde]"]);
+
+        // Malformed specs are rejected when the template is parsed
+        assert!(ProgressStyle::with_template("{pos:>x}").is_err());
+        assert!(ProgressStyle::with_template("{pos::}").is_err());
+    }
+
+    #[test]
+    fn try_template_validates_keys() {
+        let err = ProgressStyle::default_bar()
+            .try_template("{bar} {eeta}")
+            .err()
+            .unwrap();
+        assert_eq!(err.span(), 7..11);
+        assert_eq!(
+            err.to_string(),
+            "TemplateError: unknown key \"eeta\" at 7..11"
+        );
+
+        let err = ProgressStyle::default_bar()
+            .try_template("[{pos}/{len")
+            .err()
+            .unwrap();
+        assert_eq!(err.span(), 7..11);
+        assert!(ProgressStyle::with_template("{msg:>5").is_err());
+
+        // Built-in, argument and registered keys are all accepted
+        assert!(ProgressStyle::default_bar()
+            .with_key("foo", |_: &ProgressState, _: &mut dyn Write| {})
+            .try_template("{{{spinner:.green}}} {pos:>6} {metric:rows} {eta_smooth} {foo}")
+            .is_ok());
+        assert!(ProgressStyle::default_bar()
+            .try_template("{metric:}")
+            .is_err());
+        assert!(ProgressStyle::default_bar()
+            .try_template("{pos_si}/{len_si} {per_sec_si} {stall} {sparkline}")
+            .is_ok());
+    }
+
+    #[test]
+    fn documented_keys_are_builtin() {
+        // Every key in the list of template keys in the crate docs is known to `try_template()`
+        let keys = include_str!("lib.rs")
+            .lines()
+            .filter_map(|line| line.strip_prefix("//! * `"))
+            .take_while(|line| !line.starts_with("metric:"))
+            .flat_map(|line| line.split(':').next().unwrap().split('`').step_by(2))
+            .filter(|key| !key.is_empty())
+            .collect::<Vec<_>>();
+        assert!(keys.contains(&"stall"));
+        for key in keys {
+            assert!(BUILTIN_KEYS.contains(&key), "{key} is not a built-in key");
+        }
+    }
+
+    #[test]
+    fn multi_codepoint_tick_strings() {
+        const WIDTH: u16 = 80;
+        let pos = Arc::new(AtomicPosition::new());
+        let mut state = ProgressState::new(None, pos);
+        let mut buf = Vec::new();
+
+        let frames = ["e\u{301}", "\u{4e2d}", "o\u{308}", "x"];
+        let style = ProgressStyle::with_template("{spinner}|")
+            .unwrap()
+            .tick_strings(&frames);
+        for tick in 0..6 {
+            state.tick = tick;
+            buf.clear();
+            style.format_state(&state, &mut buf, WIDTH);
+            let frame = frames[tick as usize % 3];
+            assert_eq!(style.get_tick_str(tick), frame);
+            assert!(buf[0].starts_with(frame));
+            assert_eq!(measure_text_width(&buf[0]), 3);
+        }
+    }
+
+    #[test]
+    fn tick_frame_from_elapsed_time() {
+        use crate::ProgressBar;
+
+        const WIDTH: u16 = 80;
+        let time = Arc::new(Mutex::new(Instant::now()));
+        let clock = time.clone();
+        let pb = ProgressBar::hidden().with_clock(move || *clock.lock().unwrap());
+        let started = *time.lock().unwrap();
+        let mut buf = Vec::new();
+
+        let style = ProgressStyle::with_template("{spinner}")
+            .unwrap()
+            .tick_chars("abcd ")
+            .tick_frame_duration(Duration::from_millis(100));
+        for (elapsed_ms, tick) in [(0, 7), (99, 0), (100, 0), (250, 1), (399, 2), (1234, 9)] {
+            *time.lock().unwrap() = started + Duration::from_millis(elapsed_ms);
+            let mut state = pb.state();
+            state.state.tick = tick;
+            buf.clear();
+            style.format_state(&state.state, &mut buf, WIDTH);
+            drop(state);
+            let frame = (elapsed_ms / 100 % 4) as usize;
+            assert_eq!(buf[0], ["a", "b", "c", "d"][frame], "{elapsed_ms}ms");
+        }
+
+        // A zero duration goes back to one frame per tick
+        let style = style.tick_frame_duration(Duration::ZERO);
+        let mut state = pb.state();
+        state.state.tick = 1;
+        buf.clear();
+        style.format_state(&state.state, &mut buf, WIDTH);
+        drop(state);
+        assert_eq!(buf[0], "b");
+    }
+
+    #[cfg(feature = "unicode-segmentation")]
+    #[test]
+    fn tick_chars_splits_grapheme_clusters() {
+        let style = ProgressStyle::default_spinner().tick_chars("e\u{301}\u{4e2d}o\u{308}x");
+        assert_eq!(style.tick_strings.len(), 4);
+        assert_eq!(style.get_tick_str(0), "e\u{301}");
+        assert_eq!(style.get_tick_str(2), "o\u{308}");
+        assert_eq!(style.get_final_tick_str(), "x");
+    }
+
+    #[test]
+    fn si_count_keys() {
+        const WIDTH: u16 = 80;
+        let pos = Arc::new(AtomicPosition::new());
+        pos.set(1_500);
+        let mut state = ProgressState::new(Some(2_300_000), pos);
+        state.fixed_rate = Some(999.0);
+        let mut buf = Vec::new();
+
+        let style = ProgressStyle::with_template("{pos_si}/{len_si} {per_sec_si}").unwrap();
+        style.format_state(&state, &mut buf, WIDTH);
+        assert_eq!(&buf[0], "1.5k/2.3M 999/s");
+    }
+
+    #[test]
+    fn sparkline() {
+        use crate::ProgressBar;
+
+        let time = Arc::new(Mutex::new(Instant::now()));
+        let clock = time.clone();
+        let pb = ProgressBar::hidden()
+            .with_clock(move || *clock.lock().unwrap())
+            .with_style(ProgressStyle::with_template("[{sparkline}]").unwrap());
+        let update = |steps| {
+            *time.lock().unwrap() += Duration::from_secs(1);
+            pb.inc(steps);
+            pb.tick();
+        };
+
+        // The history isn't full yet, so the sparkline is padded to its full width
+        for steps in [2, 4, 8] {
+            update(steps);
+        }
+        assert_eq!(pb.state().state.rate_history(), [2.0, 4.0, 8.0]);
+        assert_eq!(pb.render_line(), format!("[{}▃▅█]", " ".repeat(13)));
+
+        for steps in 1..=20 {
+            update(steps);
+        }
+        let history = pb.state().state.rate_history();
+        assert_eq!(history.len(), 16);
+        assert_eq!(history[0], 5.0);
+        assert_eq!(history[15], 20.0);
+        let line = pb.render_line();
+        let blocks = line.trim_matches(|c| c == '[' || c == ']');
+        assert_eq!(blocks.chars().count(), 16);
+        assert!(blocks.chars().all(|c| ('▁'..='█').contains(&c)), "{line}");
+        assert!(blocks.ends_with('█'));
+    }
+
+    #[test]
+    fn elapsed_of_total() {
+        let start = Instant::now();
+        let now = start + Duration::from_secs(42);
+        let clock = Arc::new(Mutex::new(start));
+        let bar = || {
+            let clock = clock.clone();
+            let pb = crate::ProgressBar::hidden().with_clock(move || *clock.lock().unwrap());
+            pb.set_style(ProgressStyle::with_template("{elapsed_of_total}").unwrap());
+            pb
+        };
+
+        let (pb, counter) = (bar(), bar());
+        pb.set_length(130);
+        *clock.lock().unwrap() = now;
+        pb.set_position(42);
+        pb.set_fixed_rate(Some(1.0));
+        assert_eq!(pb.render_line_with_width(80), "00:00:42 / 00:02:10");
+
+        counter.set_position(42);
+        assert_eq!(counter.render_line_with_width(80), "00:00:42 / ?");
+    }
+
+    #[test]
+    fn max_eta() {
+        const WIDTH: u16 = 80;
+        let pos = Arc::new(AtomicPosition::new());
+        pos.set(1);
+        let mut state = ProgressState::new(Some(1_000_000), pos);
+        state.fixed_rate = Some(0.001);
+        let mut buf = Vec::new();
+
+        // The ETA isn't capped by default
+        let style = ProgressStyle::with_template("{eta} {eta_precise}").unwrap();
+        style.format_state(&state, &mut buf, WIDTH);
+        assert_eq!(buf, ["32y 11574d 01:30:00"]);
+
+        buf.clear();
+        let style = ProgressStyle::with_template("{eta} {eta_precise} {eta_smooth}")
+            .unwrap()
+            .max_eta(Duration::from_secs(99 * 3600), ">99h");
+        style.format_state(&state, &mut buf, WIDTH);
+        assert_eq!(buf, [">99h >99h >99h"]);
+        // The ETA itself isn't capped
+        assert!(state.eta() > Duration::from_secs(365 * 24 * 3600));
+
+        buf.clear();
+        let style = style.max_eta(Duration::from_secs(3600), "∞");
+        style.format_state(&state, &mut buf, WIDTH);
+        assert_eq!(buf, ["∞ ∞ ∞"]);
+
+        buf.clear();
+        let style = style.max_eta(Duration::ZERO, "∞");
+        style.format_state(&state, &mut buf, WIDTH);
+        assert_eq!(buf, ["32y 11574d 01:30:00 11574d 01:30:00"]);
+
+        // ETAs below the cap are shown as usual
+        buf.clear();
+        state.fixed_rate = Some(999_999.0 / 90.0);
+        let style = style.max_eta(Duration::from_secs(3600), "∞");
+        style.format_state(&state, &mut buf, WIDTH);
+        assert_eq!(buf, ["2m 00:01:30 00:01:30"]);
+    }
+
+    #[test]
+    fn finish_and_abandon_styles() {
+        use crate::ProgressBar;
+
+        let style = ProgressStyle::with_template("{msg}")
+            .unwrap()
+            .on_finish_style(Style::new().green().force_styling(true))
+            .on_abandon_style(Style::new().red().force_styling(true));
+        let render = |finish: fn(&ProgressBar)| {
+            let pb = ProgressBar::hidden()
+                .with_style(style.clone())
+                .with_message("msg");
+            finish(&pb);
+            pb.render_line_with_width(80)
+        };
+
+        assert_eq!(render(|_| {}), "msg");
+        assert_eq!(render(|pb| pb.finish()), "\u{1b}[32mmsg\u{1b}[0m");
+        assert_eq!(render(|pb| pb.abandon()), "\u{1b}[31mmsg\u{1b}[0m");
+        assert_eq!(
+            render(|pb| pb.abandon_with_message("failed")),
+            "\u{1b}[31mfailed\u{1b}[0m"
+        );
+    }
+
+    #[test]
+    fn position_step() {
+        use crate::{ProgressBar, ProgressDrawTarget};
+
+        const WIDTH: u16 = 80;
+        let pos = Arc::new(AtomicPosition::new());
+        pos.set(3_500_000);
+        let state = ProgressState::new(Some(5_000_000), pos.clone());
+        let mut buf = Vec::new();
+
+        let style = ProgressStyle::with_template("{pos}/{len} {binary_bytes} {percent}%")
+            .unwrap()
+            .position_step(1024 * 1024);
+        style.format_state(&state, &mut buf, WIDTH);
+        assert_eq!(buf, ["3145728/5000000 3.00 MiB 70%"]);
+
+        // The exact total is shown once the position reaches it
+        buf.clear();
+        pos.set(5_000_000);
+        style.format_state(&state, &mut buf, WIDTH);
+        assert_eq!(buf, ["5000000/5000000 4.77 MiB 100%"]);
+
+        // Without a length, the length is the rounded position
+        buf.clear();
+        pos.set(3_500_000);
+        let unbounded = ProgressState::new(None, pos.clone());
+        style.format_state(&unbounded, &mut buf, WIDTH);
+        assert_eq!(buf, ["3145728/3145728 3.00 MiB 0%"]);
+
+        // As is the exact position once the progress bar is finished
+        let (target, buffer) = ProgressDrawTarget::string_buffer();
+        let pb = ProgressBar::with_draw_target(Some(5_000_000), target).with_style(style);
+        pb.set_position(4_000_000);
+        pb.tick();
+        assert_eq!(*buffer.lock().unwrap(), "3145728/5000000 3.00 MiB 80%");
+        pb.abandon();
+        assert_eq!(*buffer.lock().unwrap(), "4000000/5000000 3.81 MiB 80%");
+    }
+
+    #[test]
+    fn thousands_separator() {
+        const WIDTH: u16 = 80;
+        let pos = Arc::new(AtomicPosition::new());
+        pos.set(1234567);
+        let state = ProgressState::new(Some(10485760), pos);
+        let mut buf = Vec::new();
+
+        let style = ProgressStyle::with_template("{human_pos}/{human_len}").unwrap();
+        style.format_state(&state, &mut buf, WIDTH);
+        assert_eq!(buf, ["1,234,567/10,485,760"]);
+
+        buf.clear();
+        let style = style.thousands_separator(' ');
+        style.format_state(&state, &mut buf, WIDTH);
+        assert_eq!(buf, ["1 234 567/10 485 760"]);
+
+        buf.clear();
+        let style = style.thousands_separator('.');
+        style.format_state(&state, &mut buf, WIDTH);
+        assert_eq!(buf, ["1.234.567/10.485.760"]);
+    }
+
+    #[test]
+    fn truncation_respects_display_width() {
+        let fit = |s: &str, width: usize, align: Alignment| {
+            PaddedStringDisplay {
+                str: s,
+                width,
+                align,
+                truncate: true,
+                ellipsis: false,
+            }
+            .to_string()
+        };
+
+        // Each CJK character is two columns wide and is never split
+        let cjk = "\u{4e2d}\u{6587}\u{5b57}\u{7b26}";
+        assert_eq!(fit(cjk, 5, Alignment::Left), "\u{4e2d}\u{6587} ");
+        assert_eq!(fit(cjk, 5, Alignment::Right), " \u{5b57}\u{7b26}");
+        assert_eq!(fit(cjk, 4, Alignment::Center), "\u{6587}\u{5b57}");
+        for width in 0..8 {
+            assert_eq!(measure_text_width(&fit(cjk, width, Alignment::Left)), width);
+        }
+
+        let marked = |s: &str, width: usize, align: Alignment| {
+            PaddedStringDisplay {
+                str: s,
+                width,
+                align,
+                truncate: true,
+                ellipsis: true,
+            }
+            .to_string()
+        };
+        assert_eq!(marked(cjk, 5, Alignment::Left), "\u{4e2d}\u{6587}…");
+        assert_eq!(marked(cjk, 4, Alignment::Right), " …\u{7b26}");
+        for width in 0..8 {
+            for align in [Alignment::Left, Alignment::Center, Alignment::Right] {
+                assert_eq!(measure_text_width(&marked(cjk, width, align)), width);
+            }
+        }
+
+        // Combining accents stay with their base character
+        let accents = "e\u{301}a\u{300}o\u{308}u\u{302}";
+        assert_eq!(fit(accents, 3, Alignment::Left), "e\u{301}a\u{300}o\u{308}");
+        assert_eq!(fit(accents, 2, Alignment::Right), "o\u{308}u\u{302}");
+
+        // Escape codes don't count towards the width and are kept
+        let styled = "\u{1b}[31mabcdef\u{1b}[0m";
+        assert_eq!(fit(styled, 3, Alignment::Left), "\u{1b}[31mabc\u{1b}[0m");
+    }
+
+    #[test]
+    fn wide_msg_fills_line() {
+        const WIDTH: u16 = 20;
+        let pos = Arc::new(AtomicPosition::new());
+        pos.set(3);
+        let mut state = ProgressState::new(Some(10), pos);
+        let mut buf = Vec::new();
+
+        let style = ProgressStyle::with_template("[{pos}/{len}] {wide_msg} |").unwrap();
+        state.message = TabExpandedString::NoTabs("short".into());
+        style.format_state(&state, &mut buf, WIDTH);
+        assert_eq!(buf, ["[3/10] short       |"]);
+
+        state.message = TabExpandedString::NoTabs("a message that is too long".into());
+        buf.clear();
+        style.format_state(&state, &mut buf, WIDTH);
+        assert_eq!(buf, ["[3/10] a message … |"]);
+
+        let cut = style.clone().wrap_message(WrapMode::Truncate);
+        buf.clear();
+        cut.format_state(&state, &mut buf, WIDTH);
+        assert_eq!(buf, ["[3/10] a message t |"]);
+
+        let style = style.wrap_message(WrapMode::Ellipsis);
+        buf.clear();
+        style.format_state(&state, &mut buf, WIDTH);
+        assert_eq!(buf, ["[3/10] a message … |"]);
+        assert_eq!(measure_text_width(&buf[0]), WIDTH as usize);
+
+        let err = ProgressStyle::with_template("{wide_msg} {wide_bar}")
+            .err()
+            .unwrap();
+        assert_eq!(err.span(), 12..20);
+        assert!(ProgressStyle::with_template("{wide_msg}\n{wide_bar}").is_ok());
+    }
+
+    #[test]
+    fn ascii_fallback() {
+        const WIDTH: u16 = 40;
+        let pos = Arc::new(AtomicPosition::new());
+        pos.set(5);
+        let mut state = ProgressState::new(Some(10), pos);
+        let mut buf = Vec::new();
+
+        let bar = ProgressStyle::default_bar().ascii();
+        let spinner = ProgressStyle::default_spinner().ascii();
+        assert!(bar.is_ascii() && spinner.is_ascii());
+        for tick in 0..5 {
+            state.tick = tick;
+            bar.format_state(&state, &mut buf, WIDTH);
+            spinner.format_state(&state, &mut buf, WIDTH);
+        }
+        assert!(buf.iter().all(|line| line.is_ascii()), "{buf:?}");
+        assert_eq!(buf[0], "#################>----------------- 5/10");
+
+        // ASCII characters that were set explicitly are kept
+        let custom = ProgressStyle::default_bar().progress_chars("=> ").ascii();
+        assert_eq!(
+            custom.progress_chars,
+            ProgressStyle::default_bar()
+                .progress_chars("=> ")
+                .progress_chars
+        );
+    }
+
+    #[test]
+    fn ascii_glyphs_render_the_live_style() {
+        #[derive(Clone)]
+        struct Ticks(u64);
+
+        impl ProgressTracker for Ticks {
+            fn clone_box(&self) -> Box<dyn ProgressTracker> {
+                Box::new(self.clone())
+            }
+
+            fn tick(&mut self, _: &ProgressState, _: Instant) {
+                self.0 += 1;
+            }
+
+            fn reset(&mut self, _: &ProgressState, _: Instant) {
+                self.0 = 0;
+            }
+
+            fn write(&self, _: &ProgressState, w: &mut dyn fmt::Write) {
+                write!(w, "{}", self.0).unwrap();
+            }
+        }
+
+        let pos = Arc::new(AtomicPosition::new());
+        pos.set(5);
+        let state = ProgressState::new(Some(10), pos);
+        let render = |style: &ProgressStyle| {
+            let mut buf = Vec::new();
+            style.format_state(&state, &mut buf, 40);
+            buf.remove(0)
+        };
+
+        let mut style = ProgressStyle::with_template("{bar:10} {ticks}")
+            .unwrap()
+            .with_key("ticks", Ticks(0));
+        for ticks in 1..=2 {
+            for tracker in style.format_map.values_mut() {
+                tracker.tick(&state, Instant::now());
+            }
+            assert_eq!(
+                style.with_ascii_glyphs(render),
+                format!("#####>---- {ticks}")
+            );
+        }
+
+        // The style's own glyphs are restored afterwards
+        assert_eq!(render(&style), "█████░░░░░ 2");
+    }
+
+    #[test]
+    fn color_scheme() {
+        const WIDTH: u16 = 80;
+        let pos = Arc::new(AtomicPosition::new());
+        pos.set(5);
+        let mut state = ProgressState::new(Some(10), pos);
+        state.message = TabExpandedString::NoTabs("done".into());
+        let mut buf = Vec::new();
+
+        let style = ProgressStyle::with_template("{bar:10} {msg}")
+            .unwrap()
+            .progress_chars("#>-")
+            .color_scheme(ColorScheme {
+                bar_filled: Style::new().green().force_styling(true),
+                bar_empty: Style::new().red().force_styling(true),
+                message: Style::new().bold().force_styling(true),
+                ..ColorScheme::default()
+            });
+        style.format_state(&state, &mut buf, WIDTH);
+        assert_eq!(
+            &buf[0],
+            "\u{1b}[32m#####\u{1b}[0m\u{1b}[32m>\u{1b}[0m\u{1b}[31m----\u{1b}[0m \u{1b}[1mdone\u{1b}[0m"
+        );
+
+        // Inline styles in the template take precedence
+        buf.clear();
+        let style = ProgressStyle::with_template("{bar:10.blue/yellow} {msg:.cyan}")
+            .unwrap()
+            .progress_chars("#>-")
+            .color_scheme(ColorScheme {
+                bar_empty: Style::new().red().force_styling(true),
+                message: Style::new().bold().force_styling(true),
+                ..ColorScheme::default()
+            });
+        style.format_state(&state, &mut buf, WIDTH);
+        assert!(!buf[0].contains("\u{1b}[31m") && !buf[0].contains("\u{1b}[1m"));
+    }
+
+    #[test]
+    fn compiled_template_renders_every_part() {
+        let pos = Arc::new(AtomicPosition::new());
+        let mut state = ProgressState::new(Some(1000), pos.clone());
+        state.prefix = TabExpandedString::NoTabs("extracting".into());
+        let style = ProgressStyle::with_template(
+            "{spinner} {prefix:>8!} [{wide_bar}] {pos:>7}/{len:7} {percent}% {msg:<8}|",
+        )
+        .unwrap()
+        .progress_chars("#>-")
+        .tick_chars("abc");
+
+        // The same compiled template renders each state, at different widths
+        let expected = [
+            (
+                0,
+                60,
+                "a tracting [------------------]       0/1000    0% item 0  |",
+            ),
+            (
+                250,
+                50,
+                "b tracting [#>-----]     250/1000    25% item 250|",
+            ),
+            (1000, 46, "a tracting [#]    1000/1000    100% item 1000|"),
+        ];
+        for (tick, (i, width, line)) in expected.into_iter().enumerate() {
+            pos.set(i);
+            state.tick = tick as u64;
+            state.message = TabExpandedString::NoTabs(format!("item {i}").into());
+            let mut buf = Vec::new();
+            style.format_state(&state, &mut buf, width);
+            assert_eq!(buf, [line]);
+        }
+    }
+
+    #[test]
+    fn style_builder() {
+        let pos = Arc::new(AtomicPosition::new());
+        pos.set(5);
+        let state = ProgressState::new(Some(10), pos);
+        let mut buf = Vec::new();
+
+        let style = ProgressStyleBuilder::new()
+            .with_key("unit", |_: &ProgressState, w: &mut dyn Write| {
+                write!(w, "files").unwrap()
+            })
+            .template("{spinner} {bar:10} {pos} {unit}")
+            .progress_chars("#>-")
+            .tick_strings(&["a", "b", "c"])
+            .build()
+            .unwrap();
+        style.format_state(&state, &mut buf, 80);
+        assert_eq!(&buf[0], "a #####>---- 5 files");
+
+        let err = |builder: ProgressStyleBuilder| builder.build().err().unwrap().to_string();
+        assert_eq!(
+            err(ProgressStyleBuilder::new().template("{bar} {unit}")),
+            "TemplateError: unknown key \"unit\" at 7..11"
+        );
+        assert_eq!(
+            err(ProgressStyleBuilder::new().template("{bar")),
+            "TemplateError: unclosed placeholder at 0..4"
+        );
+        assert_eq!(
+            err(ProgressStyleBuilder::new().progress_chars("#")),
+            "TemplateError: at least 2 progress chars required"
+        );
+        assert_eq!(
+            err(ProgressStyleBuilder::new().progress_chars("\u{4e2d}-")),
+            "TemplateError: progress chars must have equal widths"
+        );
+        assert_eq!(
+            err(ProgressStyleBuilder::new().tick_chars("x")),
+            "TemplateError: at least 2 tick strings required"
+        );
+    }
+
+    #[test]
+    fn bar_marks() {
+        const WIDTH: u16 = 80;
+        let pos = Arc::new(AtomicPosition::new());
+        pos.set(4);
+        let mut state = ProgressState::new(Some(10), pos.clone());
+        let mut buf = Vec::new();
+
+        let style = ProgressStyle::with_template("{bar:20}")
+            .unwrap()
+            .progress_chars("#>-")
+            .bar_marks(&[0.25, 0.5, 0.75], "+", ":");
+        style.format_state(&state, &mut buf, WIDTH);
+        assert_eq!(&buf[0], "#####+##>-:----:----");
+
+        // Marks are counted in the direction the bar fills
+        buf.clear();
+        let reversed = style.clone().reverse(true);
+        reversed.format_state(&state, &mut buf, WIDTH);
+        assert_eq!(&buf[0], "----:----:->##+#####");
+
+        // The cell being filled isn't replaced
+        buf.clear();
+        pos.set(5);
+        style.format_state(&state, &mut buf, WIDTH);
+        assert_eq!(&buf[0], "#####+####>----:----");
+
+        buf.clear();
+        pos.set(0);
+        style.format_state(&state, &mut buf, WIDTH);
+        assert_eq!(&buf[0], "-----:----:----:----");
+
+        buf.clear();
+        state.set_pos(10);
+        style.format_state(&state, &mut buf, WIDTH);
+        assert_eq!(&buf[0], "#####+####+####+####");
+    }
+
+    #[test]
+    #[should_panic(expected = "bar marks of a different width")]
+    fn bar_marks_width() {
+        let _ = ProgressStyle::default_bar().bar_marks(&[0.5], "\u{4e2d}", ":");
+    }
+
+    #[test]
+    #[should_panic(expected = "bar marks of a different width")]
+    fn progress_chars_width_with_bar_marks() {
+        let _ = ProgressStyle::default_bar()
+            .bar_marks(&[0.5], "+", ":")
+            .progress_chars("\u{4e2d}\u{6587}");
+    }
+
+    #[test]
+    fn stacked_bar() {
+        use crate::ProgressBar;
+
+        let style = ProgressStyle::with_template("{bar:100}")
+            .unwrap()
+            .progress_chars("#>-");
+        let widths = |segments: &[(&str, u64)], len| {
+            let segments = segments
+                .iter()
+                .map(|&(name, count)| (name.to_owned(), count, Style::new()))
+                .collect::<Vec<_>>();
+            let bar = style.format_segments(&segments, len, 100, None);
+            let widths = bar.segments.iter().map(|&(n, _)| n).collect::<Vec<_>>();
+            (widths, bar.rest)
+        };
+
+        assert_eq!(
+            widths(&[("done", 10), ("in flight", 20), ("pending", 70)], None),
+            (vec![10, 20, 70], 0)
+        );
+        // Relative to the length if the counts don't add up to it
+        assert_eq!(
+            widths(
+                &[("done", 10), ("in flight", 20), ("pending", 70)],
+                Some(200)
+            ),
+            (vec![5, 10, 35], 50)
+        );
+        // Rounding errors don't accumulate, and zero counts take no space
+        assert_eq!(
+            widths(&[("a", 1), ("b", 0), ("c", 1), ("d", 1)], None),
+            (vec![33, 0, 34, 33], 0)
+        );
+        assert_eq!(widths(&[("a", 0)], None), (vec![0], 100));
+
+        let pb = ProgressBar::hidden()
+            .with_style(style.clone())
+            .with_segment("done", Style::new().green())
+            .with_segment("pending", Style::new().red());
+        pb.set_segment("done", 1);
+        pb.set_segment("pending", 2);
+        pb.set_segment("unknown", 1);
+        pb.set_length(8);
+
+        let mut buf = Vec::new();
+        let state = pb.state();
+        assert_eq!(state.state.segment("pending"), Some(2));
+        assert_eq!(state.state.segment("unknown"), Some(1));
+        style.format_state(&state.state, &mut buf, 120);
+        drop(state);
+        let expected = format!(
+            "{}{}{}{}",
+            Style::new().green().apply_to("#".repeat(13)),
+            Style::new().red().apply_to("#".repeat(25)),
+            "#".repeat(12),
+            "-".repeat(50)
+        );
+        assert_eq!(buf[0], expected);
+    }
+
+    #[test]
+    fn bar_delimiters() {
+        const WIDTH: u16 = 80;
+        let pos = Arc::new(AtomicPosition::new());
+        pos.set(5);
+        let state = ProgressState::new(Some(10), pos);
+        let mut buf = Vec::new();
+
+        let style = ProgressStyle::with_template("{bar:12}")
+            .unwrap()
+            .progress_chars("#>-")
+            .bar_delimiters("(", ")");
+        style.format_state(&state, &mut buf, WIDTH);
+        assert_eq!(&buf[0], "(#####>----)");
+
+        buf.clear();
+        let style = ProgressStyle::with_template("{wide_bar} {pos}/{len}")
+            .unwrap()
+            .progress_chars("#>-")
+            .bar_delimiters("(", ")");
+        style.format_state(&state, &mut buf, 20);
+        assert_eq!(&buf[0], "(######>------) 5/10");
+        assert_eq!(measure_text_width(&buf[0]), 20);
+
+        // Empty delimiters leave the whole width to the bar
+        buf.clear();
+        let style = ProgressStyle::with_template("{bar:12}")
+            .unwrap()
+            .progress_chars("#>-")
+            .bar_delimiters("", "");
+        style.format_state(&state, &mut buf, WIDTH);
+        assert_eq!(&buf[0], "######>-----");
+    }
 }
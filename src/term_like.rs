@@ -34,6 +34,11 @@ pub trait TermLike: Debug + Send + Sync {
     fn clear_line(&self) -> io::Result<()>;
 
     fn flush(&self) -> io::Result<()>;
+
+    /// Show the cursor, in case it was hidden
+    fn show_cursor(&self) -> io::Result<()> {
+        Ok(())
+    }
 }
 
 impl TermLike for Term {
@@ -76,4 +81,8 @@ impl TermLike for Term {
     fn flush(&self) -> io::Result<()> {
         self.flush()
     }
+
+    fn show_cursor(&self) -> io::Result<()> {
+        Term::show_cursor(self)
+    }
 }
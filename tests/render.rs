@@ -1,10 +1,12 @@
 #![cfg(feature = "in_memory")]
 
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use indicatif::{
-    InMemoryTerm, MultiProgress, MultiProgressAlignment, ProgressBar, ProgressDrawTarget,
-    ProgressFinish, ProgressStyle, TermLike,
+    InMemoryTerm, MultiProgress, MultiProgressAlignment, MultiProgressOverflow, ProgressBar,
+    ProgressDrawTarget, ProgressFinish, ProgressState, ProgressStyle, TermLike,
 };
 use pretty_assertions::assert_eq;
 
@@ -127,6 +129,42 @@ fn progress_bar_percent_precise_with_no_length() {
     );
 }
 
+#[test]
+fn progress_bar_percent_precise_small_fraction() {
+    let in_mem = InMemoryTerm::new(10, 80);
+    let pb = ProgressBar::with_draw_target(
+        Some(10_000),
+        ProgressDrawTarget::term_like(Box::new(in_mem.clone())),
+    )
+    .with_style(ProgressStyle::with_template("{percent}% {percent_precise}%").unwrap());
+
+    pb.set_position(37);
+    pb.tick();
+    assert_eq!(in_mem.contents(), "0% 0.370%");
+
+    pb.set_length(0);
+    assert_eq!(in_mem.contents(), "100% 100.000%");
+}
+
+#[test]
+fn progress_bar_decimal_and_binary_bytes() {
+    let in_mem = InMemoryTerm::new(10, 80);
+    let pb = ProgressBar::with_draw_target(
+        Some(1_500_000),
+        ProgressDrawTarget::term_like(Box::new(in_mem.clone())),
+    )
+    .with_style(
+        ProgressStyle::with_template(
+            "{decimal_bytes}/{decimal_total_bytes} {binary_bytes}/{binary_total_bytes}",
+        )
+        .unwrap(),
+    );
+
+    pb.set_position(1500);
+    pb.tick();
+    assert_eq!(in_mem.contents(), "1.50 kB/1.50 MB 1.46 KiB/1.43 MiB");
+}
+
 #[test]
 fn multi_progress_single_bar_and_leave() {
     let in_mem = InMemoryTerm::new(10, 80);
@@ -150,6 +188,197 @@ fn multi_progress_single_bar_and_leave() {
     );
 }
 
+#[test]
+fn set_pos_and_len_draws_once() {
+    let in_mem = InMemoryTerm::new(10, 80);
+    let pb = ProgressBar::with_draw_target(
+        Some(10),
+        ProgressDrawTarget::term_like(Box::new(in_mem.clone())),
+    )
+    .with_style(ProgressStyle::with_template("{pos}/{len}").unwrap());
+    pb.set_position(2);
+    pb.tick();
+    assert_eq!(in_mem.contents(), "2/10");
+    in_mem.moves_since_last_check();
+
+    pb.set_pos_and_len(50, 100);
+    assert_eq!(
+        in_mem.moves_since_last_check(),
+        r#"Clear
+Str("50/100")
+Str("                                                                          ")
+Flush
+"#
+    );
+    assert_eq!(in_mem.contents(), "50/100");
+}
+
+#[test]
+fn multi_progress_default_style() {
+    let in_mem = InMemoryTerm::new(10, 80);
+    let mp =
+        MultiProgress::with_draw_target(ProgressDrawTarget::term_like(Box::new(in_mem.clone())));
+    mp.set_default_style(ProgressStyle::with_template("{msg}: {pos}/{len}").unwrap());
+
+    let pb1 = mp.add(ProgressBar::new(10).with_message("default"));
+    let pb2 = mp.add(
+        ProgressBar::new(10)
+            .with_style(ProgressStyle::with_template("{msg} {pos}").unwrap())
+            .with_message("explicit"),
+    );
+    pb1.inc(3);
+    pb2.inc(4);
+    assert_eq!(in_mem.contents(), "default: 3/10\nexplicit 4");
+}
+
+#[test]
+fn multi_progress_max_visible() {
+    let in_mem = InMemoryTerm::new(10, 80);
+    let mp =
+        MultiProgress::with_draw_target(ProgressDrawTarget::term_like(Box::new(in_mem.clone())))
+            .with_max_visible(2);
+
+    let style = ProgressStyle::with_template("{msg} {pos}/{len}").unwrap();
+    let bars = (0..5)
+        .map(|i| {
+            let pb = mp.add(
+                ProgressBar::new(10)
+                    .with_style(style.clone())
+                    .with_message(format!("bar {i}")),
+            );
+            pb.tick();
+            pb
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(in_mem.contents(), "bar 0 0/10\nbar 1 0/10\n(+3 more)");
+
+    mp.set_overflow(MultiProgressOverflow::MostRecent);
+    bars[4].inc(4);
+    bars[2].inc(2);
+    assert_eq!(in_mem.contents(), "bar 2 2/10\nbar 4 4/10\n(+3 more)");
+
+    // A finished progress bar is left on the screen and makes room for the next one
+    mp.set_overflow(MultiProgressOverflow::First);
+    let mut bars = bars;
+    let first = bars.remove(0);
+    first.abandon();
+    drop(first);
+    bars[0].inc(1);
+    assert_eq!(
+        in_mem.contents(),
+        "bar 0 0/10\nbar 1 1/10\nbar 2 2/10\n(+2 more)"
+    );
+
+    // Progress bars that are left out don't leave any lines behind
+    drop(bars);
+    mp.println("done").unwrap();
+    assert_eq!(in_mem.contents(), "bar 0 0/10\ndone");
+}
+
+#[test]
+fn multi_progress_align_prefixes() {
+    let in_mem = InMemoryTerm::new(10, 80);
+    let mp =
+        MultiProgress::with_draw_target(ProgressDrawTarget::term_like(Box::new(in_mem.clone())));
+    mp.set_align_prefixes(true);
+
+    let style = ProgressStyle::with_template("{prefix} [{bar:10}] {pos}/{len}")
+        .unwrap()
+        .progress_chars("#>-");
+    let pb1 = mp.add(
+        ProgressBar::new(10)
+            .with_style(style.clone())
+            .with_prefix("a"),
+    );
+    let pb2 = mp.add(ProgressBar::new(10).with_style(style).with_prefix("longer"));
+
+    pb1.tick();
+    pb2.tick();
+    pb1.inc(5);
+    assert_eq!(
+        in_mem.contents(),
+        "a      [#####>----] 5/10\nlonger [----------] 0/10"
+    );
+
+    let lines = in_mem.contents();
+    let mut columns = lines.lines().map(|line| line.find('['));
+    assert_eq!(columns.next(), columns.next());
+
+    // Printing above a bar keeps its prefix aligned
+    pb1.println("hello");
+    assert_eq!(
+        in_mem.contents(),
+        "hello\na      [#####>----] 5/10\nlonger [----------] 0/10"
+    );
+
+    // Removing the bar with the longest prefix shrinks the padding again
+    mp.remove(&pb2);
+    pb1.inc(1);
+    assert_eq!(in_mem.contents(), "hello\na [######>---] 6/10");
+}
+
+#[test]
+fn multi_progress_insert_and_move() {
+    let in_mem = InMemoryTerm::new(10, 80);
+    let mp =
+        MultiProgress::with_draw_target(ProgressDrawTarget::term_like(Box::new(in_mem.clone())));
+
+    let style = ProgressStyle::with_template("{msg}").unwrap();
+    let bar = |msg: &'static str| {
+        ProgressBar::new(10)
+            .with_style(style.clone())
+            .with_message(msg)
+    };
+    let pb1 = mp.add(bar("one"));
+    let pb3 = mp.add(bar("three"));
+    let pb2 = mp.insert_after(&pb1, bar("two"));
+    let pb0 = mp.insert(100, bar("zero"));
+    for pb in [&pb0, &pb1, &pb2, &pb3] {
+        pb.tick();
+    }
+    assert_eq!(in_mem.contents(), "one\ntwo\nthree\nzero");
+
+    pb0.finish();
+    mp.move_to(&pb0, 0);
+    mp.move_to(&pb3, 100);
+    pb1.tick();
+    assert_eq!(in_mem.contents(), "zero\none\ntwo\nthree");
+}
+
+#[test]
+fn multi_progress_clear_with_summary() {
+    let in_mem = InMemoryTerm::new(10, 80);
+    let mp =
+        MultiProgress::with_draw_target(ProgressDrawTarget::term_like(Box::new(in_mem.clone())));
+
+    let pb1 = mp.add(ProgressBar::new(10));
+    let pb2 = mp.add(ProgressBar::new(20));
+    pb1.inc(10);
+    pb2.inc(5);
+    pb1.finish();
+    pb2.tick();
+    assert_eq!(in_mem.contents().lines().count(), 2);
+
+    mp.clear_with_summary(|bars| {
+        bars.iter()
+            .map(|bar| format!("{}/{} done", bar.pos, bar.len.unwrap()))
+            .collect()
+    })
+    .unwrap();
+    assert_eq!(in_mem.contents(), "10/10 done\n5/20 done");
+
+    // The summary stays in place when the bars are drawn again
+    pb2.inc(1);
+    let contents = in_mem.contents();
+    assert!(contents.starts_with(
+        "10/10 done
+5/20 done
+"
+    ));
+    assert_eq!(contents.matches("done").count(), 2);
+    assert!(contents.ends_with(" 6/20"));
+}
+
 #[test]
 fn multi_progress_single_bar_and_clear() {
     let in_mem = InMemoryTerm::new(10, 80);
@@ -450,16 +679,16 @@ Flush
 "#
     );
 
+    // Only the changed line is rewritten, along with the last line as it has no padding after it
     pb1.inc(1);
     assert_eq!(
         in_mem.moves_since_last_check(),
         r#"Up(1)
-Str("\r")
+Clear
 Str("███████░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░ 1/10")
-Str("")
-NewLine
+Down(1)
+Clear
 Str("░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░░ 0/10")
-Str("")
 Flush
 "#
     );
@@ -1918,3 +2147,219 @@ fn orphan_lines_message_above_progress_bar_test(pb: &ProgressBar, in_mem: &InMem
 
     pb.finish();
 }
+
+#[test]
+fn spinner_shows_final_tick_when_finished() {
+    let in_mem = InMemoryTerm::new(10, 80);
+    let pb = ProgressBar::with_draw_target(
+        None,
+        ProgressDrawTarget::term_like(Box::new(in_mem.clone())),
+    )
+    .with_style(
+        ProgressStyle::with_template("{spinner} {msg}")
+            .unwrap()
+            .tick_strings(&["-", "\\", "|", "/", "✓"]),
+    )
+    .with_message("working");
+
+    pb.tick();
+    pb.tick();
+    assert_eq!(in_mem.contents(), "| working");
+
+    pb.finish_with_message("done");
+    assert_eq!(in_mem.contents(), "✓ done");
+}
+
+#[test]
+fn multi_progress_println_from_threads() {
+    let in_mem = InMemoryTerm::new(50, 80);
+    let mp =
+        MultiProgress::with_draw_target(ProgressDrawTarget::term_like(Box::new(in_mem.clone())));
+
+    let style = ProgressStyle::with_template("{prefix} {pos}/{len}").unwrap();
+    let bars = ["a", "b"].map(|prefix| {
+        mp.add(
+            ProgressBar::new(20)
+                .with_style(style.clone())
+                .with_prefix(prefix),
+        )
+    });
+
+    let printer = {
+        let mp = mp.clone();
+        std::thread::spawn(move || {
+            for i in 0..10 {
+                mp.println(format!("log {i} line 1\nlog {i} line 2"))
+                    .unwrap();
+            }
+        })
+    };
+    let updaters = bars
+        .iter()
+        .map(|pb| {
+            let pb = pb.clone();
+            std::thread::spawn(move || {
+                for _ in 0..20 {
+                    pb.inc(1);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    printer.join().unwrap();
+    for updater in updaters {
+        updater.join().unwrap();
+    }
+    for pb in &bars {
+        pb.finish();
+    }
+
+    let mut expected = (0..10)
+        .flat_map(|i| [format!("log {i} line 1"), format!("log {i} line 2")])
+        .collect::<Vec<_>>();
+    expected.extend(["a 20/20".to_string(), "b 20/20".to_string()]);
+    assert_eq!(in_mem.contents(), expected.join("\n"));
+}
+
+#[test]
+fn multi_progress_hide_and_unhide() {
+    let in_mem = InMemoryTerm::new(10, 80);
+    let mp =
+        MultiProgress::with_draw_target(ProgressDrawTarget::term_like(Box::new(in_mem.clone())));
+
+    let style = ProgressStyle::with_template("{prefix} {pos}/{len}").unwrap();
+    let pb1 = mp.add(
+        ProgressBar::new(10)
+            .with_style(style.clone())
+            .with_prefix("first"),
+    );
+    let pb2 = mp.add(ProgressBar::new(10).with_style(style).with_prefix("second"));
+
+    pb1.inc(3);
+    pb2.inc(1);
+    assert_eq!(in_mem.contents(), "first 3/10\nsecond 1/10");
+
+    pb1.set_visible(false);
+    assert!(!pb1.is_visible());
+    assert_eq!(in_mem.contents(), "second 1/10");
+
+    // Updates while hidden are kept, but not shown
+    pb1.inc(4);
+    pb2.inc(1);
+    assert_eq!(in_mem.contents(), "second 2/10");
+    assert!(!pb1.is_finished());
+
+    pb1.set_visible(true);
+    assert_eq!(in_mem.contents(), "first 7/10\nsecond 2/10");
+}
+
+#[test]
+fn multi_line_template() {
+    let in_mem = InMemoryTerm::new(10, 80);
+    let mp =
+        MultiProgress::with_draw_target(ProgressDrawTarget::term_like(Box::new(in_mem.clone())));
+
+    let style = ProgressStyle::with_template("{prefix}\n[{bar:10}] {pos}/{len}").unwrap();
+    let pb1 = mp.add(
+        ProgressBar::new(10)
+            .with_style(style.clone())
+            .with_prefix("Downloading"),
+    );
+    let pb2 = mp.add(
+        ProgressBar::new(10)
+            .with_style(style)
+            .with_prefix("Unpacking"),
+    );
+
+    pb1.inc(5);
+    pb2.inc(2);
+    assert_eq!(
+        in_mem.contents(),
+        "Downloading\n[█████░░░░░] 5/10\nUnpacking\n[██░░░░░░░░] 2/10"
+    );
+
+    // Both lines of the first bar are cleared and the second bar moves up
+    pb1.finish_and_clear();
+    assert_eq!(in_mem.contents(), "Unpacking\n[██░░░░░░░░] 2/10");
+
+    pb2.finish_and_clear();
+    assert_eq!(in_mem.contents(), "");
+}
+
+#[test]
+fn multi_line_template_single_bar() {
+    let in_mem = InMemoryTerm::new(10, 80);
+    let pb = ProgressBar::with_draw_target(
+        Some(10),
+        ProgressDrawTarget::term_like(Box::new(in_mem.clone())),
+    )
+    .with_style(ProgressStyle::with_template("{msg}\n{pos}/{len}").unwrap())
+    .with_message("title");
+
+    pb.inc(3);
+    assert_eq!(in_mem.contents(), "title\n3/10");
+
+    pb.println("log line");
+    assert_eq!(in_mem.contents(), "log line\ntitle\n3/10");
+
+    pb.finish_and_clear();
+    assert_eq!(in_mem.contents(), "log line");
+}
+
+#[test]
+fn redraw_shows_custom_key_changes() {
+    let in_mem = InMemoryTerm::new(10, 80);
+    let pb = ProgressBar::with_draw_target(
+        Some(10),
+        ProgressDrawTarget::term_like(Box::new(in_mem.clone())),
+    );
+    let status = Arc::new(Mutex::new("starting"));
+    let status_key = status.clone();
+    pb.set_style(
+        ProgressStyle::with_template("{pos}/{len} {status}")
+            .unwrap()
+            .with_key("status", move |_: &ProgressState, w: &mut dyn Write| {
+                write!(w, "{}", status_key.lock().unwrap()).unwrap()
+            }),
+    );
+    pb.set_draw_rate_limit(Duration::from_secs(60));
+
+    pb.inc(1);
+    assert_eq!(in_mem.contents(), "1/10 starting");
+
+    *status.lock().unwrap() = "working";
+    pb.tick();
+    assert_eq!(in_mem.contents(), "1/10 starting");
+    pb.redraw();
+    assert_eq!(in_mem.contents(), "1/10 working");
+
+    pb.finish_and_clear();
+    pb.redraw();
+    assert_eq!(in_mem.contents(), String::new());
+}
+
+#[test]
+fn line_count_follows_template() {
+    let in_mem = InMemoryTerm::new(10, 20);
+    let mp =
+        MultiProgress::with_draw_target(ProgressDrawTarget::term_like(Box::new(in_mem.clone())));
+    let pb = mp.add(
+        ProgressBar::new(10)
+            .with_style(ProgressStyle::with_template("{msg}\n{pos}/{len}").unwrap()),
+    );
+    assert_eq!(pb.line_count(), 0);
+
+    pb.set_message("copying");
+    assert_eq!(in_mem.contents(), "copying\n0/10");
+    assert_eq!(pb.line_count(), 2);
+
+    // A message longer than the terminal is wide wraps onto another row
+    pb.set_message("copying a lot of files");
+    assert_eq!(pb.line_count(), 3);
+
+    pb.set_visible(false);
+    assert_eq!(pb.line_count(), 0);
+    pb.set_visible(true);
+    pb.finish_and_clear();
+    assert_eq!(pb.line_count(), 0);
+}